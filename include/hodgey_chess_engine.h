@@ -0,0 +1,39 @@
+/* C declarations for the hodgey_chess_engine dynamic library, built with
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ * See src/ffi.rs for what each function does. */
+
+#ifndef HODGEY_CHESS_ENGINE_H
+#define HODGEY_CHESS_ENGINE_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct HodgeyEngine HodgeyEngine;
+
+typedef struct HodgeySearchResult {
+    /* The best move in UCI notation, NUL terminated. Empty if there is no legal move. */
+    char best_move[8];
+    /* Centipawns from the point of view of the side to move. */
+    int32_t score;
+    uint16_t depth;
+    uint64_t nodes;
+} HodgeySearchResult;
+
+HodgeyEngine *hodgey_new(void);
+void hodgey_free(HodgeyEngine *engine);
+
+bool hodgey_set_position_fen(HodgeyEngine *engine, const char *fen);
+bool hodgey_play_move(HodgeyEngine *engine, const char *uci_move);
+
+HodgeySearchResult hodgey_search_movetime(HodgeyEngine *engine, uint64_t movetime_ms);
+HodgeySearchResult hodgey_search_depth(HodgeyEngine *engine, uint16_t depth);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,287 @@
+//! Reviewing moves that were played.
+//!
+//! A played move is judged by how many centipawns it gives away compared to the best move. Every root
+//! move is searched for an exact score so the played move is scored at the same depth as the best one.
+//! Scores are capped at ten pawns either way first, so the difference between a quick and a slow mate,
+//! or between being a queen and a rook up, isn't held against a move. Whole games are reviewed by
+//! analyzing each of their moves in turn, and with the `pgn` feature they can be read from and
+//! written back to PGN with the analysis as comments.
+
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, Color, EnPassantMode, Move};
+
+#[cfg(feature = "pgn")]
+use shakmaty::san::San;
+#[cfg(feature = "pgn")]
+use shakmaty::Position;
+
+use crate::evaluator::Evaluator;
+#[cfg(feature = "pgn")]
+use crate::mate_in;
+#[cfg(feature = "pgn")]
+use crate::pgn::PgnGame;
+use crate::variant::SearchPosition;
+use crate::{Engine, SearchLimits};
+
+//Scores beyond this are about as won or lost as it gets
+const MAX_COUNTED_SCORE: i32 = 1_000;
+const INACCURACY_LOSS: i32 = 50;
+const MISTAKE_LOSS: i32 = 100;
+const BLUNDER_LOSS: i32 = 300;
+
+/// How a played move compares to the best move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MoveQuality {
+    /// The engine's own choice.
+    Best,
+    /// Another move that gives away less than half a pawn.
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder
+}
+
+impl MoveQuality {
+    /// The quality of a move that gives away `centipawn_loss`.
+    pub fn from_loss(centipawn_loss: i32) -> MoveQuality {
+        match centipawn_loss {
+            loss if loss >= BLUNDER_LOSS => MoveQuality::Blunder,
+            loss if loss >= MISTAKE_LOSS => MoveQuality::Mistake,
+            loss if loss >= INACCURACY_LOSS => MoveQuality::Inaccuracy,
+            _ => MoveQuality::Good
+        }
+    }
+}
+
+/// What the engine thinks of a played move. Scores are from the point of view of the side that played it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveAnalysis {
+    pub best_move: Move,
+    pub best_score: i32,
+    pub played_move: Move,
+    pub played_score: i32,
+    /// How much worse the played move is than the best move, never negative.
+    pub centipawn_loss: i32,
+    pub quality: MoveQuality
+}
+
+impl<P: SearchPosition, E: Evaluator<P>> Engine<P, E> {
+    /// Searches `chess` within `limits` and compares `played_move` to the best move.
+    /// Returns `None` if `played_move` isn't legal in `chess`.
+    pub fn analyze_move(&mut self, chess: &P, played_move: &Move, limits: SearchLimits,
+                        previously_seen_hashes: &mut Vec<u64>) -> Option<MoveAnalysis> {
+        if !chess.is_legal(played_move) {
+            return None;
+        }
+
+        let (_, root_moves) = self.search_multi_pv(chess, limits, previously_seen_hashes, usize::MAX);
+        let best = root_moves.first()?;
+        let played = root_moves.iter().find(|root_move| &root_move.mv == played_move)?;
+
+        let centipawn_loss = (capped(best.score) - capped(played.score)).max(0);
+        Some(MoveAnalysis {
+            best_move: best.mv.clone(),
+            best_score: best.score,
+            played_move: played.mv.clone(),
+            played_score: played.score,
+            centipawn_loss,
+            quality: if played.mv == best.mv {MoveQuality::Best} else {MoveQuality::from_loss(centipawn_loss)}
+        })
+    }
+
+    /// Analyzes every move of the game played from `start`, searching each position within `limits`.
+    /// Returns `None` if any of the moves is illegal.
+    pub fn analyze_game(&mut self, start: &P, moves: &[Move], limits: SearchLimits) -> Option<GameAnalysis> {
+        let mut chess = start.clone();
+        //Positions since the last capture or pawn move, so repetitions are seen as draws
+        let mut previously_seen_hashes = vec![hash(&chess)];
+        let mut analyzed = Vec::with_capacity(moves.len());
+
+        for played_move in moves {
+            analyzed.push(self.analyze_move(&chess, played_move, limits, &mut previously_seen_hashes)?);
+
+            if played_move.is_zeroing() {
+                previously_seen_hashes.clear();
+            }
+            chess.play_unchecked(played_move);
+            previously_seen_hashes.push(hash(&chess));
+        }
+
+        Some(GameAnalysis {
+            first_mover: start.turn(),
+            moves: analyzed
+        })
+    }
+}
+
+/// Every move of a game, analyzed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameAnalysis {
+    /// The side that played the first move.
+    pub first_mover: Color,
+    pub moves: Vec<MoveAnalysis>
+}
+
+impl GameAnalysis {
+    /// The side that played the move at `index`.
+    pub fn mover(&self, index: usize) -> Color {
+        if index.is_multiple_of(2) {self.first_mover} else {!self.first_mover}
+    }
+
+    /// The evaluation of the position before each move from white's point of view, for drawing a graph of the game.
+    pub fn evaluations(&self) -> Vec<i32> {
+        self.moves.iter().enumerate().map(|(index, analysis)| {
+            if self.mover(index).is_white() {analysis.best_score} else {-analysis.best_score}
+        }).collect()
+    }
+
+    /// The average centipawn loss of `color`'s moves, 0 if it played none.
+    pub fn average_centipawn_loss(&self, color: Color) -> f64 {
+        let losses: Vec<i32> = self.by_color(color).map(|analysis| analysis.centipawn_loss).collect();
+        if losses.is_empty() {0.0} else {losses.iter().sum::<i32>() as f64 / losses.len() as f64}
+    }
+
+    /// How many of `color`'s moves were of the given `quality`.
+    pub fn count(&self, color: Color, quality: MoveQuality) -> usize {
+        self.by_color(color).filter(|analysis| analysis.quality == quality).count()
+    }
+
+    fn by_color(&self, color: Color) -> impl Iterator<Item = &MoveAnalysis> {
+        self.moves.iter().enumerate().filter(move |&(index, _)| self.mover(index) == color).map(|(_, analysis)| analysis)
+    }
+}
+
+#[cfg(feature = "pgn")]
+impl GameAnalysis {
+    /// `game` with the analysis of each move as its comment, such as "-1.20 Mistake, Nf3 was best".
+    /// Scores are in pawns from white's point of view after the move, and comments already in `game` are replaced.
+    pub fn annotate(&self, game: &PgnGame) -> PgnGame {
+        let mut annotated = game.clone();
+        let mut chess = game.start.clone();
+
+        for (pgn_move, analysis) in annotated.moves.iter_mut().zip(&self.moves) {
+            let white_score = if chess.turn().is_white() {analysis.played_score} else {-analysis.played_score};
+            let score = match mate_in(analysis.played_score) {
+                Some(mate_in) if (mate_in >= 0) == chess.turn().is_white() => format!("+M{}", mate_in.abs()),
+                Some(mate_in) => format!("-M{}", mate_in.abs()),
+                None => format!("{:+.2}", white_score as f64 / 100.0)
+            };
+
+            let quality = match analysis.quality {
+                MoveQuality::Inaccuracy => Some("Inaccuracy"),
+                MoveQuality::Mistake => Some("Mistake"),
+                MoveQuality::Blunder => Some("Blunder"),
+                MoveQuality::Best | MoveQuality::Good => None
+            };
+            pgn_move.comment = Some(match quality {
+                Some(quality) => format!("{} {}, {} was best", score, quality, San::from_move(&chess, &analysis.best_move)),
+                None => score
+            });
+
+            chess.play_unchecked(&pgn_move.mv);
+        }
+
+        annotated
+    }
+}
+
+#[cfg(feature = "pgn")]
+impl<E: Evaluator> Engine<Chess, E> {
+    /// Analyzes every move of a game read from PGN, see [`Engine::analyze_game`].
+    pub fn analyze_pgn(&mut self, game: &PgnGame, limits: SearchLimits) -> Option<GameAnalysis> {
+        self.analyze_game(&game.start, &game.move_list(), limits)
+    }
+}
+
+/// Analyzes a game played from the standard starting position, see [`Engine::analyze_game`].
+pub fn analyze_game(moves: &[Move], limits_per_move: SearchLimits) -> Option<GameAnalysis> {
+    Engine::new().analyze_game(&Chess::default(), moves, limits_per_move)
+}
+
+/// Compares `played_move` in `chess` to the best move, see [`Engine::analyze_move`].
+pub fn analyze_move<P: SearchPosition>(chess: &P, played_move: &Move, limits: SearchLimits) -> Option<MoveAnalysis> {
+    Engine::new().analyze_move(chess, played_move, limits, &mut Vec::new())
+}
+
+fn hash<P: SearchPosition>(chess: &P) -> u64 {
+    let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+    hash.0
+}
+
+fn capped(score: i32) -> i32 {
+    score.clamp(-MAX_COUNTED_SCORE, MAX_COUNTED_SCORE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::uci::UciMove;
+    use shakmaty::{CastlingMode, FromSetup, Position, Role, Square};
+    use crate::REALLY_BIG_CHECKMATE_NUMBER;
+
+    #[test]
+    //Missing the mate in one should be a blunder
+    fn played_moves() {
+        let setup = Fen::from_ascii("6k1/5ppp/8/8/8/8/5q2/K2R4 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+
+        let mate = Move::Normal { role: Role::Rook, from: Square::D1, capture: None, to: Square::D8, promotion: None };
+        let analysis = analyze_move(&chess, &mate, limits).expect("the move is legal");
+        assert!(analysis.quality == MoveQuality::Best && analysis.centipawn_loss == 0);
+
+        let blunder = Move::Normal { role: Role::Rook, from: Square::D1, capture: None, to: Square::D2, promotion: None };
+        let analysis = analyze_move(&chess, &blunder, limits).expect("the move is legal");
+        assert!(analysis.best_move == mate && analysis.played_move == blunder);
+        assert!(analysis.quality == MoveQuality::Blunder && analysis.played_score < analysis.best_score);
+
+        let illegal = Move::Normal { role: Role::Rook, from: Square::D1, capture: None, to: Square::E2, promotion: None };
+        assert!(analyze_move(&chess, &illegal, limits).is_none());
+    }
+
+    #[test]
+    //A game should be analyzed move by move, with the blunder held against the side that played it
+    fn game_review() {
+        let mut chess = Chess::default();
+        let moves: Vec<Move> = ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"].iter().map(|uci| {
+            let mv = uci.parse::<UciMove>().expect("move should be valid").to_move(&chess).expect("move should be legal");
+            chess.play_unchecked(&mv);
+            mv
+        }).collect();
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let analysis = analyze_game(&moves, limits).expect("the moves are legal");
+
+        assert!(analysis.moves.len() == 7 && analysis.evaluations().len() == 7);
+        assert!(analysis.mover(5) == Color::Black);
+        assert!(analysis.moves[5].quality == MoveQuality::Blunder);
+        assert!(analysis.moves[6].quality == MoveQuality::Best);
+        assert!(analysis.evaluations()[6] >= REALLY_BIG_CHECKMATE_NUMBER);
+        assert!(analysis.average_centipawn_loss(Color::Black) > analysis.average_centipawn_loss(Color::White));
+
+        assert!(analyze_game(&moves[1..], limits).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "pgn")]
+    //Annotated games should call out the blunder and the best move instead
+    fn annotated_pgn() {
+        let game = crate::pgn::parse_pgn("1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0").expect("game should be valid");
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let analysis = Engine::new().analyze_pgn(&game, limits).expect("the moves are legal");
+
+        let annotated = analysis.annotate(&game);
+        assert!(annotated.moves[5].comment.as_deref().is_some_and(|comment| comment.starts_with("+M1 Blunder") && comment.ends_with("was best")));
+        assert!(annotated.moves[6].comment.as_deref() == Some("+M1"));
+        assert!(annotated.to_string().contains("4. Qxf7# {+M1} 1-0"));
+    }
+
+    #[test]
+    //Losses should be sorted into the right classes
+    fn quality_thresholds() {
+        assert!(MoveQuality::from_loss(0) == MoveQuality::Good);
+        assert!(MoveQuality::from_loss(INACCURACY_LOSS) == MoveQuality::Inaccuracy);
+        assert!(MoveQuality::from_loss(MISTAKE_LOSS + 1) == MoveQuality::Mistake);
+        assert!(MoveQuality::from_loss(BLUNDER_LOSS) == MoveQuality::Blunder);
+    }
+}
@@ -0,0 +1,116 @@
+//! Searching from async code, with the `tokio` feature.
+//!
+//! [`Engine::search_async`] runs a search on tokio's blocking threads, so awaiting it leaves the runtime free
+//! for other tasks such as the rest of a bot's games, and [`Engine::search_with_progress`] also streams
+//! the search's [`SearchEvent`]s as they happen. Both have to be awaited within a tokio runtime.
+
+use std::future::Future;
+use std::panic;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task;
+
+use crate::evaluator::Evaluator;
+use crate::variant::SearchPosition;
+use crate::{Engine, SearchEvent, SearchHandle, SearchLimits, SearchResult};
+
+/// Stops the search if the future running it is dropped before it finishes.
+struct StopOnDrop(Option<SearchHandle>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            handle.stop();
+        }
+    }
+}
+
+impl<P, E> Engine<P, E> where P: SearchPosition + Send + 'static, E: Evaluator<P> + Default + Send + Sync + 'static {
+    /// Searches `chess` as [`Engine::search`] does, on tokio's blocking threads.
+    /// Dropping the future before it finishes stops the search and leaves a new engine in place of this one.
+    pub async fn search_async(&mut self, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>) -> SearchResult {
+        let mut engine = std::mem::take(self);
+        let mut stop_on_drop = StopOnDrop(Some(engine.handle()));
+        let chess = chess.clone();
+        let mut hashes = previously_seen_hashes.clone();
+
+        let searched = task::spawn_blocking(move || {
+            let result = engine.search(&chess, limits, &mut hashes);
+            (engine, result, hashes)
+        }).await;
+        stop_on_drop.0 = None;
+
+        let (engine, result, hashes) = searched.unwrap_or_else(|error| panic::resume_unwind(error.into_panic()));
+        *self = engine;
+        *previously_seen_hashes = hashes;
+        result
+    }
+
+    /// Searches like [`Engine::search_async`], sending the search's events to the returned receiver instead
+    /// of the engine's progress callback. The receiver ends once the search has finished.
+    pub fn search_with_progress<'a>(&'a mut self, chess: &'a P, limits: SearchLimits, previously_seen_hashes: &'a mut Vec<u64>)
+        -> (impl Future<Output = SearchResult> + 'a, UnboundedReceiver<SearchEvent>) {
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let search = async move {
+            let progress_callback = self.progress_callback.take();
+            self.set_progress_callback(Some(Box::new(move |event| {
+                //Nobody is listening once the receiver is dropped, which doesn't matter to the search
+                let _ = sender.send(event.clone());
+            })));
+            let result = self.search_async(chess, limits, previously_seen_hashes).await;
+            //Dropping the sender along with the callback ends the receiver
+            self.progress_callback = progress_callback;
+            result
+        };
+        (search, receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::Chess;
+    use tokio::runtime::{Builder, Runtime};
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread().build().expect("runtime should start")
+    }
+
+    #[test]
+    //An awaited search should find what a blocking one does and hand the engine back
+    fn awaited_search() {
+        let chess = Chess::new();
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        let expected = Engine::<Chess>::new().search(&chess, limits, &mut Vec::new());
+
+        let mut engine: Engine = Engine::new();
+        engine.set_contempt(15);
+        let mut previously_seen_hashes = vec![1, 2, 3];
+        let result = runtime().block_on(engine.search_async(&chess, limits, &mut previously_seen_hashes));
+        assert!(result.best_move == expected.best_move && result.nodes == expected.nodes);
+        assert!(engine.options().contempt == 15 && previously_seen_hashes == vec![1, 2, 3]);
+    }
+
+    #[test]
+    //Every iteration should be streamed before the stream ends with the search
+    fn progress_stream() {
+        let chess = Chess::new();
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        let mut engine: Engine = Engine::new();
+        let mut previously_seen_hashes = Vec::new();
+
+        let (result, depths) = runtime().block_on(async {
+            let (search, mut events) = engine.search_with_progress(&chess, limits, &mut previously_seen_hashes);
+            let result = search.await;
+            let mut depths = Vec::new();
+            while let Some(event) = events.recv().await {
+                if let SearchEvent::Iteration(iteration) = event {
+                    depths.push(iteration.depth);
+                }
+            }
+            (result, depths)
+        });
+        assert!(result.depth == 4 && depths == vec![1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, FromSetup, Move};
+
+use crate::{test_fens, Engine, SearchLimits};
+
+const BENCH_DEPTH: u16 = 5;
+const BENCH_POSITION_COUNT: usize = 12;
+
+/// What the bench search found in one position.
+pub struct BenchEntry {
+    pub nodes: u64,
+    pub best_move: Option<Move>,
+    pub time: Duration
+}
+
+/// The bench positions, the first few positions from the Win at Chess suite.
+pub fn bench_positions() -> impl Iterator<Item = Chess> {
+    test_fens::WIN_AT_CHESS.iter().take(BENCH_POSITION_COUNT).map(|fen| {
+        let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+        Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+    })
+}
+
+/// Searches every bench position to the bench depth with a fresh engine.
+pub fn run_bench() -> Vec<BenchEntry> {
+    let mut engine = Engine::new();
+    let limits = SearchLimits { max_depth: Some(BENCH_DEPTH), ..SearchLimits::default() };
+
+    bench_positions().map(|chess| {
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        BenchEntry {
+            nodes: result.nodes,
+            best_move: result.best_move,
+            time: result.time
+        }
+    }).collect()
+}
+
+/// Hashes the node counts and best moves of a bench run with FNV-1a, which is stable across platforms.
+pub fn signature(entries: &[BenchEntry]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+
+    for entry in entries {
+        write(&entry.nodes.to_le_bytes());
+        match &entry.best_move {
+            Some(best_move) => write(best_move.to_uci(CastlingMode::Standard).to_string().as_bytes()),
+            None => write(b"0000")
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Any change to what the search does changes this. When that's intended, update it to the new
+    //value printed by the failing test so the change shows up in review.
+    const BENCH_SIGNATURE: u64 = 0x941c_df35_15a9_feef;
+
+    #[test]
+    //Guards against accidental changes to search behaviour
+    fn bench_signature_is_unchanged() {
+        let signature = Engine::bench_signature();
+        assert!(signature == BENCH_SIGNATURE, "bench signature changed to {:#x}", signature);
+    }
+}
@@ -0,0 +1,364 @@
+//! Polyglot opening books.
+//!
+//! A book is a list of 16 byte entries sorted by key. Each entry holds a position's zobrist key, a move
+//! and a weight, all big endian. Shakmaty's zobrist keys are the ones Polyglot uses, so positions can be
+//! looked up directly.
+//!
+//! Books can also be built from games with [`BookBuilder`]. As in Polyglot's own book maker, every move
+//! scores 2 for a win and 1 for a draw for the side that played it, and the scores become the weights.
+
+use std::collections::HashMap;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::fs;
+use std::io;
+#[cfg(feature = "pgn")]
+use std::io::BufRead;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::path::Path;
+
+use shakmaty::{Chess, EnPassantMode, Move, Outcome, Position, Role, Square};
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+
+#[cfg(feature = "pgn")]
+use crate::pgn::{PgnGame, PgnReader};
+
+const ENTRY_LENGTH: usize = 16;
+const WIN_SCORE: u64 = 2;
+const DRAW_SCORE: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BookEntry {
+    key: u64,
+    mv: u16,
+    weight: u16
+}
+
+/// An opening book loaded from a Polyglot .bin file.
+#[derive(Clone, Debug, Default)]
+pub struct Book {
+    entries: Vec<BookEntry>
+}
+
+impl Book {
+    /// Reads the Polyglot .bin file at `path`. Not on wasm32-unknown-unknown, which has no files, where
+    /// books come from [`Book::from_bytes`].
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Book> {
+        Book::from_bytes(&fs::read(path)?)
+    }
+
+    /// Reads a book from the contents of a Polyglot .bin file.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Book> {
+        if !bytes.len().is_multiple_of(ENTRY_LENGTH) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "book length isn't a multiple of the entry length"));
+        }
+
+        let mut entries: Vec<BookEntry> = bytes.chunks_exact(ENTRY_LENGTH).map(|entry| BookEntry {
+            key: u64::from_be_bytes(entry[0..8].try_into().expect("slice is 8 bytes")),
+            mv: u16::from_be_bytes([entry[8], entry[9]]),
+            weight: u16::from_be_bytes([entry[10], entry[11]])
+        }).collect();
+        //Books should already be sorted, but lookups would silently miss positions if one wasn't
+        entries.sort_by_key(|entry| entry.key);
+
+        Ok(Book { entries })
+    }
+
+    /// Writes the book as a Polyglot .bin file.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// The contents of the book as a Polyglot .bin file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_LENGTH);
+        for entry in &self.entries {
+            bytes.extend(entry.key.to_be_bytes());
+            bytes.extend(entry.mv.to_be_bytes());
+            bytes.extend(entry.weight.to_be_bytes());
+            //The learn field is left empty
+            bytes.extend(0u32.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every legal book move for `position` along with its weight.
+    pub fn moves<P: Position>(&self, position: &P) -> Vec<(Move, u16)> {
+        let key: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+        let start = self.entries.partition_point(|entry| entry.key < key.0);
+
+        self.entries[start..].iter()
+            .take_while(|entry| entry.key == key.0)
+            .filter_map(|entry| Some((decode_move(position, entry.mv)?, entry.weight)))
+            .collect()
+    }
+
+    /// Picks a book move with probability proportional to its weight, using `random` as the source of randomness.
+    pub fn pick<P: Position>(&self, position: &P, random: u64) -> Option<Move> {
+        let moves: Vec<(Move, u16)> = self.moves(position).into_iter().filter(|&(_, weight)| weight > 0).collect();
+        let total_weight: u64 = moves.iter().map(|&(_, weight)| weight as u64).sum();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut target = random % total_weight;
+        for (mv, weight) in moves {
+            if target < weight as u64 {
+                return Some(mv);
+            }
+            target -= weight as u64;
+        }
+        None
+    }
+}
+
+/// Which games go into a book and how much of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookSettings {
+    /// Only the first this many plies of each game are added.
+    pub max_ply: u32,
+    /// Games are skipped unless both players are rated at least this much.
+    pub min_rating: Option<u32>,
+    /// Moves played fewer times than this are left out.
+    pub min_count: u32
+}
+
+impl Default for BookSettings {
+    fn default() -> BookSettings {
+        BookSettings {
+            max_ply: 24,
+            min_rating: None,
+            min_count: 1
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MoveStats {
+    count: u32,
+    score: u64
+}
+
+/// Collects moves from games and turns them into a [`Book`].
+#[derive(Clone, Debug, Default)]
+pub struct BookBuilder {
+    settings: BookSettings,
+    moves: HashMap<(u64, u16), MoveStats>
+}
+
+impl BookBuilder {
+    pub fn new(settings: BookSettings) -> BookBuilder {
+        BookBuilder {
+            settings,
+            moves: HashMap::new()
+        }
+    }
+
+    /// Adds the opening moves of a game played from `start` that ended in `outcome`.
+    pub fn add_game(&mut self, start: &Chess, moves: &[Move], outcome: Outcome) {
+        let mut chess = start.clone();
+
+        for mv in moves.iter().take(self.settings.max_ply as usize) {
+            //Drops and moves Polyglot can't write end what can go in the book
+            let Some(encoded) = encode_move(mv) else {
+                break;
+            };
+            let key: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+            let stats = self.moves.entry((key.0, encoded)).or_default();
+            stats.count += 1;
+            stats.score += match outcome {
+                Outcome::Decisive { winner } if winner == chess.turn() => WIN_SCORE,
+                Outcome::Decisive { .. } => 0,
+                Outcome::Draw => DRAW_SCORE
+            };
+            chess.play_unchecked(mv);
+        }
+    }
+
+    /// Adds a game read from PGN, unless it has no result or its players are rated too low.
+    /// Returns whether the game was added.
+    #[cfg(feature = "pgn")]
+    pub fn add_pgn_game(&mut self, game: &PgnGame) -> bool {
+        let rated = |tag| game.tag(tag).and_then(|rating| rating.parse::<u32>().ok());
+        let rated_enough = self.settings.min_rating.is_none_or(|min_rating| {
+            rated("WhiteElo").is_some_and(|rating| rating >= min_rating) && rated("BlackElo").is_some_and(|rating| rating >= min_rating)
+        });
+        let Some(outcome) = game.outcome.filter(|_| rated_enough) else {
+            return false;
+        };
+
+        self.add_game(&game.start, &game.move_list(), outcome);
+        true
+    }
+
+    /// The book of every move played often enough that scored at least once. Weights are scaled down
+    /// for positions whose best move scored more than a weight can hold.
+    pub fn build(&self) -> Book {
+        let mut by_key: HashMap<u64, Vec<(u16, u64)>> = HashMap::new();
+        for (&(key, mv), stats) in &self.moves {
+            if stats.count >= self.settings.min_count && stats.score > 0 {
+                by_key.entry(key).or_default().push((mv, stats.score));
+            }
+        }
+
+        let mut entries = Vec::new();
+        for (key, moves) in by_key {
+            let max_score = moves.iter().map(|&(_, score)| score).max().unwrap_or(0);
+            let scale = max_score.div_ceil(u16::MAX as u64).max(1);
+            entries.extend(moves.into_iter().map(|(mv, score)| BookEntry {
+                key,
+                mv,
+                weight: (score / scale).max(1) as u16
+            }));
+        }
+        //Best moves first within a position, as Polyglot books are usually written
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.mv));
+
+        Book { entries }
+    }
+}
+
+/// Builds a book out of every game in a PGN database.
+#[cfg(feature = "pgn")]
+pub fn build_book(reader: impl BufRead, settings: BookSettings) -> io::Result<Book> {
+    let mut builder = BookBuilder::new(settings);
+    for game in PgnReader::new(reader) {
+        builder.add_pgn_game(&game?);
+    }
+    Ok(builder.build())
+}
+
+/// Writes a move the way Polyglot does, `None` for drops and other moves it has no way to write.
+fn encode_move(mv: &Move) -> Option<u16> {
+    let (from, to) = match *mv {
+        Move::Castle { king, rook } => (king, rook),
+        Move::Put { .. } => return None,
+        _ => (mv.from()?, mv.to())
+    };
+    let promotion = match mv.promotion() {
+        None => 0,
+        Some(Role::Knight) => 1,
+        Some(Role::Bishop) => 2,
+        Some(Role::Rook) => 3,
+        Some(Role::Queen) => 4,
+        Some(_) => return None
+    };
+    Some(promotion << 12 | (from as u16) << 6 | to as u16)
+}
+
+/// Turns a Polyglot move into the matching legal move. Castling is written as the king capturing its own rook.
+fn decode_move<P: Position>(position: &P, encoded: u16) -> Option<Move> {
+    let to = Square::new((encoded & 0x3f) as u32);
+    let from = Square::new(((encoded >> 6) & 0x3f) as u32);
+    let promotion = match (encoded >> 12) & 0x7 {
+        0 => None,
+        1 => Some(Role::Knight),
+        2 => Some(Role::Bishop),
+        3 => Some(Role::Rook),
+        4 => Some(Role::Queen),
+        _ => return None
+    };
+
+    position.legal_moves().into_iter().find(|mv| match mv {
+        Move::Castle { king, rook } => *king == from && *rook == to,
+        _ => mv.from() == Some(from) && mv.to() == to && mv.promotion() == promotion
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, Chess, FromSetup};
+
+    fn entry(chess: &Chess, from: Square, to: Square, weight: u16) -> Vec<u8> {
+        let key: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+        let mv = (from as u16) << 6 | to as u16;
+        let mut bytes = key.0.to_be_bytes().to_vec();
+        bytes.extend(mv.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    //Book moves should be found by position and picked by weight
+    fn probing() {
+        let chess = Chess::new();
+        let mut bytes = entry(&chess, Square::E2, Square::E4, 3);
+        bytes.extend(entry(&chess, Square::D2, Square::D4, 1));
+        let book = Book::from_bytes(&bytes).expect("book should be valid");
+        assert!(book.len() == 2);
+
+        let moves: Vec<String> = book.moves(&chess).iter().map(|(mv, _)| mv.to_string()).collect();
+        assert!(moves == vec!["e2-e4", "d2-d4"]);
+        assert!(book.pick(&chess, 0).is_some_and(|mv| mv.to_string() == "e2-e4"));
+        assert!(book.pick(&chess, 2).is_some_and(|mv| mv.to_string() == "e2-e4"));
+        assert!(book.pick(&chess, 3).is_some_and(|mv| mv.to_string() == "d2-d4"));
+
+        let mut after_e4 = chess.clone();
+        after_e4.play_unchecked(&book.moves(&chess)[0].0);
+        assert!(book.pick(&after_e4, 0).is_none());
+        assert!(Book::from_bytes(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    //Castling is stored as the king taking its rook
+    fn castling() {
+        let setup = Fen::from_ascii("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let book = Book::from_bytes(&entry(&chess, Square::E1, Square::H1, 1)).expect("book should be valid");
+        assert!(matches!(book.pick(&chess, 0), Some(Move::Castle { king: Square::E1, rook: Square::H1 })));
+    }
+
+    #[test]
+    #[cfg(feature = "pgn")]
+    //Built books should weigh moves by how they scored and skip filtered games
+    fn building() {
+        let games = r#"[WhiteElo "2000"]
+[BlackElo "1900"]
+1. e4 e5 2. Nf3 1-0
+
+[WhiteElo "2100"]
+[BlackElo "2050"]
+1. e4 c5 1/2-1/2
+
+[WhiteElo "2100"]
+[BlackElo "2050"]
+1. d4 d5 0-1
+
+[WhiteElo "1200"]
+[BlackElo "1300"]
+1. b4 e5 1-0
+
+1. e4 e5 *
+"#;
+        let settings = BookSettings { max_ply: 2, min_rating: Some(1500), min_count: 1 };
+        let book = build_book(games.as_bytes(), settings).expect("games should be valid");
+
+        let chess = Chess::new();
+        let moves: Vec<(String, u16)> = book.moves(&chess).into_iter().map(|(mv, weight)| (mv.to_string(), weight)).collect();
+        assert!(moves == vec![("e2-e4".to_string(), 3)]);
+
+        let mut after_e4 = chess.clone();
+        after_e4.play_unchecked(&book.moves(&chess)[0].0);
+        let replies: Vec<String> = book.moves(&after_e4).into_iter().map(|(mv, _)| mv.to_string()).collect();
+        assert!(replies == vec!["c7-c5"]);
+
+        let mut after_d4 = chess.clone();
+        after_d4.play_unchecked(&Move::Normal { role: Role::Pawn, from: Square::D2, capture: None, to: Square::D4, promotion: None });
+        assert!(book.moves(&after_d4).len() == 1);
+
+        let read_back = Book::from_bytes(&book.to_bytes()).expect("book should be valid");
+        assert!(read_back.len() == book.len() && book.len() == 3);
+    }
+}
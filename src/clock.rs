@@ -0,0 +1,90 @@
+//! Where searches get the time from.
+//!
+//! Searches measure their time limits with the [`Clock`] set by [`crate::Engine::set_clock`]. The default is
+//! [`SystemClock`], except on wasm32-unknown-unknown where std can't tell the time. There the default clock
+//! never moves, so searches only stop for their depth and node limits until a clock is given, such as
+//! one reading `performance.now()` in a browser.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+/// A source of monotonic time.
+pub trait Clock: Send + Sync {
+    /// The time since some fixed point of the clock's choosing, which must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+/// The operating system's monotonic clock, measured from when the clock was made.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    start: Instant
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when it is told to, for tests and for hosts that keep time themselves.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    nanos: AtomicU64
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    /// Moves the clock forward by `time`.
+    pub fn advance(&self, time: Duration) {
+        self.nanos.fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// The clock engines start with.
+pub(crate) fn default_clock() -> Arc<dyn Clock> {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    return Arc::new(SystemClock::default());
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    return Arc::new(ManualClock::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //A manual clock should only move when advanced, while the system clock moves on its own
+    fn clocks() {
+        let clock = ManualClock::new();
+        assert!(clock.now() == Duration::ZERO);
+        clock.advance(Duration::from_millis(1_500));
+        clock.advance(Duration::from_millis(500));
+        assert!(clock.now() == Duration::from_secs(2));
+
+        let clock = SystemClock::default();
+        let before = clock.now();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(clock.now() >= before + Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,195 @@
+//! Training data from self-play games.
+//!
+//! Each game starts with a few random moves so games don't repeat, then is played out by the engine
+//! against itself. Quiet positions along the way are kept with the score the search gave them and the
+//! result the game ended with, one per line as `<fen> | <score> | <result>`. The score is in centipawns
+//! and the result is 1.0, 0.5 or 0.0, both from white's point of view. [`crate::dataset`] reads the
+//! same lines for tuning.
+
+use std::io::{self, Write};
+
+use shakmaty::fen::Fen;
+use shakmaty::{Chess, Color, EnPassantMode, Outcome, Position};
+
+use crate::random::XorShift64;
+use crate::selfplay::{play_game, Game, TimeControl};
+use crate::{Engine, SearchLimits, REALLY_BIG_CHECKMATE_NUMBER};
+
+/// How games are played and which positions are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DatagenSettings {
+    /// Every move of the games is searched with these limits.
+    pub limits: SearchLimits,
+    /// Random moves played before the engine takes over.
+    pub random_plies: u32,
+    /// Positions scored further than this from equal are left out, since they say little about close games.
+    pub max_score: i32,
+    pub seed: u64
+}
+
+impl Default for DatagenSettings {
+    fn default() -> DatagenSettings {
+        DatagenSettings {
+            limits: SearchLimits { max_nodes: Some(5_000), ..SearchLimits::default() },
+            random_plies: 8,
+            max_score: 2_000,
+            seed: 0
+        }
+    }
+}
+
+/// A kept position with its score and result, both from white's point of view.
+#[derive(Clone, Debug)]
+pub struct DataPoint {
+    pub chess: Chess,
+    pub score: i32,
+    /// 1.0 for a white win, 0.5 for a draw and 0.0 for a black win.
+    pub result: f64
+}
+
+impl DataPoint {
+    /// The point as a line of the training data format, without the line break.
+    pub fn to_line(&self) -> String {
+        let fen = Fen::from_position(self.chess.clone(), EnPassantMode::Legal);
+        format!("{} | {} | {:.1}", fen, self.score, self.result)
+    }
+}
+
+/// Plays `games` self-play games from the standard position and writes the quiet positions to `writer`.
+/// Returns how many positions were written.
+pub fn generate(settings: &DatagenSettings, games: u32, mut writer: impl Write) -> io::Result<usize> {
+    let mut random = XorShift64::new(settings.seed);
+    let mut written = 0;
+
+    for _ in 0..games {
+        let Some(start) = random_opening(&Chess::default(), settings.random_plies, &mut random) else {
+            continue;
+        };
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, TimeControl::Fixed(settings.limits));
+
+        for point in data_points(&game, settings) {
+            writeln!(writer, "{}", point.to_line())?;
+            written += 1;
+        }
+    }
+
+    Ok(written)
+}
+
+/// Plays `plies` random moves from `start`, `None` if the game ends before they are all played.
+fn random_opening(start: &Chess, plies: u32, random: &mut XorShift64) -> Option<Chess> {
+    let mut chess = start.clone();
+
+    for _ in 0..plies {
+        let moves = chess.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        chess.play_unchecked(&moves[(random.next() % moves.len() as u64) as usize]);
+    }
+
+    (!chess.is_game_over()).then_some(chess)
+}
+
+/// The quiet positions of `game`. A position is quiet when the side to move isn't in check and the
+/// search answered it with a quiet move, so its score doesn't hinge on a capture about to happen.
+pub fn data_points(game: &Game, settings: &DatagenSettings) -> Vec<DataPoint> {
+    let result = match game.outcome {
+        Outcome::Decisive { winner: Color::White } => 1.0,
+        Outcome::Decisive { winner: Color::Black } => 0.0,
+        Outcome::Draw => 0.5
+    };
+
+    let mut points = Vec::new();
+    let mut chess = game.start.clone();
+
+    for played_move in &game.moves {
+        let search = &played_move.search;
+        let quiet = !chess.is_check() && !played_move.mv.is_capture() && !played_move.mv.is_promotion();
+        //Mate scores also fall outside any sensible limit
+        let in_range = search.score.abs() <= settings.max_score && search.score.abs() < REALLY_BIG_CHECKMATE_NUMBER;
+
+        if quiet && in_range {
+            points.push(DataPoint {
+                chess: chess.clone(),
+                score: if chess.turn().is_white() {search.score} else {-search.score},
+                result
+            });
+        }
+
+        chess.play_unchecked(&played_move.mv);
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use shakmaty::{CastlingMode, FromSetup, Move, Role, Square};
+    use crate::dataset::parse_line;
+    use crate::selfplay::{PlayedMove, Termination};
+    use crate::{SearchResult, SearchStats};
+
+    fn played_move(mv: Move, score: i32) -> PlayedMove {
+        PlayedMove {
+            search: SearchResult {
+                best_move: Some(mv.clone()),
+                score,
+                depth: 1,
+                seldepth: 1,
+                pv: vec![mv.clone()],
+                nodes: 1,
+                hashfull: 0,
+                time: Duration::ZERO,
+                stats: SearchStats::default()
+            },
+            mv
+        }
+    }
+
+    #[test]
+    //Only quiet positions should be kept, scored from white's point of view
+    fn quiet_positions() {
+        let setup = Fen::from_ascii("4k3/8/8/3p4/8/8/3R4/4K3 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let start = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let game = Game {
+            start,
+            moves: vec![
+                played_move(Move::Normal { role: Role::Rook, from: Square::D2, capture: None, to: Square::D4, promotion: None }, 300),
+                played_move(Move::Normal { role: Role::King, from: Square::E8, capture: None, to: Square::E7, promotion: None }, -250),
+                played_move(Move::Normal { role: Role::Rook, from: Square::D4, capture: Some(Role::Pawn), to: Square::D5, promotion: None }, 400),
+                played_move(Move::Normal { role: Role::King, from: Square::E7, capture: None, to: Square::E6, promotion: None }, -5_000)
+            ],
+            outcome: Outcome::Decisive { winner: Color::White },
+            termination: Termination::Checkmate
+        };
+
+        let settings = DatagenSettings::default();
+        let points = data_points(&game, &settings);
+        let scores: Vec<i32> = points.iter().map(|point| point.score).collect();
+        assert!(scores == vec![300, 250]);
+
+        let line = points[1].to_line();
+        assert!(line == "4k3/8/8/3p4/3R4/8/8/4K3 b - - 1 1 | 250 | 1.0");
+        let labeled = parse_line(&line).expect("line should be readable as a labelled position");
+        assert!(labeled.result == 1.0 && labeled.chess == points[1].chess);
+    }
+
+    #[test]
+    //Generated games should only write lines the dataset reader accepts
+    fn generated_lines() {
+        let settings = DatagenSettings {
+            limits: SearchLimits { max_depth: Some(1), ..SearchLimits::default() },
+            random_plies: 40,
+            ..DatagenSettings::default()
+        };
+
+        let mut output = Vec::new();
+        let written = generate(&settings, 1, &mut output).expect("writing to memory shouldn't fail");
+        let output = String::from_utf8(output).expect("output should be text");
+        assert!(output.lines().count() == written);
+        assert!(output.lines().all(|line| parse_line(line).is_some()));
+    }
+}
@@ -0,0 +1,227 @@
+//! Utilities for the labelled position files used to tune the evaluation.
+//!
+//! Each line holds a FEN followed by the result of the game it came from, from white's point of view.
+//! Results can be written as `1-0`/`1/2-1/2`/`0-1` or as `1.0`/`0.5`/`0.0`, and may be wrapped in
+//! brackets or quotes or separated from the FEN by `|` or `;`, so `<fen> [0.5]` and `<fen> | 1-0` both work.
+//! The `<fen> | <score> | <result>` lines written by [`crate::datagen`] are read too, ignoring the score.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, Color, EnPassantMode, Outcome, Position};
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+
+use crate::evaluation::game_phase;
+use crate::tuning;
+use crate::random::XorShift64;
+
+//Phases above this count as the opening and phases below ENDGAME_PHASE as the endgame
+const OPENING_PHASE: i32 = 18;
+const ENDGAME_PHASE: i32 = 8;
+
+/// A position along with the result of the game it was taken from.
+#[derive(Clone)]
+pub struct LabeledPosition {
+    pub chess: Chess,
+    /// 1.0 for a white win, 0.5 for a draw and 0.0 for a black win.
+    pub result: f64
+}
+
+/// Counts of positions and results for one phase of the game.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct PhaseStats {
+    pub positions: usize,
+    pub white_wins: usize,
+    pub draws: usize,
+    pub black_wins: usize
+}
+
+/// How a dataset is spread over the phases of the game.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct PhaseBalance {
+    pub opening: PhaseStats,
+    pub middlegame: PhaseStats,
+    pub endgame: PhaseStats
+}
+
+/// Parses a single line, returning `None` if it isn't a valid labelled position.
+pub fn parse_line(line: &str) -> Option<LabeledPosition> {
+    let line = line.trim().trim_end_matches(';');
+    let (fen, result) = line.rsplit_once(|c: char| c.is_whitespace() || c == '|' || c == ';')?;
+    let fen = fen.trim().trim_end_matches(['|', ';', ' ']);
+    //Lines from datagen hold the search score between the FEN and the result
+    let fen = match fen.rsplit_once('|') {
+        Some((fen, score)) if score.trim().parse::<i32>().is_ok() => fen.trim(),
+        _ => fen
+    };
+
+    let result = result.trim_matches(['[', ']', '"']);
+    let result = match Outcome::from_ascii(result.as_bytes()) {
+        Ok(Outcome::Decisive { winner: Color::White }) => 1.0,
+        Ok(Outcome::Decisive { winner: Color::Black }) => 0.0,
+        Ok(Outcome::Draw) => 0.5,
+        Err(_) => match result.parse::<f64>() {
+            Ok(result) if (0.0..=1.0).contains(&result) => result,
+            _ => return None
+        }
+    };
+
+    //EPD style lines leave out the move counters and may end with a c9 opcode before the result
+    let fen = fen.strip_suffix("c9").unwrap_or(fen).trim();
+    let chess = Fen::from_ascii(fen.as_bytes()).ok()?.into_position(CastlingMode::Standard).ok()?;
+    Some(LabeledPosition { chess, result })
+}
+
+/// Reads every labelled position from `reader`. Blank lines and lines starting with `#` are skipped.
+pub fn read_dataset(reader: impl BufRead) -> io::Result<Vec<LabeledPosition>> {
+    let mut positions = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let position = parse_line(&line).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {} is not a labelled position: {}", index + 1, line))
+        })?;
+        positions.push(position);
+    }
+
+    Ok(positions)
+}
+
+/// Removes positions with the same zobrist key as an earlier one, returning how many were removed.
+pub fn deduplicate(positions: &mut Vec<LabeledPosition>) -> usize {
+    let original_length = positions.len();
+    let mut seen = HashSet::new();
+    positions.retain(|position| {
+        let hash: Zobrist64 = position.chess.zobrist_hash(EnPassantMode::Legal);
+        seen.insert(hash.0)
+    });
+    original_length - positions.len()
+}
+
+/// Shuffles `positions` with `seed` and splits off `validation_fraction` of them as a holdout set.
+/// The same seed always gives the same split. Returns `(train, validation)`.
+pub fn split(mut positions: Vec<LabeledPosition>, validation_fraction: f64, seed: u64) -> (Vec<LabeledPosition>, Vec<LabeledPosition>) {
+    let mut rng = XorShift64::new(seed);
+
+    //Fisher-Yates shuffle
+    for i in (1..positions.len()).rev() {
+        let j = (rng.next() % (i as u64 + 1)) as usize;
+        positions.swap(i, j);
+    }
+
+    let validation_length = (positions.len() as f64 * validation_fraction.clamp(0.0, 1.0)).round() as usize;
+    let validation = positions.split_off(positions.len() - validation_length);
+    (positions, validation)
+}
+
+/// Counts positions and results in the opening, middlegame and endgame.
+pub fn phase_balance(positions: &[LabeledPosition]) -> PhaseBalance {
+    let mut balance = PhaseBalance::default();
+
+    for position in positions {
+        let phase = game_phase(position.chess.board());
+        let stats = if phase > OPENING_PHASE {
+            &mut balance.opening
+        }
+        else if phase < ENDGAME_PHASE {
+            &mut balance.endgame
+        }
+        else {
+            &mut balance.middlegame
+        };
+
+        stats.positions += 1;
+        if position.result > 0.75 {
+            stats.white_wins += 1;
+        }
+        else if position.result < 0.25 {
+            stats.black_wins += 1;
+        }
+        else {
+            stats.draws += 1;
+        }
+    }
+
+    balance
+}
+
+/// Maps an evaluation in centipawns from white's point of view to an expected result.
+/// `k` scales how quickly the expected result moves toward a win.
+pub fn expected_result(evaluation: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * evaluation as f64 / 400.0))
+}
+
+/// The mean squared error between the evaluation's expected results and the actual results.
+pub fn mean_squared_error(positions: &[LabeledPosition], k: f64) -> f64 {
+    tuning::mean_squared_error(positions, k, &tuning::TunedEvaluation::DEFAULT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATASET: &str = "
+# A comment
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 [0.5]
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 | 1-0
+4k3/8/8/8/8/8/4P3/4K3 w - - c9 \"1/2-1/2\";
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 10 6 0-1
+";
+
+    #[test]
+    //Every supported line format should parse with the right result
+    fn reading() {
+        let positions = read_dataset(DATASET.as_bytes()).expect("dataset should be valid");
+        let results: Vec<f64> = positions.iter().map(|position| position.result).collect();
+        assert!(results == vec![0.5, 1.0, 0.5, 0.0]);
+
+        assert!(read_dataset("not a fen 1-0".as_bytes()).is_err());
+        assert!(parse_line("8/8/8/8/8/8/8/8 w - - 0 1 1-0").is_none());
+    }
+
+    #[test]
+    //The start position appears twice and should only be kept once
+    fn deduplication() {
+        let mut positions = read_dataset(DATASET.as_bytes()).expect("dataset should be valid");
+        assert!(deduplicate(&mut positions) == 1);
+        assert!(positions.len() == 3);
+        assert!(positions[0].result == 0.5);
+    }
+
+    #[test]
+    //Splits should be reproducible from the seed and keep every position exactly once
+    fn splitting() {
+        let positions = read_dataset(DATASET.as_bytes()).expect("dataset should be valid");
+        let (train, validation) = split(positions.clone(), 0.25, 7);
+        assert!(train.len() == 3 && validation.len() == 1);
+
+        let (train_again, validation_again) = split(positions, 0.25, 7);
+        let fens = |positions: &[LabeledPosition]| -> Vec<String> {
+            positions.iter().map(|position| Fen::from_position(position.chess.clone(), EnPassantMode::Legal).to_string()).collect()
+        };
+        assert!(fens(&train) == fens(&train_again) && fens(&validation) == fens(&validation_again));
+    }
+
+    #[test]
+    //Phases and results should be counted into the right buckets
+    fn balance() {
+        let positions = read_dataset(DATASET.as_bytes()).expect("dataset should be valid");
+        let balance = phase_balance(&positions);
+        assert!(balance.opening == PhaseStats { positions: 3, white_wins: 1, draws: 1, black_wins: 1 });
+        assert!(balance.endgame == PhaseStats { positions: 1, white_wins: 0, draws: 1, black_wins: 0 });
+        assert!(balance.middlegame.positions == 0);
+    }
+
+    #[test]
+    //A draw in an equal position is predicted perfectly while a lost one is not
+    fn error() {
+        let positions = read_dataset(DATASET.as_bytes()).expect("dataset should be valid");
+        assert!(mean_squared_error(&positions[..1], 1.0) < 1e-9);
+        assert!(mean_squared_error(&positions[3..], 1.0) > 0.2);
+        assert!(mean_squared_error(&[], 1.0) == 0.0);
+    }
+}
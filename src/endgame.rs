@@ -0,0 +1,89 @@
+//! Knowledge of the basic mates, which a search at ordinary depths can't see the way to.
+//!
+//! With a lone king against enough material to mate it, the side with the material is scored for driving the
+//! king to the edge and bringing its own king up to help. With only a bishop and knight, the king also has to
+//! be driven to a corner of the bishop's colour, since those are the only corners it can be mated in.
+
+use shakmaty::{Bitboard, Board, Color, Piece, Role, Square};
+
+use crate::evaluation::material_value;
+
+//Added to the evaluation so positions with a mate to be had stand out from ones where material is only ahead
+const MATING_BONUS: i32 = 300;
+//For each step the lone king is from the center
+const EDGE_BONUS: i32 = 20;
+//For each step the lone king is closer to a corner it can be mated in with a bishop and knight
+const CORNER_BONUS: i32 = 15;
+//For each step the kings are closer to each other
+const KING_DISTANCE_BONUS: i32 = 10;
+
+/// The evaluation from white's point of view of a lone king against enough material to mate it, or `None`
+/// for any other board. The mating side's material gets a bonus that grows as the mate gets closer, and the
+/// piece square tables are left out since they would only pull the kings away from where the mate is.
+pub fn evaluate_mating(board: &Board) -> Option<i32> {
+    let weak = Color::ALL.into_iter().find(|&color| board.by_color(color).count() == 1)?;
+    let strong = !weak;
+    let (weak_king, strong_king) = (board.king_of(weak)?, board.king_of(strong)?);
+
+    let pieces = board.by_color(strong);
+    let bishops = pieces & board.bishops();
+    let both_bishop_colours = (bishops & Bitboard::LIGHT_SQUARES).any() && (bishops & Bitboard::DARK_SQUARES).any();
+    let major_pieces = (pieces & (board.queens() | board.rooks())).any();
+    let bishop_and_knight = bishops.any() && (pieces & board.knights()).any();
+    if !major_pieces && !bishop_and_knight && !both_bishop_colours {
+        return None;
+    }
+
+    let material: i32 = Role::ALL.into_iter().map(|role| board.by_piece(Piece { color: strong, role }).count() as i32 * material_value(role)).sum();
+    let mut bonus = MATING_BONUS + KING_DISTANCE_BONUS * (7 - strong_king.distance(weak_king) as i32);
+    if major_pieces || both_bishop_colours {
+        bonus += EDGE_BONUS * center_distance(weak_king);
+    }
+    else {
+        bonus += CORNER_BONUS * mating_corner_closeness(weak_king, (bishops & Bitboard::LIGHT_SQUARES).any());
+    }
+    let evaluation = material + bonus;
+    Some(if strong.is_white() {evaluation} else {-evaluation})
+}
+
+//How close `square` is to the corners a bishop on light or dark squares can mate in, from 0 on the other
+//long diagonal to 7 in those corners
+fn mating_corner_closeness(square: Square, light_bishop: bool) -> i32 {
+    //a1 and h8 are dark, so a light squared bishop mates in the corners of the flipped board
+    let square = if light_bishop {square.flip_horizontal()} else {square};
+    (7 - square.file() as i32 - square.rank() as i32).abs()
+}
+
+//How many steps `square` is from the four center squares, counting files and ranks separately, from 0 to 6
+fn center_distance(square: Square) -> i32 {
+    let distance = |coordinate: i32| (3 - coordinate).max(coordinate - 4);
+    distance(square.file() as i32) + distance(square.rank() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(fen: &str) -> Option<i32> {
+        evaluate_mating(&Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid"))
+    }
+
+    #[test]
+    //The lone king should be worse off near the edge, and with a bishop and knight near the bishop's corners
+    fn mating_progress() {
+        let centered = evaluate("8/8/8/3k4/8/8/8/R3K3").expect("rook should be able to mate");
+        let edge = evaluate("3k4/8/8/8/8/8/8/R3K3").expect("rook should be able to mate");
+        assert!(centered > material_value(Role::Rook) && edge > centered);
+        assert!(evaluate("3K4/8/8/8/8/8/8/r3k3") == Some(-edge));
+
+        //Dark squared bishops mate in a1 and h8, not a8
+        let right_corner = evaluate("8/8/8/8/8/1K6/8/k1B1N3").expect("bishop and knight should be able to mate");
+        let wrong_corner = evaluate("k2B1N2/8/1K6/8/8/8/8/8").expect("bishop and knight should be able to mate");
+        assert!(right_corner > wrong_corner);
+
+        assert!(evaluate("8/8/8/3k4/8/8/8/N1B1K3").is_some());
+        assert!(evaluate("8/8/8/3k4/8/8/8/N1N1K3").is_none());
+        assert!(evaluate("8/8/8/3k4/8/8/8/B1B1K3").is_none());
+        assert!(evaluate("8/8/8/3k4/8/8/3p4/R3K3").is_none());
+    }
+}
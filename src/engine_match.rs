@@ -0,0 +1,323 @@
+//! Matches between two engine configurations, for finding out whether a change makes the engine stronger.
+//!
+//! Both configurations play every opening once with each color. The result is reported as an Elo
+//! difference with a 95% error margin, and a sequential probability ratio test (SPRT) can stop the
+//! match as soon as the games so far are enough to tell which of two Elo hypotheses holds.
+
+use std::io::{self, BufRead};
+
+use shakmaty::fen::Fen;
+use shakmaty::san::San;
+use shakmaty::{CastlingMode, Chess, Color, Outcome, Position};
+
+use crate::selfplay::{play_game, Game, TimeControl};
+use crate::Engine;
+
+//The normal distribution's 97.5th percentile, for 95% error margins
+const CONFIDENCE_Z: f64 = 1.959964;
+
+/// Games from the first configuration's point of view.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, from 0.0 for losing every game to 1.0 for winning every game.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// The Elo difference that the score points to.
+    pub fn elo(&self) -> f64 {
+        elo_from_score(self.score())
+    }
+
+    /// Half the width of the 95% confidence interval around [`MatchScore::elo`].
+    pub fn elo_error(&self) -> f64 {
+        let deviation = CONFIDENCE_Z * (self.variance() / self.games().max(1) as f64).sqrt();
+        let score = self.score();
+        (elo_from_score(score + deviation) - elo_from_score(score - deviation)) / 2.0
+    }
+
+    /// The variance of the points of a single game.
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        let games = self.games().max(1) as f64;
+        (self.wins as f64 * (1.0 - score).powi(2) + self.draws as f64 * (0.5 - score).powi(2) + self.losses as f64 * score.powi(2)) / games
+    }
+
+    fn add(&mut self, outcome: Outcome, first_color: Color) {
+        match outcome {
+            Outcome::Draw => self.draws += 1,
+            Outcome::Decisive { winner } if winner == first_color => self.wins += 1,
+            Outcome::Decisive { .. } => self.losses += 1
+        }
+    }
+}
+
+/// Scores are clamped away from 0 and 1, which would be an infinite Elo difference.
+fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Tests whether the first configuration is `elo0` (H0) or `elo1` (H1) Elo stronger than the second,
+/// with `alpha` and `beta` as the chances of wrongly accepting H1 and H0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64
+}
+
+/// Which hypothesis an SPRT accepted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtDecision {
+    AcceptH0,
+    AcceptH1
+}
+
+impl Default for Sprt {
+    fn default() -> Sprt {
+        Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05
+        }
+    }
+}
+
+impl Sprt {
+    /// The log likelihood ratio of H1 against H0, using the normal approximation to the game results.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let variance = score.variance() / score.games().max(1) as f64;
+        //Results that are all the same, such as before the first game, say nothing about their spread
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let score0 = score_from_elo(self.elo0);
+        let score1 = score_from_elo(self.elo1);
+        (score1 - score0) * (2.0 * score.score() - score0 - score1) / (2.0 * variance)
+    }
+
+    /// The LLR below which H0 is accepted and above which H1 is accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// The accepted hypothesis, `None` while more games are needed.
+    pub fn decision(&self, score: &MatchScore) -> Option<SprtDecision> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        }
+        else if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        }
+        else {
+            None
+        }
+    }
+}
+
+/// How a match is played.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchSettings {
+    pub time_control: TimeControl,
+    /// The match ends after this many games if the SPRT hasn't ended it earlier.
+    pub max_games: u32,
+    pub sprt: Option<Sprt>
+}
+
+/// How a match ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchResult {
+    pub score: MatchScore,
+    /// `None` if there was no SPRT or it hadn't decided by the end of the match.
+    pub decision: Option<SprtDecision>
+}
+
+/// Plays the configurations made by `first` and `second` against each other. Every opening is played
+/// twice in a row with colors swapped, starting from the standard position if there are no openings.
+/// `on_game` is called after every game with the game, whether `first` had white, and the score so far.
+pub fn run_match(first: impl Fn() -> Engine, second: impl Fn() -> Engine, openings: &[Chess],
+                 settings: &MatchSettings, mut on_game: impl FnMut(&Game, bool, &MatchScore)) -> MatchResult {
+    let mut score = MatchScore::default();
+    let default_openings = [Chess::default()];
+    let openings = if openings.is_empty() {&default_openings[..]} else {openings};
+
+    for game_index in 0..settings.max_games {
+        let opening = &openings[(game_index / 2) as usize % openings.len()];
+        let first_is_white = game_index % 2 == 0;
+
+        //Fresh engines so nothing carries over from earlier games
+        let (mut first_engine, mut second_engine) = (first(), second());
+        let game = if first_is_white {
+            play_game(&mut first_engine, &mut second_engine, opening, settings.time_control)
+        }
+        else {
+            play_game(&mut second_engine, &mut first_engine, opening, settings.time_control)
+        };
+
+        score.add(game.outcome, if first_is_white {Color::White} else {Color::Black});
+        on_game(&game, first_is_white, &score);
+
+        //Stopping between the two games of an opening would favour whoever played the better side
+        if game_index % 2 == 1 {
+            if let Some(decision) = settings.sprt.and_then(|sprt| sprt.decision(&score)) {
+                return MatchResult { score, decision: Some(decision) };
+            }
+        }
+    }
+
+    MatchResult { score, decision: None }
+}
+
+/// Reads openings from EPD or PGN. EPD lines give positions directly, while each PGN game gives the
+/// position after its moves. Tag pairs, comments, move numbers and results are skipped.
+pub fn read_openings(reader: impl BufRead) -> io::Result<Vec<Chess>> {
+    let mut openings = Vec::new();
+    let mut game: Option<Chess> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.starts_with('[') || line.is_empty() {
+            //Tags and the blank line after a game's moves end the game before
+            if let Some(chess) = game.take() {
+                openings.push(chess);
+            }
+            continue;
+        }
+
+        if let Some(chess) = parse_epd(line) {
+            openings.push(chess);
+            continue;
+        }
+
+        let chess = game.get_or_insert_with(Chess::default);
+        for token in strip_comments(line).split_whitespace() {
+            //Move numbers may be written right before the move, as in "1.e4"
+            let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            if token.is_empty() || Outcome::from_ascii(token.as_bytes()).is_ok() || token == "*" {
+                continue;
+            }
+            let m = San::from_ascii(token.trim_end_matches(['+', '#', '!', '?']).as_bytes()).ok()
+                .and_then(|san| san.to_move(chess).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("illegal move {} in opening", token)))?;
+            chess.play_unchecked(&m);
+        }
+    }
+
+    if let Some(chess) = game {
+        openings.push(chess);
+    }
+    Ok(openings)
+}
+
+/// EPD lines are the first four FEN fields followed by operations, which don't matter for openings.
+fn parse_epd(line: &str) -> Option<Chess> {
+    let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+    if fields.len() < 4 || !fields[0].contains('/') {
+        return None;
+    }
+    let fen = format!("{} 0 1", fields.join(" "));
+    Fen::from_ascii(fen.as_bytes()).ok()?.into_position(CastlingMode::Standard).ok()
+}
+
+fn strip_comments(line: &str) -> String {
+    let line = line.split(';').next().unwrap_or_default();
+    let mut depth = 0;
+    line.chars().filter(|&c| {
+        match c {
+            '{' | '(' => depth += 1,
+            '}' | ')' => depth -= 1,
+            _ => return depth == 0
+        }
+        false
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchLimits;
+
+    #[test]
+    //Elo should follow the score, with smaller margins after more games
+    fn elo_from_results() {
+        let even = MatchScore { wins: 10, draws: 20, losses: 10 };
+        assert!(even.elo().abs() < 1e-9);
+        let ahead = MatchScore { wins: 30, draws: 10, losses: 10 };
+        assert!((ahead.score() - 0.7).abs() < 1e-9);
+        assert!((ahead.elo() - 147.2).abs() < 0.1);
+
+        let more_games = MatchScore { wins: 300, draws: 100, losses: 100 };
+        assert!(more_games.elo_error() < ahead.elo_error());
+        assert!(ahead.elo_error() > 0.0);
+    }
+
+    #[test]
+    //The SPRT should accept whichever hypothesis the results clearly favour
+    fn sprt_decisions() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+        assert!(sprt.decision(&MatchScore { wins: 600, draws: 800, losses: 400 }) == Some(SprtDecision::AcceptH1));
+        assert!(sprt.decision(&MatchScore { wins: 400, draws: 800, losses: 600 }) == Some(SprtDecision::AcceptH0));
+        assert!(sprt.decision(&MatchScore { wins: 5, draws: 10, losses: 5 }).is_none());
+        assert!(sprt.llr(&MatchScore::default()) == 0.0);
+    }
+
+    #[test]
+    //Openings should be read from both EPD lines and PGN games
+    fn openings() {
+        let input = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";\n\
+                     [Event \"?\"]\n\
+                     \n\
+                     1. d4 {main line} d5 (1... Nf6) 2.c4 *\n\
+                     \n\
+                     1. Nf3 Nf6\n";
+        let openings = read_openings(input.as_bytes()).expect("openings should be valid");
+        assert!(openings.len() == 3);
+        assert!(openings[0].turn() == Color::Black);
+        assert!(openings[1].fullmoves().get() == 2 && openings[1].turn() == Color::Black);
+        assert!(openings[2].fullmoves().get() == 2 && openings[2].turn() == Color::White);
+
+        assert!(read_openings("1. e4 Ke7\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    //Every opening should be played with both colors, ending early once the SPRT decides
+    fn match_pairs_openings() {
+        let limits = SearchLimits { max_depth: Some(1), ..SearchLimits::default() };
+        let openings = read_openings("6k1/5ppp/8/8/8/8/8/K2R4 w - -\n".as_bytes()).expect("openings should be valid");
+        let settings = MatchSettings { time_control: TimeControl::Fixed(limits), max_games: 4, sprt: None };
+
+        let mut colors = Vec::new();
+        let result = run_match(Engine::new, Engine::new, &openings, &settings, |game, first_is_white, _| {
+            assert!(game.moves.len() == 1);
+            colors.push(first_is_white);
+        });
+        assert!(colors == vec![true, false, true, false]);
+        assert!(result.score == MatchScore { wins: 2, draws: 0, losses: 2 });
+        assert!(result.decision.is_none());
+    }
+}
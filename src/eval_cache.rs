@@ -0,0 +1,44 @@
+const CACHE_LENGTH: usize = 1 << 16;
+const CACHE_INDEX_MASK: u64 = CACHE_LENGTH as u64 - 1;
+
+/// Static evaluations of recently evaluated positions, indexed by the low bits of their hash. Quiescence
+/// search reaches the same positions through different capture orders, and those don't need evaluating again.
+pub struct EvalCache {
+    entries: Box<[(u64, i32)]>
+}
+
+impl EvalCache {
+    pub fn new() -> EvalCache {
+        //Each slot starts with a hash that would be stored in a different slot, so it can't match before it is written
+        EvalCache {
+            entries: (0..CACHE_LENGTH as u64).map(|index| (index ^ 1, 0)).collect()
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<i32> {
+        let (stored_hash, evaluation) = self.entries[(hash & CACHE_INDEX_MASK) as usize];
+        (stored_hash == hash).then_some(evaluation)
+    }
+
+    pub fn insert(&mut self, hash: u64, evaluation: i32) {
+        self.entries[(hash & CACHE_INDEX_MASK) as usize] = (hash, evaluation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //Only the hash last stored in a slot should be found
+    fn cached_evaluations() {
+        let mut cache = EvalCache::new();
+        assert!(cache.get(0).is_none() && cache.get(1).is_none());
+
+        cache.insert(5, -40);
+        assert!(cache.get(5) == Some(-40));
+        cache.insert(CACHE_LENGTH as u64 + 5, 12);
+        assert!(cache.get(5).is_none());
+        assert!(cache.get(CACHE_LENGTH as u64 + 5) == Some(12));
+    }
+}
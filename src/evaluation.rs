@@ -1,53 +1,737 @@
-use shakmaty::{Board, Color, Move, Role};
-use crate::piece_square_tables;
+use std::fmt;
+
+use shakmaty::{attacks, Bitboard, Board, ByColor, ByRole, Color, File, Move, Piece, Rank, Role, Square};
+use crate::piece_square_tables::PieceSquareTables;
 
 // Returns an evaluation of the current board position from the perspective of white
 #[inline]
 pub fn evaluate_board(board: &Board) -> i32 {
+    evaluate_board_with_tables(board, &PieceSquareTables::DEFAULT)
+}
+
+// Same as evaluate_board, but with the given piece square tables
+#[inline]
+pub fn evaluate_board_with_tables(board: &Board, tables: &PieceSquareTables) -> i32 {
     let mut white_material_score = 0;
     let mut black_material_score = 0;
 
     for square in board.white().intersect(board.pawns()) {
-        white_material_score += piece_square_tables::PAWN[square as usize];
+        white_material_score += tables.pawn[square as usize];
     }
     for square in board.white().intersect(board.bishops()) {
-        white_material_score += piece_square_tables::BISHOP[square as usize];
+        white_material_score += tables.bishop[square as usize];
     }
     for square in board.white().intersect(board.knights()) {
-        white_material_score += piece_square_tables::KNIGHT[square as usize];
+        white_material_score += tables.knight[square as usize];
     }
     for square in board.white().intersect(board.rooks()) {
-        white_material_score += piece_square_tables::ROOK[square as usize];
+        white_material_score += tables.rook[square as usize];
     }
     for square in board.white().intersect(board.queens()) {
-        white_material_score += piece_square_tables::QUEEN[square as usize];
+        white_material_score += tables.queen[square as usize];
+    }
+    //Some variants play without kings or can lose them
+    if let Some(king) = board.king_of(Color::White) {
+        white_material_score += tables.king[king as usize];
     }
-    white_material_score += piece_square_tables::KING[board.king_of(Color::White).unwrap() as usize];
 
     for square in board.black().intersect(board.pawns()) {
-        black_material_score += piece_square_tables::PAWN[square as usize ^ 56];
+        black_material_score += tables.pawn[square as usize ^ 56];
     }
     for square in board.black().intersect(board.bishops()) {
-        black_material_score += piece_square_tables::BISHOP[square as usize ^ 56];
+        black_material_score += tables.bishop[square as usize ^ 56];
     }
     for square in board.black().intersect(board.knights()) {
-        black_material_score += piece_square_tables::KNIGHT[square as usize ^ 56];
+        black_material_score += tables.knight[square as usize ^ 56];
     }
     for square in board.black().intersect(board.rooks()) {
-        black_material_score += piece_square_tables::ROOK[square as usize ^ 56];
+        black_material_score += tables.rook[square as usize ^ 56];
     }
     for square in board.black().intersect(board.queens()) {
-        black_material_score += piece_square_tables::QUEEN[square as usize ^ 56];
+        black_material_score += tables.queen[square as usize ^ 56];
+    }
+    if let Some(king) = board.king_of(Color::Black) {
+        black_material_score += tables.king[king as usize ^ 56];
     }
-    black_material_score += piece_square_tables::KING[board.king_of(Color::Black).unwrap() as usize ^ 56];
 
     let material_difference = white_material_score - black_material_score;
     let total_material = white_material_score + black_material_score;
 
     //encourages trading when up material
-    let trade_bonus = 100 * material_difference / total_material;
+    let trade_bonus = trade_bonus(material_difference, total_material, &EvalParams::DEFAULT);
+
+    let pawn_structure = pawn_structure(board);
+
+    let evaluation = material_difference + trade_bonus + pawn_structure.score.total() + piece_terms(board, &pawn_structure);
+    evaluation * endgame_scale(board, &pawn_structure, evaluation, &EvalParams::DEFAULT) / 100
+}
+
+//The bonus for trading when ahead grows as the material left shrinks
+fn trade_bonus(material_difference: i32, total_material: i32, params: &EvalParams) -> i32 {
+    if total_material > 0 {params.trade_bonus * material_difference / total_material} else {0}
+}
+
+/// The piece square table value of `piece` on `square`, which includes the piece's material.
+#[inline]
+pub fn piece_square_value(piece: Piece, square: Square) -> i32 {
+    //Tables are laid out from white's side, so black's squares are flipped
+    let flip = if piece.color.is_white() {0} else {56};
+    PieceSquareTables::DEFAULT.table(piece.role)[square as usize ^ flip]
+}
+
+/// The piece square values of each side's pieces other than the king, which [`evaluate_piece_sums`]
+/// turns into the evaluation of the board. Sums kept up to date move by move save adding up every piece.
+pub fn piece_sums(board: &Board) -> ByColor<i32> {
+    ByColor::new_with(|color| {
+        [Role::Pawn, Role::Knight, Role::Bishop, Role::Rook, Role::Queen].into_iter().map(|role| {
+            let piece = Piece { color, role };
+            board.by_piece(piece).into_iter().map(|square| piece_square_value(piece, square)).sum::<i32>()
+        }).sum()
+    })
+}
+
+/// [`evaluate_board`] from the [`piece_sums`] and [`pawn_structure`] of `board`.
+#[inline]
+pub fn evaluate_piece_sums(board: &Board, piece_sums: ByColor<i32>, pawn_structure: &PawnStructure) -> i32 {
+    let material_score = |color| {
+        //Some variants play without kings or can lose them
+        piece_sums.get(color) + board.king_of(color).map_or(0, |king| piece_square_value(Piece { color, role: Role::King }, king))
+    };
+    let (white_material_score, black_material_score) = (material_score(Color::White), material_score(Color::Black));
+
+    let material_difference = white_material_score - black_material_score;
+    let total_material = white_material_score + black_material_score;
+    let trade_bonus = trade_bonus(material_difference, total_material, &EvalParams::DEFAULT);
+
+    let evaluation = material_difference + trade_bonus + pawn_structure.score.total() + piece_terms(board, pawn_structure);
+    evaluation * endgame_scale(board, pawn_structure, evaluation, &EvalParams::DEFAULT) / 100
+}
+
+//The terms after the pawn structure, which depend on where the pieces stand and aren't kept in the pawn table
+fn piece_terms(board: &Board, pawn_structure: &PawnStructure) -> i32 {
+    let params = &EvalParams::DEFAULT;
+    material_imbalance(board, params).total() + knight_outposts(board, pawn_structure, params).total() + bad_bishops(board, pawn_structure, params).total()
+        + king_tropism(board, params).total() + pawn_storms(board, params).total() + threats(board, params).total()
+        + pawn_races(board, pawn_structure, params).total() + development(board, params).total() + trapped_pieces(board, params).total()
+}
+
+/// The pawn structure terms of a board, which only depend on where the pawns are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PawnStructure {
+    /// Penalties for doubled and isolated pawns and bonuses for passed pawns.
+    pub score: EvalTerm,
+    /// Pawns of either side with no enemy pawn in front of them on their own or a neighbouring file.
+    pub passed: Bitboard,
+    /// Squares in the enemy half where each side's knights are guarded by a pawn and no enemy pawn can ever attack them.
+    pub outposts: ByColor<Bitboard>,
+    /// Each side's pawns blocked by an enemy pawn right in front of them, which can't move until a capture frees them.
+    pub fixed: ByColor<Bitboard>
+}
+
+/// Scores the pawns of both sides, which the search looks up in a pawn hash table since most moves leave them alone.
+pub fn pawn_structure(board: &Board) -> PawnStructure {
+    pawn_structure_with_params(board, &EvalParams::DEFAULT)
+}
+
+/// [`pawn_structure`] with the penalties and bonuses from `params`, before they are weighed.
+pub fn pawn_structure_with_params(board: &Board, params: &EvalParams) -> PawnStructure {
+    let mut structure = PawnStructure::default();
+
+    for color in Color::ALL {
+        let pawns = board.by_piece(color.pawn());
+        let enemy_pawns = board.by_piece((!color).pawn());
+        let mut score = 0;
+
+        for square in pawns {
+            let file = Bitboard::from_file(square.file());
+            let neighbours = neighbouring_files(square.file());
+            let ahead = ranks_ahead(color, square);
+
+            //Each pawn with another of its own in front of it counts once, so a doubled pawn is penalized once
+            let doubled = (pawns & file & ahead).any();
+            if doubled {
+                score -= params.doubled_pawn_penalty;
+            }
+            if (pawns & neighbours).is_empty() {
+                score -= params.isolated_pawn_penalty;
+            }
+            //Only the front pawn of a doubled pair can be passed
+            if !doubled && (enemy_pawns & (file | neighbours) & ahead).is_empty() {
+                structure.passed.add(square);
+                let advanced = if color.is_white() {square.rank() as usize} else {7 - square.rank() as usize};
+                score += params.passed_pawn_bonus[advanced];
+            }
+            if square.offset(if color.is_white() {8} else {-8}).is_some_and(|front| enemy_pawns.contains(front)) {
+                structure.fixed.get_mut(color).add(square);
+            }
+        }
+
+        let outpost_ranks = [Rank::Fourth, Rank::Fifth, Rank::Sixth].into_iter().map(|rank| Bitboard::from_rank(color.relative_rank(rank))).fold(Bitboard::EMPTY, |ranks, rank| ranks | rank);
+        let guarded = pawns.into_iter().map(|square| attacks::pawn_attacks(color, square)).fold(Bitboard::EMPTY, |guarded, attacks| guarded | attacks);
+        for square in guarded & outpost_ranks {
+            if (enemy_pawns & neighbouring_files(square.file()) & ranks_ahead(color, square)).is_empty() {
+                structure.outposts.get_mut(color).add(square);
+            }
+        }
+
+        match color {
+            Color::White => structure.score.white = score,
+            Color::Black => structure.score.black = score
+        }
+    }
+
+    structure
+}
+
+fn neighbouring_files(file: File) -> Bitboard {
+    [-1, 1].into_iter().filter_map(|delta| file.offset(delta)).map(Bitboard::from_file).fold(Bitboard::EMPTY, |files, file| files | file)
+}
+
+//Squares on the ranks in front of `square` from `color`'s side
+fn ranks_ahead(color: Color, square: Square) -> Bitboard {
+    let rank = square.rank() as u32;
+    match color {
+        Color::White => Bitboard(u64::MAX.checked_shl(8 * (rank + 1)).unwrap_or(0)),
+        Color::Black => Bitboard((1 << (8 * rank)) - 1)
+    }
+}
+
+/// The bonuses for passed pawns in an ending with only kings and pawns: for the pawn that queens first when the
+/// enemy king can't catch it, and for kings standing closer to the square in front of a passed pawn than the
+/// enemy king. The side to move isn't known, so a pawn only counts as unstoppable if the enemy king is outside
+/// its square even with the enemy to move.
+pub fn pawn_races(board: &Board, pawn_structure: &PawnStructure, params: &EvalParams) -> EvalTerm {
+    if board.occupied() != board.kings() | board.pawns() {
+        return EvalTerm::default();
+    }
+    let (Some(white_king), Some(black_king)) = (board.king_of(Color::White), board.king_of(Color::Black)) else {
+        return EvalTerm::default();
+    };
+    let kings = ByColor { white: white_king, black: black_king };
+
+    let mut races = EvalTerm::default();
+    //The fewest moves any of each side's unstoppable pawns needs to queen
+    let mut queening = ByColor::<Option<i32>>::default();
+    for color in Color::ALL {
+        let (own_king, enemy_king) = (*kings.get(color), *kings.get(!color));
+        let mut proximity = 0;
+        for pawn in pawn_structure.passed & board.by_piece(color.pawn()) {
+            let promotion = Square::from_coords(pawn.file(), color.relative_rank(Rank::Eighth));
+            let path = Bitboard::from_file(pawn.file()) & ranks_ahead(color, pawn);
+            //The double step from the starting rank saves a move
+            let moves = (path.count() as i32).min(5);
+            if (path & board.occupied()).is_empty() && enemy_king.distance(promotion) as i32 > moves + 1 {
+                let fastest = queening.get_mut(color);
+                *fastest = Some(fastest.map_or(moves, |fastest| fastest.min(moves)));
+            }
+            if let Some(front) = pawn.offset(if color.is_white() {8} else {-8}) {
+                proximity += enemy_king.distance(front) as i32 - own_king.distance(front) as i32;
+            }
+        }
+        match color {
+            Color::White => races.white = proximity * params.passer_king_proximity_bonus,
+            Color::Black => races.black = proximity * params.passer_king_proximity_bonus
+        }
+    }
+
+    //When both sides have a pawn that can't be caught, only the one that queens first is sure to count
+    match (queening.white, queening.black) {
+        (Some(white), Some(black)) if white < black => races.white += params.unstoppable_passer_bonus,
+        (Some(white), Some(black)) if black < white => races.black += params.unstoppable_passer_bonus,
+        (Some(_), None) => races.white += params.unstoppable_passer_bonus,
+        (None, Some(_)) => races.black += params.unstoppable_passer_bonus,
+        _ => ()
+    }
+    races
+}
+
+/// Corrections to adding up piece values for combinations of pieces that are worth more or less together:
+/// the bishop pair, rooks and queens doing the same work, minor pieces against a rook and pieces against a queen.
+pub fn material_imbalance(board: &Board, params: &EvalParams) -> EvalTerm {
+    let count = |color: Color, pieces: Bitboard| (board.by_color(color) & pieces).count() as i32;
+    let minors = |color| count(color, board.knights() | board.bishops());
+    let rooks = |color| count(color, board.rooks());
+    let queens = |color| count(color, board.queens());
+
+    let own = |color: Color| {
+        let bishop_pair = if count(color, board.bishops()) >= 2 {params.bishop_pair_bonus} else {0};
+        let redundant_majors = (rooks(color) + queens(color) - 1).max(0);
+        bishop_pair - redundant_majors * params.redundant_major_penalty
+    };
+    let mut imbalance = EvalTerm { white: own(Color::White), black: own(Color::Black) };
+
+    //Differences in white's favour, so a positive one and a negative one mean pieces were traded for others
+    let (minors, rooks, queens) = (minors(Color::White) - minors(Color::Black), rooks(Color::White) - rooks(Color::Black), queens(Color::White) - queens(Color::Black));
+    if minors * rooks < 0 {
+        let bonus = minors.abs().min(rooks.abs()) * params.minors_for_rook_bonus;
+        if minors > 0 {imbalance.white += bonus} else {imbalance.black += bonus}
+    }
+    if queens * (minors + rooks) < 0 {
+        let bonus = queens.abs() * params.pieces_for_queen_bonus;
+        if queens < 0 {imbalance.white += bonus} else {imbalance.black += bonus}
+    }
+    imbalance
+}
+
+/// The bonus for knights standing on their side's outposts, see [`PawnStructure::outposts`].
+pub fn knight_outposts(board: &Board, pawn_structure: &PawnStructure, params: &EvalParams) -> EvalTerm {
+    let outposts = |color: Color| (board.by_piece(color.knight()) & *pawn_structure.outposts.get(color)).count() as i32 * params.knight_outpost_bonus;
+    EvalTerm { white: outposts(Color::White), black: outposts(Color::Black) }
+}
+
+/// The penalty for bishops hemmed in by their own fixed pawns, counted for every fixed pawn on the bishop's square colour.
+pub fn bad_bishops(board: &Board, pawn_structure: &PawnStructure, params: &EvalParams) -> EvalTerm {
+    let penalty = |color: Color| {
+        let fixed = *pawn_structure.fixed.get(color);
+        board.by_piece(color.bishop()).into_iter().map(|bishop| {
+            let same_colour = if bishop.is_light() {Bitboard::LIGHT_SQUARES} else {Bitboard::DARK_SQUARES};
+            -((fixed & same_colour).count() as i32 * params.bad_bishop_penalty)
+        }).sum()
+    };
+    EvalTerm { white: penalty(Color::White), black: penalty(Color::Black) }
+}
+
+/// The bonus for pieces close to the enemy king, with queens counting twice. It fades out as pieces come off
+/// the board, since the king comes out to fight in the endgame.
+pub fn king_tropism(board: &Board, params: &EvalParams) -> EvalTerm {
+    let phase = game_phase(board);
+    let tropism = |color: Color| {
+        let Some(king) = board.king_of(!color) else {
+            return 0;
+        };
+        let attackers = board.by_color(color) & !board.pawns() & !board.kings();
+        let closeness: i32 = attackers.into_iter().map(|square| {
+            let closeness = 7 - square.distance(king) as i32;
+            if board.queens().contains(square) {2 * closeness} else {closeness}
+        }).sum();
+        //The full bonus is for a piece next to the king, down to nothing from the far side of the board
+        params.king_tropism * closeness * phase / (6 * MAX_GAME_PHASE)
+    };
+    EvalTerm { white: tropism(Color::White), black: tropism(Color::Black) }
+}
+
+/// Penalties for pieces caught where they can't get out: a bishop that took a rook pawn and was shut in by the
+/// pawn next to it, a knight in an enemy corner with every way out attacked by the enemy or taken by its own
+/// pieces, and a rook in the corner behind its own king with a pawn in front of it.
+pub fn trapped_pieces(board: &Board, params: &EvalParams) -> EvalTerm {
+    let trapped = |color: Color| {
+        //Squares are given from white's side, so black's are flipped
+        let relative = |square: Square| if color.is_white() {square} else {square.flip_vertical()};
+        let own = |role: Role, square: Square| board.piece_at(relative(square)) == Some(Piece { color, role });
+        let mut penalty = 0;
+
+        for (bishop, pawn) in [(Square::A7, Square::B6), (Square::H7, Square::G6), (Square::A6, Square::B5), (Square::H6, Square::G5)] {
+            if own(Role::Bishop, bishop) && board.piece_at(relative(pawn)) == Some((!color).pawn()) {
+                penalty += params.trapped_bishop_penalty;
+            }
+        }
+
+        for corner in [Square::A8, Square::H8] {
+            let exits = attacks::knight_attacks(relative(corner)) & !board.by_color(color);
+            let safe_exit = exits.into_iter().any(|exit| board.attacks_to(exit, !color, board.occupied()).is_empty());
+            if own(Role::Knight, corner) && !safe_exit {
+                penalty += params.trapped_knight_penalty;
+            }
+        }
+
+        if let Some(king) = board.king_of(color).map(relative).filter(|king| king.rank() == Rank::First) {
+            for rook in (board.by_piece(color.rook()) & Bitboard::from_rank(color.relative_rank(Rank::First))).into_iter().map(relative) {
+                let behind_king = if king.file() >= File::F {rook.file() > king.file()} else {king.file() <= File::D && rook.file() < king.file()};
+                let blocked = rook.offset(8).is_some_and(|front| own(Role::Pawn, front));
+                if behind_king && blocked {
+                    penalty += params.trapped_rook_penalty;
+                }
+            }
+        }
+        -penalty
+    };
+    EvalTerm { white: trapped(Color::White), black: trapped(Color::Black) }
+}
+
+/// Penalties for knights and bishops still on their starting squares and for a queen brought out while they are,
+/// and a bonus for a castled king. A piece moved twice early leaves another undeveloped, which is where it is paid for.
+/// Fades out as pieces come off the board, the same as [`king_tropism`].
+pub fn development(board: &Board, params: &EvalParams) -> EvalTerm {
+    let phase = game_phase(board);
+    let development = |color: Color| {
+        let back_rank = |file: File| Square::from_coords(file, color.relative_rank(Rank::First));
+        let on_start = |role: Role, files: &[File]| files.iter().filter(|&&file| board.piece_at(back_rank(file)) == Some(Piece { color, role })).count() as i32;
+        let undeveloped = on_start(Role::Knight, &[File::B, File::G]) + on_start(Role::Bishop, &[File::C, File::F]);
+
+        let mut score = -undeveloped * params.undeveloped_minor_penalty;
+        if board.by_piece(color.queen()).any() && on_start(Role::Queen, &[File::D]) == 0 {
+            score -= undeveloped * params.early_queen_penalty;
+        }
+        if board.king_of(color).is_some_and(|king| [File::B, File::C, File::G].into_iter().any(|file| king == back_rank(file))) {
+            score += params.castled_king_bonus;
+        }
+        score * phase / MAX_GAME_PHASE
+    };
+    EvalTerm { white: development(Color::White), black: development(Color::Black) }
+}
+
+/// The bonus for pawns advancing on the enemy king when the kings are on opposite wings, which is when
+/// a side can throw its pawns forward without opening up its own king.
+pub fn pawn_storms(board: &Board, params: &EvalParams) -> EvalTerm {
+    let (Some(white_king), Some(black_king)) = (board.king_of(Color::White), board.king_of(Color::Black)) else {
+        return EvalTerm::default();
+    };
+    let queenside = |king: Square| king.file() <= File::C;
+    let kingside = |king: Square| king.file() >= File::F;
+    if !(queenside(white_king) && kingside(black_king) || kingside(white_king) && queenside(black_king)) {
+        return EvalTerm::default();
+    }
+
+    let storm = |color: Color, king: Square| {
+        let files = Bitboard::from_file(king.file()) | neighbouring_files(king.file());
+        (board.by_piece(color.pawn()) & files).into_iter().map(|square| {
+            let advanced = if color.is_white() {square.rank() as usize} else {7 - square.rank() as usize};
+            params.pawn_storm_bonus[advanced]
+        }).sum()
+    };
+    EvalTerm { white: storm(Color::White, black_king), black: storm(Color::Black, white_king) }
+}
+
+/// The bonus for attacking enemy pieces, either with a piece worth less than the one attacked or when the
+/// attacked piece has nothing guarding it. Kings are left out, since attacking one is a check.
+pub fn threats(board: &Board, params: &EvalParams) -> EvalTerm {
+    let threats = |color: Color| {
+        let targets = board.by_color(!color) & !board.kings();
+        targets.into_iter().map(|square| {
+            let attackers = board.attacks_to(square, color, board.occupied());
+            let Some(cheapest) = attackers.into_iter().filter_map(|attacker| board.role_at(attacker)).map(material_value).min() else {
+                return 0;
+            };
+            let victim = board.role_at(square).map_or(0, material_value);
+            if cheapest < victim {
+                params.threat_bonus
+            }
+            else if board.attacks_to(square, !color, board.occupied()).is_empty() {
+                params.hanging_piece_bonus
+            }
+            else {
+                0
+            }
+        }).sum()
+    };
+    EvalTerm { white: threats(Color::White), black: threats(Color::Black) }
+}
+
+/// How many percent of `evaluation` are kept in an endgame the side ahead may not be able to win despite it:
+/// opposite coloured bishops, rook endings without a passed pawn for the side ahead, and no pawns left to
+/// promote without a rook's worth of material more, unless a queen is up against no queen.
+pub fn endgame_scale(board: &Board, pawn_structure: &PawnStructure, evaluation: i32, params: &EvalParams) -> i32 {
+    //Variants without kings play by other rules
+    if evaluation == 0 || board.king_of(Color::White).is_none() || board.king_of(Color::Black).is_none() {
+        return 100;
+    }
+    let strong = if evaluation > 0 {Color::White} else {Color::Black};
+    let pieces = |color: Color, role: Role| board.by_piece(Piece { color, role });
+    let piece_material = |color: Color| [Role::Knight, Role::Bishop, Role::Rook, Role::Queen].into_iter().map(|role| pieces(color, role).count() as i32 * material_value(role)).sum::<i32>();
+    let only = |roles: Bitboard| (board.occupied() & !board.pawns() & !board.kings()) == roles;
+
+    //A queen against a rook or minor pieces wins even though it is less than a rook ahead
+    let queen_ahead = pieces(strong, Role::Queen).any() && pieces(!strong, Role::Queen).is_empty();
+
+    let mut scale = 100;
+    if pieces(strong, Role::Pawn).is_empty() && piece_material(strong) - piece_material(!strong) < material_value(Role::Rook) && !queen_ahead {
+        scale = scale.min(params.pawnless_scale);
+    }
+    if only(board.bishops()) && pieces(Color::White, Role::Bishop).count() == 1 && pieces(Color::Black, Role::Bishop).count() == 1 {
+        let on_light = |color: Color| (pieces(color, Role::Bishop) & Bitboard::LIGHT_SQUARES).any();
+        if on_light(Color::White) != on_light(Color::Black) {
+            scale = scale.min(params.opposite_bishops_scale);
+        }
+    }
+    if only(board.rooks()) && pieces(Color::White, Role::Rook).count() == 1 && pieces(Color::Black, Role::Rook).count() == 1 {
+        let extra_pawns = pieces(strong, Role::Pawn).count() as i32 - pieces(!strong, Role::Pawn).count() as i32;
+        if extra_pawns <= 1 && (pawn_structure.passed & board.by_color(strong)).is_empty() {
+            scale = scale.min(params.rook_ending_scale);
+        }
+    }
+    scale
+}
 
-    material_difference + trade_bonus
+/// One term of the evaluation, split by the side it counts for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalTerm {
+    pub white: i32,
+    pub black: i32
+}
+
+impl EvalTerm {
+    /// The term from white's point of view.
+    pub fn total(&self) -> i32 {
+        self.white - self.black
+    }
+
+    //The term weighed by `weight` percent
+    fn weighed(self, weight: i32) -> EvalTerm {
+        EvalTerm {
+            white: self.white * weight / 100,
+            black: self.black * weight / 100
+        }
+    }
+}
+
+/// The numbers behind the engine's own evaluation, set with [`crate::Engine::set_eval_params`] or
+/// a [`crate::personality::Personality`]. The terms of [`EvalBreakdown`] are weighed in percent of their usual weight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalParams {
+    /// The weight of plain piece values and the material imbalance.
+    pub material: i32,
+    /// The weight of how much better or worse each piece's square is than its plain value, which is where piece
+    /// activity is seen, and of outposts, bad bishops and trapped pieces.
+    pub piece_squares: i32,
+    /// The bonus for trading when ahead, in centipawns for being ahead by all the material on the board.
+    pub trade_bonus: i32,
+    /// The weight of the pawn structure.
+    pub pawn_structure: i32,
+    pub doubled_pawn_penalty: i32,
+    pub isolated_pawn_penalty: i32,
+    /// Indexed by how far the pawn has advanced from its side's back rank.
+    pub passed_pawn_bonus: [i32; 8],
+    pub bishop_pair_bonus: i32,
+    /// Per rook or queen beyond a side's first, since they do the same work.
+    pub redundant_major_penalty: i32,
+    /// For the side with minor pieces against a rook, per rook.
+    pub minors_for_rook_bonus: i32,
+    /// For the side with rooks and minor pieces against a queen, per queen.
+    pub pieces_for_queen_bonus: i32,
+    pub knight_outpost_bonus: i32,
+    /// Per fixed pawn of the bishop's own side on its square colour.
+    pub bad_bishop_penalty: i32,
+    /// For a bishop on a7 or h7 shut in by an enemy pawn on b6 or g6, or on a6 or h6 by one on b5 or g5.
+    pub trapped_bishop_penalty: i32,
+    /// For a knight in an enemy corner that can't get out.
+    pub trapped_knight_penalty: i32,
+    /// For a rook in the corner behind its own king with a pawn of its side in front of it.
+    pub trapped_rook_penalty: i32,
+    /// The weight of development and castling.
+    pub development: i32,
+    /// Per knight or bishop still on its starting square.
+    pub undeveloped_minor_penalty: i32,
+    /// Per knight or bishop still on its starting square once the queen has left hers.
+    pub early_queen_penalty: i32,
+    /// For a king on the square castling took it to, or next to it on the queenside.
+    pub castled_king_bonus: i32,
+    /// The weight of king tropism and pawn storms.
+    pub king_attack: i32,
+    /// For a piece next to the enemy king with all the pieces on the board.
+    pub king_tropism: i32,
+    /// Indexed by how far the pawn has advanced from its side's back rank.
+    pub pawn_storm_bonus: [i32; 8],
+    /// For the passed pawn that queens first when the enemy king can't catch it, in an ending with only kings and pawns.
+    pub unstoppable_passer_bonus: i32,
+    /// Per step a king is closer than the enemy king to the square in front of one of its passed pawns, in an
+    /// ending with only kings and pawns.
+    pub passer_king_proximity_bonus: i32,
+    /// The weight of threats against enemy pieces.
+    pub threats: i32,
+    /// Per enemy piece attacked by a piece worth less than it.
+    pub threat_bonus: i32,
+    /// Per enemy piece attacked and not guarded, unless it is also attacked by a piece worth less.
+    pub hanging_piece_bonus: i32,
+    /// The percent of the evaluation kept when the side ahead has no pawns and isn't a rook's worth of pieces ahead.
+    pub pawnless_scale: i32,
+    /// The percent of the evaluation kept in an ending with only opposite coloured bishops and pawns.
+    pub opposite_bishops_scale: i32,
+    /// The percent of the evaluation kept in an ending with a rook each when the side ahead has at most
+    /// one pawn more and none passed.
+    pub rook_ending_scale: i32
+}
+
+impl EvalParams {
+    pub const DEFAULT: EvalParams = EvalParams {
+        material: 100,
+        piece_squares: 100,
+        trade_bonus: 100,
+        pawn_structure: 100,
+        doubled_pawn_penalty: 12,
+        isolated_pawn_penalty: 10,
+        passed_pawn_bonus: [0, 5, 10, 20, 35, 60, 100, 0],
+        bishop_pair_bonus: 30,
+        redundant_major_penalty: 10,
+        minors_for_rook_bonus: 25,
+        pieces_for_queen_bonus: 20,
+        knight_outpost_bonus: 20,
+        bad_bishop_penalty: 5,
+        trapped_bishop_penalty: 100,
+        trapped_knight_penalty: 50,
+        trapped_rook_penalty: 40,
+        development: 100,
+        undeveloped_minor_penalty: 15,
+        early_queen_penalty: 10,
+        castled_king_bonus: 30,
+        king_attack: 100,
+        king_tropism: 6,
+        pawn_storm_bonus: [0, 0, 4, 10, 18, 25, 25, 0],
+        unstoppable_passer_bonus: 500,
+        passer_king_proximity_bonus: 5,
+        threats: 100,
+        threat_bonus: 30,
+        hanging_piece_bonus: 15,
+        pawnless_scale: 20,
+        opposite_bishops_scale: 40,
+        rook_ending_scale: 60
+    };
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams::DEFAULT
+    }
+}
+
+/// The terms making up [`evaluate_board`], in centipawns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalBreakdown {
+    /// Plain piece values, before the squares the pieces stand on are considered.
+    pub material: EvalTerm,
+    /// How much better or worse each piece's square is than its plain value.
+    pub piece_squares: EvalTerm,
+    /// The bonus for trading when ahead in material, from white's point of view.
+    pub trade_bonus: i32,
+    /// Doubled, isolated and passed pawns.
+    pub pawn_structure: EvalTerm,
+    /// Combinations of pieces worth more or less than their values add up to.
+    pub imbalance: EvalTerm,
+    /// Knights on squares enemy pawns can't drive them from.
+    pub outposts: EvalTerm,
+    /// Bishops blocked by their own fixed pawns.
+    pub bad_bishops: EvalTerm,
+    /// Bishops, knights and rooks caught where they can't get out.
+    pub trapped_pieces: EvalTerm,
+    /// Knights and bishops left at home, early queen moves and castled kings.
+    pub development: EvalTerm,
+    /// Pieces close to the enemy king.
+    pub king_tropism: EvalTerm,
+    /// Pawns advancing on the enemy king when the kings are on opposite wings.
+    pub pawn_storms: EvalTerm,
+    /// Enemy pieces attacked by cheaper pieces or left unguarded.
+    pub threats: EvalTerm,
+    /// Passed pawns the enemy king can't catch and kings near passed pawns, in endings with only kings and pawns.
+    pub pawn_races: EvalTerm,
+    /// The percent taken off the sum of the terms for an endgame the side ahead is unlikely to win.
+    pub drawish: i32
+}
+
+impl EvalBreakdown {
+    /// The evaluation from white's point of view, the same as [`evaluate_board`] gives.
+    pub fn total(&self) -> i32 {
+        self.sum() * (100 - self.drawish) / 100
+    }
+
+    //The terms added up, before the endgame scaling
+    fn sum(&self) -> i32 {
+        self.material.total() + self.piece_squares.total() + self.trade_bonus + self.pawn_structure.total() + self.imbalance.total() + self.outposts.total() + self.bad_bishops.total()
+            + self.trapped_pieces.total() + self.development.total() + self.king_tropism.total() + self.pawn_storms.total() + self.threats.total() + self.pawn_races.total()
+    }
+}
+
+//Prints a table in pawns, one row per term
+impl fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<16}{:>9}{:>9}{:>9}", "Term", "White", "Black", "Total")?;
+        for (name, term) in [("Material", self.material), ("Piece squares", self.piece_squares), ("Pawn structure", self.pawn_structure), ("Imbalance", self.imbalance), ("Outposts", self.outposts), ("Bad bishops", self.bad_bishops),
+            ("Trapped pieces", self.trapped_pieces), ("Development", self.development), ("King tropism", self.king_tropism), ("Pawn storms", self.pawn_storms), ("Threats", self.threats), ("Pawn races", self.pawn_races)] {
+            writeln!(f, "{:<16}{:>9}{:>9}{:>9}", name, pawns(term.white), pawns(term.black), pawns(term.total()))?;
+        }
+        writeln!(f, "{:<16}{:>9}{:>9}{:>9}", "Trade bonus", "-", "-", pawns(self.trade_bonus))?;
+        writeln!(f, "{:<16}{:>9}{:>9}{:>9}", "Drawish", "-", "-", format!("{}%", -self.drawish))?;
+        writeln!(f, "{:<16}{:>9}{:>9}{:>9}", "Total", "-", "-", pawns(self.total()))
+    }
+}
+
+fn pawns(centipawns: i32) -> String {
+    format!("{:+.2}", centipawns as f64 / 100.0)
+}
+
+//Plain piece values, which the piece square tables add the worth of each square to
+pub(crate) const fn material_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight => 300,
+        Role::Bishop => 300,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        Role::King => 0
+    }
+}
+
+/// [`evaluate_board`] split up into its terms, for seeing why a position is scored the way it is.
+pub fn evaluate_board_detailed(board: &Board) -> EvalBreakdown {
+    evaluate_board_detailed_with_params(board, &EvalParams::DEFAULT)
+}
+
+/// [`evaluate_board_detailed`] with the terms worked out and weighed by `params`.
+pub fn evaluate_board_detailed_with_params(board: &Board, params: &EvalParams) -> EvalBreakdown {
+    evaluate_board_detailed_with_tables(board, params, &PieceSquareTables::DEFAULT)
+}
+
+/// [`evaluate_board_detailed_with_params`] with the squares of the pieces valued by `tables`.
+pub fn evaluate_board_detailed_with_tables(board: &Board, params: &EvalParams, tables: &PieceSquareTables) -> EvalBreakdown {
+    let mut material = EvalTerm::default();
+    let mut piece_squares = EvalTerm::default();
+
+    for color in Color::ALL {
+        let (material, piece_squares) = match color {
+            Color::White => (&mut material.white, &mut piece_squares.white),
+            Color::Black => (&mut material.black, &mut piece_squares.black)
+        };
+        //Tables are laid out from white's side, so black's squares are flipped
+        let flip = if color.is_white() {0} else {56};
+
+        for role in [Role::Pawn, Role::Knight, Role::Bishop, Role::Rook, Role::Queen] {
+            for square in board.by_piece(Piece { color, role }) {
+                *material += material_value(role);
+                *piece_squares += tables.table(role)[square as usize ^ flip] - material_value(role);
+            }
+        }
+        //Counted the same way evaluate_board counts it, which skips boards without exactly one king
+        if let Some(king) = board.king_of(color) {
+            *piece_squares += tables.king[king as usize ^ flip];
+        }
+    }
+
+    let material_difference = material.total() + piece_squares.total();
+    let total_material = material.white + material.black + piece_squares.white + piece_squares.black;
+
+    let pawn_structure = pawn_structure_with_params(board, params);
+
+    let mut breakdown = EvalBreakdown {
+        material: material.weighed(params.material),
+        piece_squares: piece_squares.weighed(params.piece_squares),
+        trade_bonus: trade_bonus(material_difference, total_material, params),
+        pawn_structure: pawn_structure.score.weighed(params.pawn_structure),
+        imbalance: material_imbalance(board, params).weighed(params.material),
+        outposts: knight_outposts(board, &pawn_structure, params).weighed(params.piece_squares),
+        bad_bishops: bad_bishops(board, &pawn_structure, params).weighed(params.piece_squares),
+        trapped_pieces: trapped_pieces(board, params).weighed(params.piece_squares),
+        development: development(board, params).weighed(params.development),
+        king_tropism: king_tropism(board, params).weighed(params.king_attack),
+        pawn_storms: pawn_storms(board, params).weighed(params.king_attack),
+        threats: threats(board, params).weighed(params.threats),
+        pawn_races: pawn_races(board, &pawn_structure, params).weighed(params.pawn_structure),
+        drawish: 0
+    };
+    breakdown.drawish = 100 - endgame_scale(board, &pawn_structure, breakdown.sum(), params);
+    breakdown
+}
+
+//Returns how much non-pawn material is left, from 24 at the start of the game down to 0
+pub fn game_phase(board: &Board) -> i32 {
+    let phase = board.knights().count() + board.bishops().count() + 2 * board.rooks().count() + 4 * board.queens().count();
+    //Promotions can push the count past the starting material
+    (phase as i32).min(MAX_GAME_PHASE)
+}
+
+pub const MAX_GAME_PHASE: i32 = 24;
+
+//Returns the value of the pieces in a crazyhouse pocket, about what each is worth on an average square
+pub fn pocket_value(pocket: &ByRole<u8>) -> i32 {
+    pocket.pawn as i32 * 110 + pocket.knight as i32 * 300 + pocket.bishop as i32 * 305 + pocket.rook as i32 * 510 + pocket.queen as i32 * 900
 }
 
 //Gives moves a score for sorting, lower scores are better
@@ -75,20 +759,21 @@ pub const fn move_score(m: &Move) -> i32 {
 //Gives captures a score for sorting, lower scores are better
 #[inline]
 pub fn capture_score(m: &Move) -> i32 {
-    let role = m.capture().unwrap();
-    return match m.role() {
+    let attacker_value = match m.role() {
         Role::Pawn => 1,
         Role::Bishop => 3,
         Role::Knight => 3,
         Role::Rook => 5,
         _ => 9
-    } - match role {
+    };
+    let victim_value = match m.capture().unwrap() {
         Role::Pawn => 10,
         Role::Bishop => 30,
         Role::Knight => 30,
         Role::Rook => 50,
         _ => 90
-    }
+    };
+    attacker_value - victim_value
 }
 
 #[cfg(test)]
@@ -104,6 +789,195 @@ mod tests {
         assert!(evaluate_board(&board1) > evaluate_board(&board2));
     }
 
+    #[test]
+    //The terms should add up to the evaluation
+    fn detailed_evaluation() {
+        let starting = evaluate_board_detailed(&Board::default());
+        assert!(starting.material.white == 3900 && starting.material.total() == 0);
+        assert!(starting.total() == evaluate_board(&Board::default()));
+
+        for fen in ["6qk/7p/8/8/8/Q7/P7/KQ6", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R", "8/8/8/8/8/8/8/8"] {
+            let board = Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid");
+            assert!(evaluate_board_detailed(&board).total() == evaluate_board(&board));
+        }
+
+        let board = Board::from_ascii_board_fen("6qk/7p/8/8/8/Q7/P7/KQ6".as_bytes()).expect("Fen should be valid");
+        let material_only = EvalParams { piece_squares: 0, trade_bonus: 0, pawn_structure: 0, development: 0, king_attack: 0, threats: 0, ..EvalParams::DEFAULT };
+        let breakdown = evaluate_board_detailed_with_params(&board, &material_only);
+        let detailed = evaluate_board_detailed(&board);
+        assert!(breakdown.total() == detailed.material.total() + detailed.imbalance.total());
+
+        let output = starting.to_string();
+        assert!(output.lines().count() == 16);
+        assert!(output.contains("Material") && output.contains("+39.00"));
+    }
+
+    #[test]
+    //Doubled, isolated and passed pawns should be found for both sides
+    fn pawn_terms() {
+        let starting = pawn_structure(&Board::default());
+        assert!(starting.score == EvalTerm::default() && starting.passed.is_empty());
+
+        //White has doubled isolated passed pawns on the c file, black an isolated passed pawn on a3 and connected ones on g7 and h6
+        let board = Board::from_ascii_board_fen("4k3/6p1/7p/8/8/p1P5/2P5/4K3".as_bytes()).expect("Fen should be valid");
+        let structure = pawn_structure(&board);
+        let params = EvalParams::DEFAULT;
+        assert!(structure.score.white == -params.doubled_pawn_penalty - 2 * params.isolated_pawn_penalty + params.passed_pawn_bonus[2]);
+        assert!(structure.score.black == -params.isolated_pawn_penalty + params.passed_pawn_bonus[5] + params.passed_pawn_bonus[1] + params.passed_pawn_bonus[2]);
+        assert!(structure.passed == Bitboard::from(Square::A3) | Square::C3 | Square::G7 | Square::H6);
+        assert!(evaluate_board_detailed(&board).pawn_structure == structure.score);
+    }
+
+    #[test]
+    //Only the combinations of pieces that play differently from their values should be corrected for
+    fn imbalance() {
+        let params = EvalParams::DEFAULT;
+        let imbalance = |fen: &str| material_imbalance(&Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid"), &params);
+        let starting = material_imbalance(&Board::default(), &params);
+        assert!(starting.white == params.bishop_pair_bonus - 2 * params.redundant_major_penalty && starting.total() == 0);
+
+        //White has two knights for a rook, then a bishop for a rook
+        assert!(imbalance("r3k3/8/8/8/8/8/8/1NN1K3") == EvalTerm { white: params.minors_for_rook_bonus, black: 0 });
+        assert!(imbalance("r3k3/8/8/8/8/8/8/2B1K3") == EvalTerm { white: params.minors_for_rook_bonus, black: 0 });
+        //Black has two rooks for the queen, white's lone queen isn't redundant
+        assert!(imbalance("r3k2r/8/8/8/8/8/8/3QK3") == EvalTerm { white: 0, black: params.pieces_for_queen_bonus - params.redundant_major_penalty });
+        assert!(imbalance("4k3/8/8/8/8/8/8/2B1KB2") == EvalTerm { white: params.bishop_pair_bonus, black: 0 });
+    }
+
+    #[test]
+    //Knights should only count outposts enemy pawns can't attack, and bishops each fixed pawn on their colour
+    fn piece_placement() {
+        //The knight on d5 is guarded by e4 and no black pawn can reach c6 or e6, the one on f5 can be driven off by g7
+        let board = Board::from_ascii_board_fen("4k3/6p1/3p4/3NpN2/4P3/2B5/8/4K3".as_bytes()).expect("Fen should be valid");
+        let structure = pawn_structure(&board);
+        let params = EvalParams::DEFAULT;
+        assert!(structure.outposts.white.contains(Square::D5) && !structure.outposts.white.contains(Square::F5));
+        assert!(structure.fixed.white == Bitboard::from(Square::E4) && structure.fixed.black == Bitboard::from(Square::E5));
+        assert!(knight_outposts(&board, &structure, &params) == EvalTerm { white: params.knight_outpost_bonus, black: 0 });
+
+        //The dark squared bishop on c3 isn't hemmed in by the fixed pawn on e4, a light squared one would be
+        assert!(bad_bishops(&board, &structure, &params) == EvalTerm::default());
+        let board = Board::from_ascii_board_fen("4k3/6p1/3p4/3NpN2/4P3/3B4/8/4K3".as_bytes()).expect("Fen should be valid");
+        assert!(bad_bishops(&board, &structure, &params) == EvalTerm { white: -params.bad_bishop_penalty, black: 0 });
+        assert!(evaluate_board_detailed(&board).total() == evaluate_board(&board));
+    }
+
+    #[test]
+    //Each of the classic traps should be found for either side, and only when the piece can't get out
+    fn trapped_piece_terms() {
+        let params = EvalParams::DEFAULT;
+        let trapped = |fen: &str| trapped_pieces(&Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid"), &params);
+        assert!(trapped("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR") == EvalTerm::default());
+
+        //The bishop on a7 is shut in by b6, the one on h2 only once a white pawn reaches g3
+        assert!(trapped("4k3/B7/1p6/8/8/8/7b/4K3") == EvalTerm { white: -params.trapped_bishop_penalty, black: 0 });
+        assert!(trapped("4k3/B7/1p6/8/8/6P1/7b/4K3") == EvalTerm { white: -params.trapped_bishop_penalty, black: -params.trapped_bishop_penalty });
+
+        //The knight on h8 can only go to f7, guarded by the king, and g6, guarded by the pawn on h7
+        assert!(trapped("4k2N/7p/8/8/8/8/8/4K3").white == -params.trapped_knight_penalty);
+        assert!(trapped("4k2N/8/7p/8/8/8/8/4K3").white == 0);
+
+        //The king on f1 shuts in the rook on h1, castled on g1 it has the rook outside on f1
+        assert!(trapped("4k3/8/8/8/8/8/5PPP/5K1R").white == -params.trapped_rook_penalty);
+        assert!(trapped("4k3/8/8/8/8/8/5PPP/5RK1").white == 0);
+        assert!(trapped("r1k5/pp6/8/8/8/8/8/4K3").black == -params.trapped_rook_penalty);
+    }
+
+    #[test]
+    //Minor pieces left at home should cost more once the queen is out, and a castled king should count
+    fn development_terms() {
+        let params = EvalParams::DEFAULT;
+        let development = |fen: &str| development(&Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid"), &params);
+        let starting = development("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+        assert!(starting.white == -4 * params.undeveloped_minor_penalty && starting.total() == 0);
+
+        //White has castled with only the c1 bishop left at home, black has brought the queen out before any minor piece
+        let breakdown = development("rnb1kbnr/pppp1ppp/8/4p2q/4P3/2N2N2/PPPPBPPP/R1BQ1RK1");
+        assert!(breakdown.white == params.castled_king_bonus - params.undeveloped_minor_penalty);
+        assert!(breakdown.black == -4 * (params.undeveloped_minor_penalty + params.early_queen_penalty));
+    }
+
+    #[test]
+    //Pieces should count for being near the enemy king, and pawns for storming it only with the kings on opposite wings
+    fn king_attack() {
+        let params = EvalParams::DEFAULT;
+        let near = Board::from_ascii_board_fen("r5k1/pp3ppp/8/6Q1/8/8/PPP5/1K1R4".as_bytes()).expect("Fen should be valid");
+        let far = Board::from_ascii_board_fen("r5k1/pp3ppp/8/8/8/8/PPP5/QK1R4".as_bytes()).expect("Fen should be valid");
+        assert!(king_tropism(&near, &params).white > king_tropism(&far, &params).white);
+        assert!(king_tropism(&near, &params).black == king_tropism(&far, &params).black);
+
+        //White storms with the g and h pawns, black has only taken a step with the a pawn
+        let opposite = Board::from_ascii_board_fen("r5k1/5p1p/p5p1/6PP/8/8/PPP5/1K1R4".as_bytes()).expect("Fen should be valid");
+        let storms = pawn_storms(&opposite, &params);
+        assert!(storms.white == 2 * params.pawn_storm_bonus[4]);
+        assert!(storms.black == params.pawn_storm_bonus[2]);
+        let same_side = Board::from_ascii_board_fen("r5k1/5p1p/p5p1/6PP/8/8/PPP5/6KR".as_bytes()).expect("Fen should be valid");
+        assert!(pawn_storms(&same_side, &params) == EvalTerm::default());
+        assert!(evaluate_board_detailed(&opposite).total() == evaluate_board(&opposite));
+    }
+
+    #[test]
+    //Pieces should count as threatened when attacked by something cheaper or when nothing guards them
+    fn threat_terms() {
+        let params = EvalParams::DEFAULT;
+        let threats = |fen: &str| threats(&Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid"), &params);
+        assert!(threats("4k3/8/8/8/8/8/8/4K3") == EvalTerm::default());
+
+        //The pawn on e4 attacks the knight on d5, which is guarded
+        assert!(threats("4k3/8/4p3/3n4/4P3/8/8/4K3") == EvalTerm { white: params.threat_bonus, black: 0 });
+        //The rook on a1 attacks the unguarded rook on a8, which attacks it back just as unguarded
+        assert!(threats("r3k3/8/8/8/8/8/8/R5K1") == EvalTerm { white: params.hanging_piece_bonus, black: params.hanging_piece_bonus });
+        //A guarded piece attacked by one worth as much isn't threatened
+        assert!(threats("r3k3/p7/8/8/8/8/P7/R5K1") == EvalTerm::default());
+    }
+
+    #[test]
+    //A passed pawn should only count as unstoppable outside the enemy king's square, and only the faster of two
+    fn pawn_race_terms() {
+        let params = EvalParams::DEFAULT;
+        let races = |fen: &str| {
+            let board = Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid");
+            assert!(evaluate_board_detailed(&board).total() == evaluate_board(&board));
+            pawn_races(&board, &pawn_structure(&board), &params)
+        };
+        //The a pawn needs three moves to queen, so a king four moves from a8 catches it and one seven moves away doesn't
+        assert!(races("8/8/8/P3k3/8/8/8/K7").white < params.unstoppable_passer_bonus);
+        assert!(races("8/8/8/P7/8/8/8/K5k1").white >= params.unstoppable_passer_bonus);
+        //The black pawn queens in three moves, the white one in five
+        let race = races("8/8/8/8/7p/8/P7/K5k1");
+        assert!(race.black >= params.unstoppable_passer_bonus && race.white < params.unstoppable_passer_bonus);
+        //Any piece on the board makes it more than a pawn ending
+        assert!(races("8/8/8/P7/8/8/8/K5kn") == EvalTerm::default());
+    }
+
+    #[test]
+    //Endgames the side ahead can't be expected to win should be scaled down, and only those
+    fn endgame_scaling() {
+        let params = EvalParams::DEFAULT;
+        let scale = |fen: &str| {
+            let board = Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid");
+            assert!(evaluate_board_detailed(&board).total() == evaluate_board(&board));
+            endgame_scale(&board, &pawn_structure(&board), evaluate_board_detailed(&board).sum(), &params)
+        };
+        assert!(scale("4k3/8/8/8/8/8/8/2B1K3") == params.pawnless_scale);
+        assert!(scale("4k3/8/8/8/8/8/8/R3K3") == 100);
+        //Queen against rook is won, but rook and bishop against rook usually isn't
+        assert!(scale("r3k3/8/8/8/8/8/8/Q3K3") == 100);
+        assert!(scale("r3k3/8/8/8/8/8/8/RB2K3") == params.pawnless_scale);
+        assert!(scale("4k3/2b2p2/5p2/8/8/5P2/5PP1/4KB2") == params.opposite_bishops_scale);
+        assert!(scale("4k3/1b3p2/5p2/8/8/5P2/5PP1/4KB2") == 100);
+        assert!(scale("4k3/r4p2/6p1/8/8/5PP1/5P2/4K2R") == params.rook_ending_scale);
+        //A passed pawn on the a file gives the side ahead something to play for
+        assert!(scale("4k3/r4p2/6p1/8/P7/6P1/5P2/4K2R") == 100);
+    }
+
+    #[test]
+    //Phase should go from the full starting value down to 0 with only pawns left
+    fn phase() {
+        assert!(game_phase(&Board::default()) == MAX_GAME_PHASE);
+        assert!(game_phase(&Board::from_ascii_board_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3".as_bytes()).expect("Fen should be valid")) == 0);
+    }
+
     #[test]
     //Basic test of move ordering
     fn ordering() {
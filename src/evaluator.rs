@@ -0,0 +1,54 @@
+//! Pluggable evaluation.
+//!
+//! The search only ever looks at a position's value through an [`Evaluator`], so a different evaluation
+//! can be tried without touching the search. [`Engine::with_evaluator`](crate::Engine::with_evaluator)
+//! sets it, and [`PieceSquareEvaluator`], the engine's own evaluation, is used otherwise.
+//! [`evaluate_board_detailed`] breaks the engine's own evaluation of a board down into its terms.
+
+use shakmaty::Chess;
+
+use crate::variant::SearchPosition;
+pub use crate::evaluation::{evaluate_board_detailed, evaluate_board_detailed_with_params, evaluate_board_detailed_with_tables, EvalBreakdown, EvalParams, EvalTerm};
+
+/// Evaluates positions of type `P` for the search, from any of the search's threads.
+pub trait Evaluator<P: SearchPosition = Chess>: Send + Sync {
+    /// Returns an evaluation of `position` in centipawns from the perspective of white.
+    fn evaluate(&self, position: &P) -> i32;
+
+    /// [`Evaluator::evaluate`] given the material and piece square evaluation of the board, which the search
+    /// keeps up to date as it plays moves. Evaluations that build on it can use it instead of working it out again.
+    fn evaluate_with_board(&self, position: &P, board_evaluation: i32) -> i32 {
+        let _ = board_evaluation;
+        self.evaluate(position)
+    }
+
+    /// [`Evaluator::evaluate_with_board`] with the evaluation's terms weighed by `params`, which come from the
+    /// engine's [`Engine::set_eval_params`](crate::Engine::set_eval_params). Evaluations without such terms ignore them.
+    fn evaluate_with_params(&self, position: &P, board_evaluation: i32, params: &EvalParams) -> i32 {
+        let _ = params;
+        self.evaluate_with_board(position, board_evaluation)
+    }
+}
+
+/// Material and piece square tables, along with whatever the kind of position adds to them
+/// through [`SearchPosition::evaluate`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PieceSquareEvaluator;
+
+impl<P: SearchPosition> Evaluator<P> for PieceSquareEvaluator {
+    fn evaluate(&self, position: &P) -> i32 {
+        position.evaluate()
+    }
+
+    fn evaluate_with_board(&self, position: &P, board_evaluation: i32) -> i32 {
+        position.evaluate_with_board(board_evaluation)
+    }
+
+    //The board evaluation the search keeps up to date is only for the default params, others add up the terms again
+    fn evaluate_with_params(&self, position: &P, board_evaluation: i32, params: &EvalParams) -> i32 {
+        if *params == EvalParams::DEFAULT {
+            return position.evaluate_with_board(board_evaluation);
+        }
+        position.evaluate_with_board(evaluate_board_detailed_with_params(position.board(), params).total())
+    }
+}
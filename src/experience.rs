@@ -0,0 +1,195 @@
+//! What the engine learned from its own games, kept between games in an experience file.
+//!
+//! Every move the engine played is recorded against the position it was played in along with how the game
+//! went for it. Searches with an [`Experience`] set (see [`crate::Engine::set_experience`]) score root
+//! moves that have repeatedly led to losses a little lower, so the engine drifts away from them unless
+//! they are clearly best.
+//!
+//! The file is plain text with a line per position and move: the position's zobrist hash in hex, the
+//! move in UCI notation, then the wins, draws and losses it led to.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Color, EnPassantMode, Move, Outcome, Position};
+
+//A move has to lose this many more games than it won before it is avoided
+const MIN_NET_LOSSES: u32 = 2;
+const PENALTY_PER_LOSS: i32 = 10;
+const MAX_PENALTY: i32 = 60;
+
+/// How the games a move was played in ended, for the side that played it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveRecord {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+/// Moves the engine has played and how the games went, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Experience {
+    moves: HashMap<(u64, String), MoveRecord>
+}
+
+impl Experience {
+    pub fn new() -> Experience {
+        Experience::default()
+    }
+
+    /// How many different moves have been recorded.
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Records that `mv` was played in `position` in a game that ended with `outcome`.
+    pub fn record<P: Position>(&mut self, position: &P, mv: &Move, outcome: Outcome) {
+        let record = self.moves.entry(key(position, mv)).or_default();
+        match outcome {
+            Outcome::Decisive { winner } if winner == position.turn() => record.wins += 1,
+            Outcome::Decisive { .. } => record.losses += 1,
+            Outcome::Draw => record.draws += 1
+        }
+    }
+
+    /// Records every move `engine` played in a game that started at `start`, went on with `moves`
+    /// and ended with `outcome`. Moves after an illegal one are left out.
+    pub fn record_game<P: Position + Clone>(&mut self, start: &P, moves: &[Move], outcome: Outcome, engine: Color) {
+        let mut position = start.clone();
+        for mv in moves {
+            if !position.is_legal(mv) {
+                break;
+            }
+            if position.turn() == engine {
+                self.record(&position, mv, outcome);
+            }
+            position.play_unchecked(mv);
+        }
+    }
+
+    /// What is known about `mv` in `position`, `None` if it has never been played there.
+    pub fn get<P: Position>(&self, position: &P, mv: &Move) -> Option<MoveRecord> {
+        self.moves.get(&key(position, mv)).copied()
+    }
+
+    /// How many centipawns the search takes off the score of `mv` in `position`.
+    pub fn penalty<P: Position>(&self, position: &P, mv: &Move) -> i32 {
+        let Some(record) = self.get(position, mv) else {
+            return 0;
+        };
+        let net_losses = record.losses.saturating_sub(record.wins);
+        if net_losses < MIN_NET_LOSSES {
+            return 0;
+        }
+        (net_losses as i32).saturating_mul(PENALTY_PER_LOSS).min(MAX_PENALTY)
+    }
+
+    /// Reads an experience file.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Experience> {
+        Experience::from_text(&std::fs::read_to_string(path)?)
+    }
+
+    /// Reads experience from the contents of an experience file.
+    pub fn from_text(text: &str) -> io::Result<Experience> {
+        let mut experience = Experience::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid experience line {}", line));
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [hash, mv, wins, draws, losses] = fields[..] else {
+                return Err(invalid());
+            };
+            let hash = u64::from_str_radix(hash, 16).map_err(|_| invalid())?;
+            let count = |count: &str| count.parse::<u32>().map_err(|_| invalid());
+            let record = MoveRecord { wins: count(wins)?, draws: count(draws)?, losses: count(losses)? };
+            experience.moves.insert((hash, mv.to_string()), record);
+        }
+        Ok(experience)
+    }
+
+    /// Writes the experience file.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+/// The contents of the experience file, sorted so files can be compared.
+impl fmt::Display for Experience {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut moves: Vec<_> = self.moves.iter().collect();
+        moves.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for ((hash, mv), record) in moves {
+            writeln!(f, "{:016x} {} {} {} {}", hash, mv, record.wins, record.draws, record.losses)?;
+        }
+        Ok(())
+    }
+}
+
+fn key<P: Position>(position: &P, mv: &Move) -> (u64, String) {
+    let hash: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+    (hash.0, mv.to_uci(position.castles().mode()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{Chess, Square, Role};
+
+    fn e4() -> Move {
+        Move::Normal { role: Role::Pawn, from: Square::E2, capture: None, to: Square::E4, promotion: None }
+    }
+
+    #[test]
+    //Only the engine's moves should be recorded, each scored for the side that played it
+    fn recording() {
+        let chess = Chess::new();
+        let e5 = Move::Normal { role: Role::Pawn, from: Square::E7, capture: None, to: Square::E5, promotion: None };
+        let mut experience = Experience::new();
+        experience.record_game(&chess, &[e4(), e5.clone()], Outcome::Decisive { winner: Color::Black }, Color::White);
+        experience.record_game(&chess, &[e4(), e5.clone()], Outcome::Draw, Color::White);
+        assert!(experience.len() == 1);
+        assert!(experience.get(&chess, &e4()) == Some(MoveRecord { wins: 0, draws: 1, losses: 1 }));
+
+        experience.record_game(&chess, &[e4(), e5.clone()], Outcome::Decisive { winner: Color::Black }, Color::Black);
+        let after_e4 = chess.clone().play(&e4()).expect("move should be legal");
+        assert!(experience.get(&after_e4, &e5) == Some(MoveRecord { wins: 1, draws: 0, losses: 0 }));
+    }
+
+    #[test]
+    //Moves should only be penalized once they have lost repeatedly, and by no more than the maximum
+    fn penalties() {
+        let chess = Chess::new();
+        let mut experience = Experience::new();
+        let loss = Outcome::Decisive { winner: Color::Black };
+        experience.record(&chess, &e4(), loss);
+        assert!(experience.penalty(&chess, &e4()) == 0);
+        experience.record(&chess, &e4(), loss);
+        assert!(experience.penalty(&chess, &e4()) == 2 * PENALTY_PER_LOSS);
+        experience.record(&chess, &e4(), Outcome::Decisive { winner: Color::White });
+        assert!(experience.penalty(&chess, &e4()) == 0);
+        for _ in 0..20 {
+            experience.record(&chess, &e4(), loss);
+        }
+        assert!(experience.penalty(&chess, &e4()) == MAX_PENALTY);
+    }
+
+    #[test]
+    //The text form should read back as it was, and lines that aren't records should be refused
+    fn text_round_trip() {
+        let chess = Chess::new();
+        let mut experience = Experience::new();
+        experience.record(&chess, &e4(), Outcome::Draw);
+        let text = experience.to_string();
+        assert!(text == "463b96181691fc9c e2e4 0 1 0\n");
+        assert!(Experience::from_text(&text).expect("text should be read") == experience);
+        assert!(Experience::from_text("463b96181691fc9c e2e4 0 1").is_err());
+        assert!(Experience::from_text("not hex e2e4 0 1 0").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! C bindings, for embedding the engine in programs that aren't written in Rust.
+//!
+//! The functions are exported with the `ffi` feature, and declared for C in `include/hodgey_chess_engine.h`.
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib` builds them into a dynamic library.
+//!
+//! An engine comes from [`hodgey_new`] and must be given back to [`hodgey_free`]. It starts from the
+//! standard starting position, which [`hodgey_set_position_fen`] replaces and [`hodgey_play_move`] plays
+//! moves on, keeping track of repetitions the way a game would.
+
+use std::ffi::{c_char, CStr};
+use std::time::Duration;
+
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, EnPassantMode, Position};
+
+use crate::{chess_from_fen, Engine, SearchLimits, SearchResult};
+
+/// An engine along with the game it is searching, opaque to C.
+pub struct HodgeyEngine {
+    engine: Engine,
+    chess: Chess,
+    previously_seen_hashes: Vec<u64>
+}
+
+impl HodgeyEngine {
+    fn set_position(&mut self, chess: Chess) {
+        self.previously_seen_hashes = vec![hash(&chess)];
+        self.chess = chess;
+    }
+}
+
+/// What a search found, see [`SearchResult`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HodgeySearchResult {
+    /// The best move in UCI notation, NUL terminated. Empty if there is no legal move.
+    pub best_move: [c_char; 8],
+    /// Score in centipawns from the point of view of the side to move.
+    pub score: i32,
+    pub depth: u16,
+    pub nodes: u64
+}
+
+impl HodgeySearchResult {
+    fn new(result: &SearchResult, chess: &Chess) -> HodgeySearchResult {
+        let mut best_move = [0; 8];
+        if let Some(m) = &result.best_move {
+            //UCI moves are at most 5 characters long, such as "e7e8q"
+            for (c, byte) in best_move.iter_mut().zip(m.to_uci(chess.castles().mode()).to_string().bytes()) {
+                *c = byte as c_char;
+            }
+        }
+        HodgeySearchResult {
+            best_move,
+            score: result.score,
+            depth: result.depth,
+            nodes: result.nodes
+        }
+    }
+}
+
+fn hash(chess: &Chess) -> u64 {
+    let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+    hash.0
+}
+
+/// A new engine at the starting position.
+#[no_mangle]
+pub extern "C" fn hodgey_new() -> *mut HodgeyEngine {
+    let mut engine = HodgeyEngine {
+        engine: Engine::new(),
+        chess: Chess::default(),
+        previously_seen_hashes: Vec::new()
+    };
+    engine.set_position(Chess::default());
+    Box::into_raw(Box::new(engine))
+}
+
+/// Frees an engine. Null is ignored.
+///
+/// # Safety
+///
+/// `engine` must be null or come from [`hodgey_new`], and mustn't be used again.
+#[no_mangle]
+pub unsafe extern "C" fn hodgey_free(engine: *mut HodgeyEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Sets up the position in `fen`. Returns false and leaves the position as it was if the FEN
+/// can't be read or isn't a legal position.
+///
+/// # Safety
+///
+/// `engine` must be null or come from [`hodgey_new`], and `fen` must be null or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hodgey_set_position_fen(engine: *mut HodgeyEngine, fen: *const c_char) -> bool {
+    let Some((engine, fen)) = (unsafe { engine_and_text(engine, fen) }) else {
+        return false;
+    };
+    match chess_from_fen(fen) {
+        Ok(chess) => {
+            engine.set_position(chess);
+            true
+        },
+        Err(_) => false
+    }
+}
+
+/// Plays `uci_move`, such as "e2e4". Returns false and leaves the position as it was if the move isn't legal.
+///
+/// # Safety
+///
+/// `engine` must be null or come from [`hodgey_new`], and `uci_move` must be null or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hodgey_play_move(engine: *mut HodgeyEngine, uci_move: *const c_char) -> bool {
+    let Some((engine, uci_move)) = (unsafe { engine_and_text(engine, uci_move) }) else {
+        return false;
+    };
+    let Some(m) = UciMove::from_ascii(uci_move.as_bytes()).ok().and_then(|m| m.to_move(&engine.chess).ok()) else {
+        return false;
+    };
+
+    if m.is_zeroing() {
+        engine.previously_seen_hashes.clear();
+    }
+    engine.chess.play_unchecked(&m);
+    engine.previously_seen_hashes.push(hash(&engine.chess));
+    true
+}
+
+/// Searches the current position for `movetime_ms` milliseconds. A null engine finds no move.
+///
+/// # Safety
+///
+/// `engine` must be null or come from [`hodgey_new`].
+#[no_mangle]
+pub unsafe extern "C" fn hodgey_search_movetime(engine: *mut HodgeyEngine, movetime_ms: u64) -> HodgeySearchResult {
+    let limits = SearchLimits { move_time: Some(Duration::from_millis(movetime_ms)), ..SearchLimits::default() };
+    unsafe { search(engine, limits) }
+}
+
+/// Searches the current position to `depth` plies. A null engine finds no move.
+///
+/// # Safety
+///
+/// `engine` must be null or come from [`hodgey_new`].
+#[no_mangle]
+pub unsafe extern "C" fn hodgey_search_depth(engine: *mut HodgeyEngine, depth: u16) -> HodgeySearchResult {
+    let limits = SearchLimits { max_depth: Some(depth), ..SearchLimits::default() };
+    unsafe { search(engine, limits) }
+}
+
+unsafe fn search(engine: *mut HodgeyEngine, limits: SearchLimits) -> HodgeySearchResult {
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return HodgeySearchResult::default();
+    };
+    let result = engine.engine.search(&engine.chess, limits, &mut engine.previously_seen_hashes);
+    HodgeySearchResult::new(&result, &engine.chess)
+}
+
+//Text that isn't UTF-8 is treated like a null pointer
+unsafe fn engine_and_text<'a>(engine: *mut HodgeyEngine, text: *const c_char) -> Option<(&'a mut HodgeyEngine, &'a str)> {
+    if text.is_null() {
+        return None;
+    }
+    let text = unsafe { CStr::from_ptr(text) }.to_str().ok()?;
+    Some((unsafe { engine.as_mut() }?, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn best_move(result: &HodgeySearchResult) -> String {
+        unsafe { CStr::from_ptr(result.best_move.as_ptr()) }.to_str().expect("move should be text").to_string()
+    }
+
+    #[test]
+    //A C caller should be able to set up a position, play moves and search, with bad input refused
+    fn c_calls() {
+        let engine = hodgey_new();
+        let mate = CString::new("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let invalid = CString::new("8/8/8 w - - 0 1").unwrap();
+        unsafe {
+            assert!(!best_move(&hodgey_search_depth(engine, 1)).is_empty());
+            assert!(!hodgey_set_position_fen(engine, invalid.as_ptr()));
+            assert!(hodgey_set_position_fen(engine, mate.as_ptr()));
+
+            let result = hodgey_search_depth(engine, 3);
+            assert!(best_move(&result) == "a1a6" && result.depth == 3);
+            assert!(!hodgey_play_move(engine, CString::new("a1a9").unwrap().as_ptr()));
+            assert!(hodgey_play_move(engine, CString::new("a1a6").unwrap().as_ptr()));
+            assert!(hodgey_play_move(engine, CString::new("b7a6").unwrap().as_ptr()));
+            assert!(best_move(&hodgey_search_movetime(engine, 50)) == "b6b7");
+
+            assert!(best_move(&hodgey_search_depth(std::ptr::null_mut(), 1)).is_empty());
+            assert!(!hodgey_play_move(engine, std::ptr::null()));
+            hodgey_free(engine);
+        }
+    }
+
+    #[test]
+    //A timed search shouldn't write anything to the stdout of the program embedding the engine
+    fn movetime_search_is_silent() {
+        //The test harness captures printing in its own process, so the search is run again in a child that doesn't
+        if std::env::var_os("HODGEY_SILENT_SEARCH_CHILD").is_some() {
+            println!("search started");
+            let engine = hodgey_new();
+            unsafe {
+                hodgey_search_movetime(engine, 50);
+                hodgey_free(engine);
+            }
+            println!("search finished");
+            return;
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().expect("test binary should be found"))
+            .args(["ffi::tests::movetime_search_is_silent", "--exact", "--nocapture", "--test-threads=1"])
+            .env("HODGEY_SILENT_SEARCH_CHILD", "1")
+            .output()
+            .expect("test binary should run");
+        let stdout = String::from_utf8(output.stdout).expect("output should be text");
+        assert!(output.status.success());
+        assert!(stdout.contains("search started\nsearch finished\n"));
+    }
+}
@@ -0,0 +1,233 @@
+//! A bitbase of king and pawn against king endings, worked out once the first time one is probed.
+//!
+//! Every position with the pawn on the a to d files is solved by going back from the positions that are
+//! decided right away: the pawn queening safely is a win, and stalemate or losing the pawn is a draw.
+//! Positions with the pawn on the other files are mirrored, and ones with a black pawn flipped.
+
+use std::sync::OnceLock;
+
+use shakmaty::{attacks, Bitboard, Board, Color, File, Rank, Square};
+
+//Which side is to move, both kings, and the pawn on one of 24 squares
+const POSITIONS: usize = 2 * 64 * 64 * 24;
+
+//Added to the evaluation of a won position, small enough that queening still looks better
+const WIN_BONUS: i32 = 400;
+
+static WINS: OnceLock<Vec<u64>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win
+}
+
+//A position from the side with the pawn, which is white and has its pawn on the a to d files
+#[derive(Clone, Copy)]
+struct Entry {
+    strong_to_move: bool,
+    strong_king: Square,
+    weak_king: Square,
+    pawn: Square
+}
+
+impl Entry {
+    fn index(self) -> usize {
+        let pawn = self.pawn.file() as usize + 4 * (self.pawn.rank() as usize - 1);
+        usize::from(self.strong_to_move) | (self.strong_king as usize) << 1 | (self.weak_king as usize) << 7 | pawn << 13
+    }
+
+    fn from_index(index: usize) -> Entry {
+        let pawn = index >> 13;
+        Entry {
+            strong_to_move: index & 1 == 1,
+            strong_king: Square::new((index >> 1 & 63) as u32),
+            weak_king: Square::new((index >> 7 & 63) as u32),
+            pawn: Square::from_coords(File::new((pawn % 4) as u32), Rank::new((pawn / 4 + 1) as u32))
+        }
+    }
+
+    //What can be told about the position without looking at the positions after it
+    fn initial_outcome(self) -> Outcome {
+        let Entry { strong_to_move, strong_king, weak_king, pawn } = self;
+        if strong_king == pawn || weak_king == pawn || strong_king.distance(weak_king) <= 1 {
+            return Outcome::Invalid;
+        }
+        if strong_to_move && attacks::pawn_attacks(Color::White, pawn).contains(weak_king) {
+            return Outcome::Invalid;
+        }
+
+        if strong_to_move && pawn.rank() == Rank::Seventh {
+            let queening = pawn.offset(8).expect("pawn should be below the last rank");
+            if queening != strong_king && queening != weak_king && (weak_king.distance(queening) > 1 || strong_king.distance(queening) == 1) {
+                return Outcome::Win;
+            }
+        }
+        if !strong_to_move {
+            let guarded = attacks::king_attacks(strong_king) | attacks::pawn_attacks(Color::White, pawn);
+            //A lone king can't be mated by a king and pawn, so a king with no moves is stalemated
+            if (attacks::king_attacks(weak_king) & !guarded).is_empty() {
+                return Outcome::Draw;
+            }
+            if attacks::king_attacks(weak_king).contains(pawn) && !attacks::king_attacks(strong_king).contains(pawn) {
+                return Outcome::Draw;
+            }
+        }
+        Outcome::Unknown
+    }
+
+    //The outcome from the outcomes of the positions after each move, unknown until enough of them are known
+    fn outcome(self, outcomes: &[Outcome]) -> Outcome {
+        let Entry { strong_to_move, strong_king, weak_king, pawn } = self;
+        let mut children = Vec::with_capacity(10);
+
+        if strong_to_move {
+            for to in attacks::king_attacks(strong_king) & !attacks::king_attacks(weak_king) & !Bitboard::from(pawn) {
+                children.push(Entry { strong_to_move: false, strong_king: to, ..self });
+            }
+            //Queening is only worked out in the initial outcome, so the pawn is only pushed up to the seventh rank
+            let empty = |square: Square| square != strong_king && square != weak_king;
+            if pawn.rank() < Rank::Seventh {
+                let push = pawn.offset(8).expect("pawn should be below the last rank");
+                if empty(push) {
+                    children.push(Entry { strong_to_move: false, pawn: push, ..self });
+                    let double_push = pawn.offset(16).expect("pawn should be below the last rank");
+                    if pawn.rank() == Rank::Second && empty(double_push) {
+                        children.push(Entry { strong_to_move: false, pawn: double_push, ..self });
+                    }
+                }
+            }
+            let outcomes = children.iter().map(|child| outcomes[child.index()]);
+            combine(outcomes, Outcome::Win, Outcome::Draw)
+        }
+        else {
+            let guarded = attacks::king_attacks(strong_king) | attacks::pawn_attacks(Color::White, pawn);
+            //Taking the pawn was found in the initial outcome
+            for to in attacks::king_attacks(weak_king) & !guarded & !Bitboard::from(pawn) {
+                children.push(Entry { strong_to_move: true, weak_king: to, ..self });
+            }
+            let outcomes = children.iter().map(|child| outcomes[child.index()]);
+            combine(outcomes, Outcome::Draw, Outcome::Win)
+        }
+    }
+}
+
+//`good` if any move reaches it, `bad` if every move reaches that, otherwise unknown
+fn combine(outcomes: impl Iterator<Item = Outcome>, good: Outcome, bad: Outcome) -> Outcome {
+    let mut all_bad = true;
+    for outcome in outcomes {
+        if outcome == good {
+            return good;
+        }
+        all_bad &= outcome == bad;
+    }
+    if all_bad {bad} else {Outcome::Unknown}
+}
+
+//Solves every position, keeping a bit for each won one
+fn generate() -> Vec<u64> {
+    let mut outcomes: Vec<Outcome> = (0..POSITIONS).map(|index| Entry::from_index(index).initial_outcome()).collect();
+    loop {
+        let mut changed = false;
+        for index in 0..POSITIONS {
+            if outcomes[index] == Outcome::Unknown {
+                let outcome = Entry::from_index(index).outcome(&outcomes);
+                if outcome != Outcome::Unknown {
+                    outcomes[index] = outcome;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    //Whatever still isn't known can't be forced to a win
+    let mut wins = vec![0; POSITIONS / 64];
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        if outcome == Outcome::Win {
+            wins[index / 64] |= 1 << (index % 64);
+        }
+    }
+    wins
+}
+
+/// Whether the side with the pawn wins, or `None` if `board` isn't a king and pawn against king ending.
+pub fn probe(board: &Board, turn: Color) -> Option<bool> {
+    if board.occupied().count() != 3 {
+        return None;
+    }
+    let pawn = board.pawns().single_square()?;
+    let strong = board.color_at(pawn)?;
+    let (strong_king, weak_king) = (board.king_of(strong)?, board.king_of(!strong)?);
+
+    let flip = |square: Square| {
+        let square = if strong.is_white() {square} else {square.flip_vertical()};
+        if pawn.file() > File::D {square.flip_horizontal()} else {square}
+    };
+    let entry = Entry { strong_to_move: turn == strong, strong_king: flip(strong_king), weak_king: flip(weak_king), pawn: flip(pawn) };
+    if entry.pawn.rank() == Rank::First || entry.pawn.rank() == Rank::Eighth {
+        return None;
+    }
+
+    let index = entry.index();
+    Some(WINS.get_or_init(generate)[index / 64] & 1 << (index % 64) != 0)
+}
+
+/// The evaluation of a king and pawn against king ending from white's point of view, which is 0 if it is
+/// drawn and `board_evaluation` with a bonus for the side with the pawn if it is won.
+pub fn evaluate(board: &Board, turn: Color, board_evaluation: i32) -> Option<i32> {
+    let wins = probe(board, turn)?;
+    if !wins {
+        return Some(0);
+    }
+    let strong_is_white = (board.pawns() & board.white()).any();
+    Some(board_evaluation + if strong_is_white {WIN_BONUS} else {-WIN_BONUS})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_fen(fen: &str, turn: Color) -> Option<bool> {
+        probe(&Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid"), turn)
+    }
+
+    #[test]
+    //Every position should index back to itself
+    fn indexing() {
+        for index in [0, 1, 12345, POSITIONS - 1] {
+            assert!(Entry::from_index(index).index() == index);
+        }
+    }
+
+    #[test]
+    //Textbook endings should be solved the same way for either colour and on either wing
+    fn textbook_endings() {
+        //A king in front of its pawn on the sixth rank wins whoever is to move
+        for turn in Color::ALL {
+            assert!(probe_fen("4k3/8/4K3/8/4P3/8/8/8", turn) == Some(true));
+            assert!(probe_fen("8/8/8/4p3/8/4k3/8/4K3", turn) == Some(true));
+            assert!(probe_fen("3k4/8/3K4/8/3P4/8/8/8", turn) == Some(true));
+        }
+        //The defending king in the corner in front of a rook pawn can't be driven out
+        for turn in Color::ALL {
+            assert!(probe_fen("k7/8/8/8/PK6/8/8/8", turn) == Some(false));
+            assert!(probe_fen("7k/8/8/8/6KP/8/8/8", turn) == Some(false));
+        }
+        //Opposition decides a king in front of its pawn, unless the king already stands on a key square
+        assert!(probe_fen("8/4k3/8/4K3/4P3/8/8/8", Color::White) == Some(false));
+        assert!(probe_fen("8/4k3/8/4K3/4P3/8/8/8", Color::Black) == Some(true));
+        assert!(probe_fen("4k3/8/8/4K3/8/4P3/8/8", Color::White) == Some(true));
+        //Pushing the pawn to the sixth rank ahead of the king only leads to stalemate
+        assert!(probe_fen("4k3/8/4P3/4K3/8/8/8/8", Color::Black) == Some(false));
+        //The pawn is lost when the defending king is next to it and it isn't guarded
+        assert!(probe_fen("8/8/8/8/3kP3/8/8/7K", Color::Black) == Some(false));
+
+        assert!(probe_fen("4k3/8/8/8/4P3/8/8/4K2N", Color::White).is_none());
+        assert!(probe_fen("4k3/8/8/8/8/8/8/4K3", Color::White).is_none());
+    }
+}
@@ -3,23 +3,145 @@
 //! `hodgey_chess_engine` is a very simple chess engine for [hodgeybot](https://github.com/Jixen124/hodgeybot).
 //! hodgeybot can also be found [here](https://lichess.org/@/Hodgeybot) on lichess.
 //! This requires using the [shakmaty crate](https://crates.io/crates/shakmaty) for handling chess games.
+//!
+//! With the `tracing` feature each search emits [tracing](https://crates.io/crates/tracing) events: a summary of
+//! every search at the info level, and its time budget, iterations, time decisions and transposition table use
+//! at the debug level.
+//!
+//! With the `serde` feature search limits and results, [`EngineOptions`], and the evaluation's
+//! [`tuning::PieceSquareTables`] can be serialized. With the `ffi` feature the engine can be called from C,
+//! see [`ffi`], and with the `python` feature from Python, see [`python`].
+//! With the `tokio` feature searches can be awaited from async code, see [`Engine::search_async`].
+//!
+//! The library builds for wasm32-unknown-unknown with `cargo build --lib --target wasm32-unknown-unknown`.
+//! There the UCI loop, book files and helper threads are left out, and time limits need a [`clock::Clock`]
+//! from the host, see [`Engine::set_clock`].
 
-use std::time::{Duration, Instant};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use shakmaty::{zobrist::{Zobrist64, ZobristHash}, Chess, Move, Outcome, Position};
-use evaluation::{evaluate_board, move_score, capture_score};
+use shakmaty::{zobrist::{Zobrist64, ZobristHash}, CastlingMode, Chess, Color, EnPassantMode, FromSetup, Move, Outcome, Position, PositionErrorKinds};
+use shakmaty::fen::{Fen, ParseFenError};
+use shakmaty::san::SanPlus;
+use eval_cache::EvalCache;
+use evaluation::{move_score, capture_score};
+use evaluator::{evaluate_board_detailed_with_tables, EvalParams, Evaluator, PieceSquareEvaluator};
+use experience::Experience;
+use node_position::NodePosition;
+use pawn_table::PawnTable;
+use personality::Personality;
+use book::Book;
+use clock::{default_clock, Clock};
+use random::XorShift64;
+use repertoire::Repertoire;
+use repetition::Repetitions;
+use root_moves::{RootMove, RootMoves};
+use search_params::SearchParams;
+use search_speed::{SearchSpeed, predict_iteration_nodes};
+use strength::{elo_nodes, pick_move, skill_depth, MAX_SKILL_LEVEL, SKILL_MULTI_PV};
+use time_management::{TimeBudget, TimeManager, DEFAULT_MOVE_OVERHEAD};
+#[cfg(feature = "trace")]
+use trace::{SearchTrace, Tracer};
+use tuning::PieceSquareTables;
+use variant::SearchPosition;
 
+pub mod analysis;
+#[cfg(feature = "tokio")]
+mod async_search;
+mod bench;
+pub mod book;
+pub mod clock;
+pub mod datagen;
+pub mod dataset;
+pub mod engine_match;
+pub mod endgame;
+mod eval_cache;
 mod evaluation;
+pub mod evaluator;
+pub mod experience;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod kpk;
+pub mod mate_search;
+mod node_position;
+mod pawn_table;
+pub mod perft;
+pub mod personality;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "pgn")]
+pub mod pgn;
 mod piece_square_tables;
+pub mod pool;
+mod random;
+pub mod repertoire;
+mod repetition;
+mod root_moves;
+pub mod search_params;
+mod search_speed;
+pub mod selfplay;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod spsa;
+pub mod strength;
 mod test_fens;
+pub mod time_management;
+#[cfg(feature = "trace")]
+pub mod trace;
+pub mod tuning;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod uci;
+pub mod variant;
+pub mod wdl;
 
 const INFINITY: i32 = i32::MAX;
 const NEG_INFINITY: i32 = -INFINITY;
 const REALLY_BIG_CHECKMATE_NUMBER: i32 = 100_000_000;
-const TRANSPOSITION_TABLE_LENGTH: usize = 1024 * 1024 * 8;
+//Being checkmated at ply n scores n - CHECKMATE_SCORE, so any mate is still worth more than REALLY_BIG_CHECKMATE_NUMBER
+const CHECKMATE_SCORE: i32 = REALLY_BIG_CHECKMATE_NUMBER + 1_000;
+const TRANSPOSITION_TABLE_LENGTH: usize = 1024 * 1024 * 16;
 const TABLE_INDEX_MASK: usize = TRANSPOSITION_TABLE_LENGTH - 1;
+//Saved transposition tables start with this, then the table length, then an index and packed entry for each filled entry
+const TABLE_FILE_MAGIC: &[u8; 8] = b"HODGEYTT";
+const TABLE_FILE_ENTRY_LENGTH: usize = 12;
+//After the iteration at this depth, a best move that every other move scores at least the easy move margin below
+//is played after a fraction of the time
+const EASY_MOVE_DEPTH: u16 = 6;
+const MAX_PV_LENGTH: usize = 32;
+//Root moves searched exactly when looking for candidate moves
+const CANDIDATE_MOVES: usize = 4;
+//Quiet moves within this many plies of the horizon are skipped if the static evaluation plus
+//the futility margin per ply of depth can't reach alpha
+const FUTILITY_DEPTH: u16 = 3;
+//Within this many plies of the horizon, a static evaluation beating beta by the reverse futility margin
+//per ply of depth is trusted to hold up without searching
+const REVERSE_FUTILITY_DEPTH: u16 = 3;
+//Within this many plies of the horizon, quiet moves are skipped once 3 + depth * depth moves have been searched
+const LATE_MOVE_PRUNING_DEPTH: u16 = 3;
+//Nodes at least this deep without a move from the table are searched a ply shallower
+const INTERNAL_ITERATIVE_REDUCTION_DEPTH: u16 = 4;
+//Lines are cut off at this ply so pathological positions can't overflow the stack
+const MAX_PLY: u16 = 128;
+//How many nodes are searched between checks of the clock against the hard time limit
+const DEADLINE_CHECK_NODES: u64 = 1024;
+const INFO_INTERVAL: Duration = Duration::from_secs(1);
+//Root moves are only reported once a search has run this long, so short searches don't flood the GUI
+const CURRENT_MOVE_DELAY: Duration = Duration::from_secs(1);
+//Quiescence only looks for stalemate when the side to move is at least this far behind and has only its king
+//and pawns, which is when the side ahead is likely to take away its last moves without noticing
+const STALEMATE_CHECK_MARGIN: i32 = 500;
+const FIFTY_MOVE_RULE_HALFMOVES: u32 = 100;
+const SEVENTY_FIVE_MOVE_RULE_HALFMOVES: u32 = 150;
+
+//Scores are stored in 16 bits, with mates stored by how far the score is from CHECKMATE_SCORE so they fit
+const TABLE_MATE_SCORE: i16 = i16::MAX;
+const MAX_TABLE_SCORE: i32 = TABLE_MATE_SCORE as i32 - (CHECKMATE_SCORE - REALLY_BIG_CHECKMATE_NUMBER) - 1;
 
 #[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
 enum TranspositionTableFlag {
     None,
     Exact,
@@ -27,11 +149,13 @@ enum TranspositionTableFlag {
     Upperbound
 }
 
+/// A transposition table entry, which the table packs into 8 bytes so it holds more positions.
 #[derive(Clone, Copy)]
 struct TranspositionTableData {
-    hash: u64,
-    score: i32,
-    depth: u16,
+    /// The top bits of the position's hash, the low bits already chose the entry.
+    key: u16,
+    score: i16,
+    depth: u8,
     best_move_index: u8,
     flag: TranspositionTableFlag
 }
@@ -39,284 +163,2950 @@ struct TranspositionTableData {
 impl TranspositionTableData {
     const fn new() -> TranspositionTableData {
         TranspositionTableData {
-            hash: 0,
+            key: 0,
             score: 0,
             depth: 0,
             best_move_index: 0,
             flag: TranspositionTableFlag::None
         }
     }
+
+    /// An entry for the position with `hash`. Scores are from [`score_to_table`].
+    fn store(hash: u64, score: i32, depth: u16, best_move_index: usize, flag: TranspositionTableFlag) -> TranspositionTableData {
+        let score = if score >= REALLY_BIG_CHECKMATE_NUMBER {
+            TABLE_MATE_SCORE as i32 - (CHECKMATE_SCORE - score)
+        }
+        else if score <= -REALLY_BIG_CHECKMATE_NUMBER {
+            -(TABLE_MATE_SCORE as i32 - (CHECKMATE_SCORE + score))
+        }
+        else {
+            score.clamp(-MAX_TABLE_SCORE, MAX_TABLE_SCORE)
+        };
+
+        TranspositionTableData {
+            key: table_key(hash),
+            score: score as i16,
+            depth: depth.min(u8::MAX as u16) as u8,
+            best_move_index: best_move_index as u8,
+            flag
+        }
+    }
+
+    /// Whether this entry was stored for the position with `hash`, barring the odd collision.
+    fn matches(&self, hash: u64) -> bool {
+        self.flag != TranspositionTableFlag::None && self.key == table_key(hash)
+    }
+
+    fn score(&self) -> i32 {
+        let score = self.score as i32;
+        if score > MAX_TABLE_SCORE {
+            CHECKMATE_SCORE - (TABLE_MATE_SCORE as i32 - score)
+        }
+        else if score < -MAX_TABLE_SCORE {
+            -CHECKMATE_SCORE + (TABLE_MATE_SCORE as i32 + score)
+        }
+        else {
+            score
+        }
+    }
+
+    fn depth(&self) -> u16 {
+        self.depth as u16
+    }
+
+    fn pack(self) -> u64 {
+        self.key as u64 | (self.score as u16 as u64) << 16 | (self.depth as u64) << 32 | (self.best_move_index as u64) << 40 | (self.flag as u64) << 48
+    }
+
+    fn unpack(packed: u64) -> TranspositionTableData {
+        TranspositionTableData {
+            key: packed as u16,
+            score: (packed >> 16) as u16 as i16,
+            depth: (packed >> 32) as u8,
+            best_move_index: (packed >> 40) as u8,
+            flag: match (packed >> 48) as u8 {
+                1 => TranspositionTableFlag::Exact,
+                2 => TranspositionTableFlag::Lowerbound,
+                3 => TranspositionTableFlag::Upperbound,
+                _ => TranspositionTableFlag::None
+            }
+        }
+    }
 }
 
-/// Finds the best move for a given depth.
-pub fn find_best_move_with_depth(chess: &Chess, max_depth: u16, previously_seen_hashes: &mut Vec<u64>) -> Move {
-    let mut transposition_table: Vec<TranspositionTableData> = vec![TranspositionTableData::new(); TRANSPOSITION_TABLE_LENGTH];
-    
-    let mut moves = chess.legal_moves();
-    
-    if moves.len() == 1 {
-        return moves[0].clone();
+/// The transposition table, shared by every thread of a search. Each entry is a single atomic,
+/// so one thread never sees half of another thread's write.
+#[derive(Default)]
+struct TranspositionTable {
+    entries: Vec<AtomicU64>
+}
+
+impl TranspositionTable {
+    fn new() -> TranspositionTable {
+        TranspositionTable {
+            entries: (0..TRANSPOSITION_TABLE_LENGTH).map(|_| AtomicU64::new(TranspositionTableData::new().pack())).collect()
+        }
     }
 
-    let mut depth = 2;
+    fn clear(&self) {
+        for entry in &self.entries {
+            entry.store(TranspositionTableData::new().pack(), Ordering::Relaxed);
+        }
+    }
 
-    while depth < max_depth {
-        let mut best_score = NEG_INFINITY;
+    fn get(&self, index: usize) -> TranspositionTableData {
+        TranspositionTableData::unpack(self.entries[index].load(Ordering::Relaxed))
+    }
 
-        for (index, m) in moves.clone().iter().enumerate() {
-            let mut new_chess = chess.clone();
-            new_chess.play_unchecked(&m);
+    fn set(&self, index: usize, entry: TranspositionTableData) {
+        self.entries[index].store(entry.pack(), Ordering::Relaxed);
+    }
 
-            let score = -nega_max(&new_chess, depth, NEG_INFINITY, -best_score,
-                                        &mut transposition_table, previously_seen_hashes);
-            if score > best_score {
-                best_score = score;
-                for i in (0..index).rev() {
-                    moves.swap(i, i+1);
-                }
+    /// Writes out the filled entries, each as its index and packed entry, after a header with the table length.
+    fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let empty = TranspositionTableData::new().pack();
+        writer.write_all(TABLE_FILE_MAGIC)?;
+        writer.write_all(&(TRANSPOSITION_TABLE_LENGTH as u64).to_le_bytes())?;
+        for (index, entry) in self.entries.iter().enumerate() {
+            let entry = entry.load(Ordering::Relaxed);
+            if entry != empty {
+                writer.write_all(&(index as u32).to_le_bytes())?;
+                writer.write_all(&entry.to_le_bytes())?;
             }
         }
-        
-        //This is in outer loop to make sure that faster checkmates are selected
-        //Possibly not needed now with iterative deepening?
-        //First move that gives me a checkmate possibly good enough?
-        if best_score.abs() >= REALLY_BIG_CHECKMATE_NUMBER {
-            break;
+        writer.flush()
+    }
+
+    /// Reads a table written by [`TranspositionTable::write_to`].
+    fn read_from(mut reader: impl Read) -> io::Result<TranspositionTable> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != TABLE_FILE_MAGIC {
+            return Err(invalid("not a transposition table file"));
+        }
+        if u64::from_le_bytes(header[8..].try_into().expect("slice is 8 bytes")) != TRANSPOSITION_TABLE_LENGTH as u64 {
+            return Err(invalid("transposition table is a different size"));
         }
 
-        depth += 2;
+        let table = TranspositionTable::new();
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if !bytes.len().is_multiple_of(TABLE_FILE_ENTRY_LENGTH) {
+            return Err(invalid("transposition table file is cut off"));
+        }
+        for entry in bytes.chunks_exact(TABLE_FILE_ENTRY_LENGTH) {
+            let index = u32::from_le_bytes(entry[..4].try_into().expect("slice is 4 bytes")) as usize;
+            let packed = u64::from_le_bytes(entry[4..].try_into().expect("slice is 8 bytes"));
+            table.entries.get(index).ok_or_else(|| invalid("transposition table entry out of range"))?.store(packed, Ordering::Relaxed);
+        }
+        Ok(table)
     }
+}
 
-    return moves[0].clone();
+fn table_key(hash: u64) -> u16 {
+    (hash >> 48) as u16
 }
 
-/// Finds the best move searching for a given minimum search time.
-/// 
-/// # WARNING
-/// Currently goes well over the given time.
-pub fn find_best_move_with_time(chess: &Chess, min_search_time: Duration, previously_seen_hashes: &mut Vec<u64>) -> Move {
-    let start_time = Instant::now();
+/// Bounds on a search. Any combination can be set and the search stops at whichever is reached first.
+/// With no limits set the search only stops once it finds a forced mate.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchLimits {
+    /// The deepest iteration to search, in plies.
+    pub max_depth: Option<u16>,
+    /// The search is stopped as soon as this many nodes have been searched.
+    pub max_nodes: Option<u64>,
+    /// The search is stopped once this much time has passed.
+    pub move_time: Option<Duration>,
+    /// Time left on the clocks, used to budget the search when playing a game.
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    /// Moves until the next time control, `None` if the rest of the game is played on the current clock.
+    pub moves_to_go: Option<u32>,
+    /// Ignores every time limit, including the engine's max move time.
+    pub infinite: bool
+}
 
-    let mut transposition_table: Vec<TranspositionTableData> = vec![TranspositionTableData::new(); TRANSPOSITION_TABLE_LENGTH];
-    
-    let mut moves = chess.legal_moves();
-    
-    if moves.len() == 1 {
-        return moves[0].clone();
+/// Everything a search found out about a position.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchResult {
+    /// `None` if the game is already over by checkmate or stalemate.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::option_move"))]
+    pub best_move: Option<Move>,
+    /// Score from the point of view of the side to move, in the search's own units. Mates are scored beyond
+    /// `REALLY_BIG_CHECKMATE_NUMBER`, see [`SearchResult::mate_in`], and [`SearchResult::normalized_score`]
+    /// gives the score on the scale other engines report.
+    pub score: i32,
+    /// Depth in plies of the last fully completed iteration.
+    pub depth: u16,
+    /// Deepest ply reached by any line, including quiescence search.
+    pub seldepth: u16,
+    /// The expected continuation, starting with the best move.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::moves"))]
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    /// How full the transposition table is in permille.
+    pub hashfull: usize,
+    pub time: Duration,
+    pub stats: SearchStats
+}
+
+/// Counts of what happened during a search, for judging how well move ordering and pruning work.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchStats {
+    /// Nodes of the main search where a move reached beta.
+    pub beta_cutoffs: u64,
+    /// Beta cutoffs made by the first move searched.
+    pub first_move_cutoffs: u64,
+    pub table_probes: u64,
+    /// Probes that found an entry for the position, whether or not it was deep enough to use.
+    pub table_hits: u64,
+    pub quiescence_nodes: u64,
+    /// Nodes cut off by reverse futility pruning without searching any moves.
+    pub reverse_futility_cutoffs: u64,
+    /// Quiet moves skipped by futility pruning or late move pruning.
+    pub pruned_moves: u64,
+    /// Root searches repeated with a full window after the aspiration window failed.
+    pub re_searches: u64,
+    /// Static evaluations asked for, and how many of them were found in the evaluation cache.
+    pub eval_probes: u64,
+    pub eval_cache_hits: u64
+}
+
+impl SearchStats {
+    /// The share of beta cutoffs made by the first move, higher with better move ordering.
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        ratio(self.first_move_cutoffs, self.beta_cutoffs)
+    }
+
+    pub fn table_hit_rate(&self) -> f64 {
+        ratio(self.table_hits, self.table_probes)
     }
 
-    let mut depth = 2;
+    pub fn eval_cache_hit_rate(&self) -> f64 {
+        ratio(self.eval_cache_hits, self.eval_probes)
+    }
 
-    while Instant::now() - start_time < min_search_time {
-        let mut best_score = NEG_INFINITY;
+    /// Adds in the counts of another thread of the same search.
+    fn add(&mut self, other: &SearchStats) {
+        self.beta_cutoffs += other.beta_cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+        self.table_probes += other.table_probes;
+        self.table_hits += other.table_hits;
+        self.quiescence_nodes += other.quiescence_nodes;
+        self.reverse_futility_cutoffs += other.reverse_futility_cutoffs;
+        self.pruned_moves += other.pruned_moves;
+        self.re_searches += other.re_searches;
+        self.eval_probes += other.eval_probes;
+        self.eval_cache_hits += other.eval_cache_hits;
+    }
 
-        for (index, m) in moves.clone().iter().enumerate() {
-            if Instant::now() - start_time >= min_search_time {
-                break;
-            }
+    /// The share of `nodes` that were searched by quiescence search.
+    pub fn quiescence_ratio(&self, nodes: u64) -> f64 {
+        ratio(self.quiescence_nodes, nodes)
+    }
+}
 
-            let mut new_chess = chess.clone();
-            new_chess.play_unchecked(&m);
+fn ratio(count: u64, total: u64) -> f64 {
+    if total == 0 {0.0} else {count as f64 / total as f64}
+}
 
-            let score = -nega_max(&new_chess, depth, NEG_INFINITY, -best_score,
-                                        &mut transposition_table, previously_seen_hashes);
-            if score > best_score {
-                best_score = score;
-                for i in (0..index).rev() {
-                    moves.swap(i, i+1);
-                }
+/// The totals of a bench run, see [`Engine::bench`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchReport {
+    pub nodes: u64,
+    pub time: Duration,
+    /// See [`Engine::bench_signature`].
+    pub signature: u64
+}
+
+impl BenchReport {
+    /// Nodes searched per second.
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(f64::EPSILON)) as u64
+    }
+}
+
+//Prints the report in the format testing frameworks read
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Total time (ms): {}", self.time.as_millis())?;
+        writeln!(f, "Nodes searched: {}", self.nodes)?;
+        writeln!(f, "Nodes/second: {}", self.nps())?;
+        writeln!(f, "Signature: {:#x}", self.signature)
+    }
+}
+
+/// A reasonable move found by [`Engine::find_candidate_moves`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CandidateMove {
+    pub mv: Move,
+    /// Score from the point of view of the side to move, like [`SearchResult::score`].
+    pub score: i32,
+    /// The expected continuation, starting with this move.
+    pub pv: Vec<Move>,
+    /// The chance of playing this move, the probabilities of all candidates add up to 1.
+    pub probability: f64
+}
+
+/// A legal move scored by [`Engine::evaluate_all_root_moves`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoredMove {
+    pub mv: Move,
+    /// Score from the point of view of the side to move, like [`SearchResult::score`].
+    pub score: i32,
+    /// The expected continuation, starting with this move.
+    pub pv: Vec<Move>
+}
+
+/// Why a position given as a FEN couldn't be searched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineError {
+    /// The FEN couldn't be read.
+    InvalidFen(ParseFenError),
+    /// The FEN was read but isn't a legal position, for the reasons given.
+    InvalidPosition(PositionErrorKinds)
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::InvalidFen(error) => write!(f, "{}", error),
+            EngineError::InvalidPosition(kinds) => {
+                let reasons: Vec<String> = kinds.iter_names().map(|(name, _)| name.to_lowercase().replace('_', " ")).collect();
+                write!(f, "illegal position: {}", reasons.join(", "))
             }
         }
-        
-        //This is in outer loop to make sure that faster checkmates are selected
-        //Possibly not needed now with iterative deepening?
-        //First move that gives me a checkmate possibly good enough?
-        if best_score.abs() >= REALLY_BIG_CHECKMATE_NUMBER {
-            break;
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::InvalidFen(error) => Some(error),
+            EngineError::InvalidPosition(_) => None
         }
+    }
+}
 
-        depth += 2;
+/// Progress reported to the engine's progress callback while it searches.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum SearchEvent {
+    /// An iteration of iterative deepening finished, holding what the search knows so far.
+    Iteration(SearchResult),
+    /// The search started on a root move in a search that has been running for a while.
+    /// `number` counts the root moves from 1 in the order the iteration at `depth` searches them.
+    CurrentMove { depth: u16, mv: Move, number: usize },
+    /// A UCI `info` line the engine reports along the way, such as the time it plans to use or how many
+    /// nodes it has searched during a long iteration. Dropped when there is no progress callback.
+    Info(String)
+}
+
+/// Sends `line` to the progress callback as a [`SearchEvent::Info`]. Without a callback nobody is listening,
+/// and library users shouldn't find UCI text on their stdout, so the line is dropped.
+fn report_line(progress_callback: &mut Option<ProgressCallback>, line: String) {
+    if let Some(progress_callback) = progress_callback {
+        progress_callback(&SearchEvent::Info(line));
+    }
+}
+
+impl SearchResult {
+    /// Moves until mate if the score is a mate, negative when the side to move is getting mated.
+    pub fn mate_in(&self) -> Option<i32> {
+        mate_in(self.score)
+    }
+
+    /// The score in centipawns where 100 means the side to move wins about half its games, the scale other
+    /// engines report scores on. Mates are left as they are.
+    pub fn normalized_score(&self) -> i32 {
+        wdl::normalize_score(self.score)
+    }
+
+    /// Estimated chances of winning, drawing and losing for the side to move, going by the score.
+    pub fn wdl(&self) -> wdl::Wdl {
+        wdl::Wdl::from_score(self.score)
+    }
+
+    /// The best move in standard algebraic notation, such as "Nf3" or "Qxf7#", given the searched `position`.
+    pub fn best_move_san<P: Position + Clone>(&self, position: &P) -> Option<SanPlus> {
+        self.best_move.as_ref().map(|best_move| SanPlus::from_move(position.clone(), best_move))
     }
 
-    return moves[0].clone();
+    /// The principal variation in standard algebraic notation, played out from the searched `position`.
+    pub fn pv_san<P: Position + Clone>(&self, position: &P) -> Vec<SanPlus> {
+        let mut position = position.clone();
+        self.pv.iter().map(|m| SanPlus::from_move_and_play_unchecked(&mut position, m)).collect()
+    }
 }
 
-fn nega_max(chess: &Chess, depth: u16, mut alpha: i32, mut beta: i32,
-            transposition_table: &mut Vec<TranspositionTableData>, previously_seen_hashes: &mut Vec<u64>) -> i32 {
-    
-    if let Some(outcome) = chess.outcome() {
-        return match outcome {
-            Outcome::Draw => 0,
-            _ => -REALLY_BIG_CHECKMATE_NUMBER - depth as i32
-        };
+/// Moves until mate for a score from a search's root, see [`SearchResult::mate_in`].
+pub(crate) fn mate_in(score: i32) -> Option<i32> {
+    if score >= REALLY_BIG_CHECKMATE_NUMBER {
+        Some((CHECKMATE_SCORE - score + 1) / 2)
+    }
+    else if score <= -REALLY_BIG_CHECKMATE_NUMBER {
+        Some(-(CHECKMATE_SCORE + score) / 2)
     }
+    else {
+        None
+    }
+}
 
-    let hash: Zobrist64 = chess.zobrist_hash(shakmaty::EnPassantMode::Legal);
-    let hash = hash.0;
-    
-    //Engine will evaluate a draw if a single repetition occurs
-    if previously_seen_hashes.contains(&hash) {
-        // A draw is given zero score
-        return 0;
+/// Stops a running search from another thread.
+/// The stopped search still returns the best move it has found so far.
+#[derive(Clone, Debug)]
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>
+}
+
+impl SearchHandle {
+    /// Stops the engine's current search. Has no effect if the engine isn't searching.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
     }
 
-    if depth == 0 {
-        return quiescence_search(&chess, alpha, beta);
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
     }
+}
 
-    let original_alpha = alpha;
+/// The engine's settings gathered together, so they can be kept and applied at once.
+/// See the setters of [`Engine`] for what each does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EngineOptions {
+    pub max_move_time: Option<Duration>,
+    pub move_overhead: Duration,
+    pub contempt: i32,
+    pub skill_level: u8,
+    pub elo_limit: Option<u32>,
+    pub threads: usize,
+    pub deterministic: bool,
+    pub personality: Personality
+}
 
-    let table_index = hash as usize & TABLE_INDEX_MASK;
-    if transposition_table[table_index].hash == hash && transposition_table[table_index].depth >= depth {
-        if transposition_table[table_index].flag == TranspositionTableFlag::Exact {
-            return transposition_table[table_index].score;
-        }
-        else if transposition_table[table_index].flag == TranspositionTableFlag::Lowerbound {
-            alpha = alpha.max(transposition_table[table_index].score);
-        }
-        else if transposition_table[table_index].flag == TranspositionTableFlag::Upperbound {
-            beta = beta.min(transposition_table[table_index].score);
-        }
-        
-        if alpha >= beta {
-            return transposition_table[table_index].score;
+impl Default for EngineOptions {
+    fn default() -> EngineOptions {
+        EngineOptions {
+            max_move_time: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            contempt: 0,
+            skill_level: MAX_SKILL_LEVEL,
+            elo_limit: None,
+            threads: 1,
+            deterministic: false,
+            personality: Personality::Balanced
         }
     }
+}
 
-    previously_seen_hashes.push(hash);
+/// Receives the engine's [`SearchEvent`]s, see [`Engine::set_progress_callback`].
+pub type ProgressCallback = Box<dyn FnMut(&SearchEvent) + Send>;
 
-    let mut value = NEG_INFINITY;
-    let mut best_move_index = 0;
+/// Holds search state that is kept between moves of the same game.
+/// Searches positions of type `P`, which is standard chess unless a variant is chosen, and evaluates
+/// them with `E`, the engine's own evaluation unless another is given.
+pub struct Engine<P: SearchPosition = Chess, E: Evaluator<P> = PieceSquareEvaluator> {
+    evaluator: Arc<E>,
+    previous_search: Option<PreviousSearch<P>>,
+    /// The last search's transposition table, kept so the next search doesn't have to allocate one.
+    transposition_table: Arc<TranspositionTable>,
+    /// Whether the next search starts from the table as it is instead of clearing it, after loading one.
+    keep_table: bool,
+    max_move_time: Option<Duration>,
+    move_overhead: Option<Duration>,
+    search_speed: SearchSpeed,
+    debug: bool,
+    report_info: bool,
+    contempt: i32,
+    personality: Personality,
+    eval_params: EvalParams,
+    /// Tables replacing the engine's own piece square tables, see [`Engine::set_piece_square_tables`].
+    piece_square_tables: Option<Arc<PieceSquareTables>>,
+    search_params: SearchParams,
+    skill: Option<(u8, XorShift64)>,
+    elo_limit: Option<u32>,
+    /// How many root moves are searched for an exact score, see [`SearchContext`].
+    multi_pv: usize,
+    /// The root moves searches are restricted to, all of them if empty.
+    search_moves: Vec<Move>,
+    /// Threads searching root moves alongside the calling thread, see [`Engine::set_threads`].
+    helper_threads: usize,
+    stop: Arc<AtomicBool>,
+    progress_callback: Option<ProgressCallback>,
+    book: Option<(Book, XorShift64)>,
+    experience: Option<Experience>,
+    repertoire: Option<Repertoire>,
+    clock: Arc<dyn Clock>,
+    /// Whether searches are repeatable, see [`Engine::set_deterministic`].
+    deterministic: bool,
+    /// The deepest ply recorded by the tracer, `None` when not tracing.
+    #[cfg(feature = "trace")]
+    trace_depth: Option<u16>,
+    #[cfg(feature = "trace")]
+    trace: Option<SearchTrace>
+}
 
-    let mut moves = chess.legal_moves();
-    moves.sort_unstable_by_key(|m| move_score(m));
+/// The root position and root moves of the last completed search.
+struct PreviousSearch<P> {
+    root: P,
+    root_moves: RootMoves,
+    /// Depth of the search's last completed iteration.
+    depth: u16
+}
 
+/// Information carried over from the previous search when the game continued along its tree.
+#[derive(Default)]
+struct SearchSeed {
+    /// The previous search's score for the position, searched `depth` plies deep.
+    score: Option<i32>,
+    depth: u16,
+    first_move: Option<Move>
+}
 
-    if transposition_table[table_index].hash == hash && (transposition_table[table_index].best_move_index as usize) < moves.len() {
-        //Search best move first if there is an entry in the transposition table
-        let mut new_chess = chess.clone();
-        best_move_index = transposition_table[table_index].best_move_index as usize;
-        new_chess.play_unchecked(&moves[best_move_index]);
-        let score = -nega_max(&new_chess, depth - 1, -beta, -alpha,
-                                    transposition_table, previously_seen_hashes);
-        value = value.max(score);
-        alpha = alpha.max(value);
+impl Engine {
+    /// A hash of the node counts and best moves from searching a fixed set of positions to a fixed depth.
+    /// Any change to search behaviour changes it, while pure speedups leave it alone.
+    pub fn bench_signature() -> u64 {
+        Engine::bench().signature
     }
-    
-    if !(alpha >= beta) {
-        for (index, m) in moves.iter().enumerate() {
-            if transposition_table[table_index].hash == hash && index == transposition_table[table_index].best_move_index as usize {
-                continue;
-            }
 
-            let mut new_chess = chess.clone();
-            new_chess.play_unchecked(m);
-            let score = -nega_max(&new_chess, depth - 1, -beta, -alpha,
-                                        transposition_table, previously_seen_hashes);
-            if score > value {
-                value = score;
-                best_move_index = index;
-                alpha = alpha.max(value);
-                if alpha >= beta {
-                    break;
-                }
-            }
+    /// Searches a fixed set of positions to a fixed depth with a fresh engine, for checking that a change
+    /// leaves the search node for node the same and for measuring its speed.
+    pub fn bench() -> BenchReport {
+        let entries = bench::run_bench();
+        BenchReport {
+            nodes: entries.iter().map(|entry| entry.nodes).sum(),
+            time: entries.iter().map(|entry| entry.time).sum(),
+            signature: bench::signature(&entries)
         }
     }
+}
 
-    previously_seen_hashes.pop();
+impl<P: SearchPosition> Engine<P> {
+    pub fn new() -> Engine<P> {
+        Engine::default()
+    }
+}
 
-    if transposition_table[table_index].depth < depth {
-        transposition_table[table_index].hash = hash;
-        transposition_table[table_index].score = value;
-        transposition_table[table_index].depth = depth;
-        transposition_table[table_index].best_move_index = best_move_index as u8;
-        
-        transposition_table[table_index].flag = if value <= original_alpha {
-            TranspositionTableFlag::Upperbound
-        }
-        else if value >= beta {
-            TranspositionTableFlag::Lowerbound
-        }
-        else {
-            TranspositionTableFlag::Exact
-        }
+impl<P: SearchPosition, E: Evaluator<P> + Default> Default for Engine<P, E> {
+    fn default() -> Engine<P, E> {
+        Engine::with_evaluator(E::default())
     }
-    
-    value
 }
 
-fn quiescence_search(chess: &Chess, mut alpha: i32, beta: i32) -> i32 {
-    let stand_pat = evaluate_board(chess.board()) * if chess.turn().is_white() {1} else {-1};
-    
-    if stand_pat >= beta {
-        return beta;
+impl<P: SearchPosition, E: Evaluator<P>> Engine<P, E> {
+    /// An engine that evaluates positions with `evaluator` instead of its own evaluation.
+    pub fn with_evaluator(evaluator: E) -> Engine<P, E> {
+        Engine {
+            evaluator: Arc::new(evaluator),
+            previous_search: None,
+            transposition_table: Arc::default(),
+            keep_table: false,
+            max_move_time: None,
+            move_overhead: None,
+            search_speed: SearchSpeed::default(),
+            debug: false,
+            report_info: false,
+            contempt: 0,
+            personality: Personality::Balanced,
+            eval_params: EvalParams::DEFAULT,
+            piece_square_tables: None,
+            search_params: SearchParams::DEFAULT,
+            skill: None,
+            elo_limit: None,
+            multi_pv: 1,
+            search_moves: Vec::new(),
+            helper_threads: 0,
+            stop: Arc::default(),
+            progress_callback: None,
+            book: None,
+            experience: None,
+            repertoire: None,
+            clock: default_clock(),
+            deterministic: false,
+            #[cfg(feature = "trace")]
+            trace_depth: None,
+            #[cfg(feature = "trace")]
+            trace: None
+        }
     }
 
-    if alpha < stand_pat {
-        alpha = stand_pat;
+    /// The evaluation the search uses.
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
     }
-    
-    let mut capture_moves = chess.capture_moves();
-    capture_moves.sort_unstable_by_key(|m| capture_score(m));
 
-    for m in &capture_moves {
-        let mut new_chess = chess.clone();
-        new_chess.play_unchecked(m);
-        let score = -quiescence_search(&new_chess, -beta, -alpha);
+    /// Caps the time spent on any single move, no matter what limits the search is given.
+    /// The cap is enforced even in the middle of searching a root move.
+    pub fn set_max_move_time(&mut self, max_move_time: Option<Duration>) {
+        self.max_move_time = max_move_time;
+    }
 
-        if score >= beta {
-            return beta;
-        }
+    /// Sets how much time is lost on every move outside of the search, such as to network lag.
+    /// It is left on the clock when budgeting time. Defaults to [`time_management::DEFAULT_MOVE_OVERHEAD`].
+    pub fn set_move_overhead(&mut self, move_overhead: Duration) {
+        self.move_overhead = Some(move_overhead);
+    }
+
+    /// Scores draws `contempt` centipawns below equal for the side the engine is searching for, and the
+    /// same amount above for its opponent, so a positive contempt steers away from draws against weaker opposition.
+    pub fn set_contempt(&mut self, contempt: i32) {
+        self.contempt = contempt;
+    }
+
+    /// Plays in the style of `personality`, which adds to the contempt and replaces the eval params with its own.
+    pub fn set_personality(&mut self, personality: Personality) {
+        self.personality = personality;
+        self.eval_params = personality.eval_params();
+    }
 
-        if score > alpha {
-            alpha = score;
+    /// The numbers the engine's own evaluation is worked out with.
+    pub fn eval_params(&self) -> EvalParams {
+        self.eval_params
+    }
+
+    /// Evaluates with `eval_params` from the next search on. Evaluations other than the engine's own ignore them.
+    pub fn set_eval_params(&mut self, eval_params: EvalParams) {
+        self.eval_params = eval_params;
+    }
+
+    /// The margins and scales the search prunes and spends its time with.
+    pub fn search_params(&self) -> SearchParams {
+        self.search_params
+    }
+
+    /// Searches with `search_params` from the next search on.
+    pub fn set_search_params(&mut self, search_params: SearchParams) {
+        self.search_params = search_params;
+    }
+
+    /// Values the squares of the pieces with `tables` from the next search on, or with the engine's own tables
+    /// for `None`, so tuned tables can be tried without rebuilding. The board is then evaluated from scratch at
+    /// every node instead of being kept up to date move by move, which makes the search slower.
+    pub fn set_piece_square_tables(&mut self, tables: Option<PieceSquareTables>) {
+        self.piece_square_tables = tables.map(Arc::new);
+    }
+
+    /// Plays weaker below [`strength::MAX_SKILL_LEVEL`], searching less deeply and sometimes
+    /// settling for a move somewhat worse than the best. Levels above the maximum play at full strength.
+    pub fn set_skill_level(&mut self, skill_level: u8) {
+        self.skill = (skill_level < MAX_SKILL_LEVEL).then(|| (skill_level, XorShift64::from_time()));
+    }
+
+    /// Caps the nodes searched to play at about `elo`, clamped to between [`strength::MIN_ELO`]
+    /// and [`strength::MAX_ELO`]. `None` plays at full strength.
+    pub fn set_elo_limit(&mut self, elo: Option<u32>) {
+        self.elo_limit = elo;
+    }
+
+    /// Searches with `threads` threads, which share out the root moves after the first one and share
+    /// the transposition table. Searches with a node limit or more than one PV stay on a single thread,
+    /// since they need the root moves searched in order. On wasm32-unknown-unknown, which has no threads,
+    /// searches always stay on the calling thread.
+    pub fn set_threads(&mut self, threads: usize) {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        {
+            self.helper_threads = threads.saturating_sub(1);
         }
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        let _ = threads;
     }
-    
-    return alpha;
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use shakmaty::fen::Fen;
-    use shakmaty::{CastlingMode, Chess, FromSetup};
+    /// Makes searches repeatable, so the same position, game history and limits always give the same
+    /// result on any machine. Time limits are spent as nodes at [`time_management::DETERMINISTIC_NODES_PER_SECOND`],
+    /// searches stay on one thread and don't carry anything over from earlier searches, and the random
+    /// choices of the book and skill level are seeded from the position.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
 
-    #[test]
-    //This is just to test performace, it asserts nothing
-    fn test_fens_time() {
-        for fen in test_fens::WIN_AT_CHESS {
-            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
-            let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
-            find_best_move_with_depth(&chess, 2, &mut Vec::new());
+    /// The engine's current settings.
+    pub fn options(&self) -> EngineOptions {
+        EngineOptions {
+            max_move_time: self.max_move_time,
+            move_overhead: self.move_overhead.unwrap_or(DEFAULT_MOVE_OVERHEAD),
+            contempt: self.contempt,
+            skill_level: self.skill.as_ref().map_or(MAX_SKILL_LEVEL, |&(skill_level, _)| skill_level),
+            elo_limit: self.elo_limit,
+            threads: self.helper_threads + 1,
+            deterministic: self.deterministic,
+            personality: self.personality
         }
     }
 
-    #[test]
-    //This is just to test performace, it asserts nothing
-    fn test_position_time() {
-        let setup = Fen::from_ascii("2rq1bk1/1b4pp/pn3n2/1p1Ppp2/1PP1P3/7P/3N1PP1/R2QRBK1 w - - 0 23".as_bytes()).expect("Fen should be valid").0;
-        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
-        find_best_move_with_depth(&chess, 8, &mut Vec::new());
+    /// Applies every setting in `options`, as if each setter were called.
+    pub fn set_options(&mut self, options: EngineOptions) {
+        self.set_max_move_time(options.max_move_time);
+        self.set_move_overhead(options.move_overhead);
+        self.set_contempt(options.contempt);
+        self.set_skill_level(options.skill_level);
+        self.set_elo_limit(options.elo_limit);
+        self.set_threads(options.threads);
+        self.set_deterministic(options.deterministic);
+        self.set_personality(options.personality);
     }
 
-    #[test]
-    //Checks the the program successfully solves the lasker position
-    fn lasker_position() {
-        let setup = Fen::from_ascii("8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - -".as_bytes()).expect("Fen should be valid").0;
-        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
-        assert!(find_best_move_with_depth(&chess, 20, &mut Vec::new()).to_string() == "Ka1-b1");
+    /// Reports `info string` lines with time forecasts to the progress callback while searching.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
     }
 
-    #[test]
-    //Makes sure both my methods agree on best move from a test position
-    //This might stop working if my searches become faster. It is kinda luck and hardware based.
-    fn time_and_depth_agree() {
-        let setup = Fen::from_ascii("2rq1bk1/1b4pp/pn3n2/1p1Ppp2/1PP1P3/7P/3N1PP1/R2QRBK1 w - - 0 23".as_bytes()).expect("Fen should be valid").0;
-        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
-        let m1 = find_best_move_with_depth(&chess, 8, &mut Vec::new());
-        let m2 = find_best_move_with_time(&chess, Duration::from_millis(500), &mut Vec::new());
-        assert!(m1 == m2);
+    /// Reports UCI `info` lines with the search's progress during long iterations to the progress callback, such as
+    /// how full the hash table is.
+    pub fn set_report_info(&mut self, report_info: bool) {
+        self.report_info = report_info;
+    }
+
+    /// Calls `progress_callback` with a [`SearchEvent`] after every completed iteration of a search,
+    /// and before each root move once a search has been running for a while.
+    pub fn set_progress_callback(&mut self, progress_callback: Option<ProgressCallback>) {
+        self.progress_callback = progress_callback;
+    }
+
+    /// Plays moves from `book` instead of searching while the game is still in it.
+    /// Book moves are picked at random, weighted by how often the book recommends them.
+    pub fn set_book(&mut self, book: Option<Book>) {
+        self.book = book.map(|book| (book, XorShift64::from_time()));
+    }
+
+    /// Steers searches away from root moves that lost games before, see [`experience`].
+    pub fn set_experience(&mut self, experience: Option<Experience>) {
+        self.experience = experience;
+    }
+
+    /// The experience searches use, which finished games can be recorded into.
+    pub fn experience_mut(&mut self) -> Option<&mut Experience> {
+        self.experience.as_mut()
+    }
+
+    /// Keeps searches to the moves of `repertoire` while the game is in it, see [`repertoire`].
+    /// The book isn't used in positions of the repertoire.
+    pub fn set_repertoire(&mut self, repertoire: Option<Repertoire>) {
+        self.repertoire = repertoire;
+    }
+
+    /// Saves the last search's transposition table to `path`, so what it learned can be picked up again with
+    /// [`Engine::load_tt`] after a restart. Only filled entries are written, so the file is small after short searches.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn save_tt(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        self.write_tt(io::BufWriter::new(std::fs::File::create(path)?))
+    }
+
+    /// Loads a transposition table saved by [`Engine::save_tt`], which the next search starts from
+    /// instead of an empty table. Deterministic searches still start from an empty table.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn load_tt(&mut self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        self.read_tt(io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// Writes the last search's transposition table to `writer`, as [`Engine::save_tt`] does to a file.
+    pub fn write_tt(&self, writer: impl Write) -> io::Result<()> {
+        self.transposition_table.write_to(writer)
+    }
+
+    /// Reads a transposition table from `reader`, as [`Engine::load_tt`] does from a file.
+    pub fn read_tt(&mut self, reader: impl Read) -> io::Result<()> {
+        self.transposition_table = Arc::new(TranspositionTable::read_from(reader)?);
+        self.keep_table = true;
+        Ok(())
+    }
+
+    /// Keeps the transposition table for the next search instead of emptying it, for a search that carries on
+    /// from the position the last one searched, such as once the move that was pondered on is played.
+    pub fn keep_table(&mut self) {
+        self.keep_table = true;
+    }
+
+    /// Records the tree of each search down to `max_ply`, see [`trace`]. `None` stops tracing.
+    #[cfg(feature = "trace")]
+    pub fn set_trace_depth(&mut self, max_ply: Option<u16>) {
+        self.trace_depth = max_ply;
+    }
+
+    /// The tree recorded by the last search while tracing, `None` if it wasn't traced or was already taken.
+    #[cfg(feature = "trace")]
+    pub fn take_trace(&mut self) -> Option<SearchTrace> {
+        self.trace.take()
+    }
+
+    /// Measures time limits with `clock` instead of the default, see [`clock`].
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// A handle that can stop this engine's searches from another thread.
+    pub fn handle(&self) -> SearchHandle {
+        SearchHandle {
+            stop: self.stop.clone()
+        }
+    }
+
+    /// Searches `chess` within the given limits, or until stopped through a [`SearchHandle`].
+    pub fn search(&mut self, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>) -> SearchResult {
+        let start_time = self.clock.now();
+        //A stop from before this search started was meant for an earlier one
+        self.stop.store(false, Ordering::Relaxed);
+
+        let mut root_moves = RootMoves::new(chess);
+        root_moves.restrict(&self.search_moves);
+        let in_repertoire = self.repertoire.as_ref().is_some_and(|repertoire| repertoire.contains(chess));
+        if let Some(repertoire) = self.repertoire.as_ref().filter(|_| in_repertoire) {
+            root_moves.restrict(&repertoire.moves(chess));
+        }
+        if let Some(experience) = &self.experience {
+            for root_move in root_moves.iter_mut() {
+                root_move.penalty = experience.penalty(chess, &root_move.mv);
+            }
+        }
+        if root_moves.is_empty() {
+            self.previous_search = None;
+            return SearchResult {
+                best_move: None,
+                score: match chess.outcome() {
+                    Some(Outcome::Decisive { winner }) if winner == chess.turn() => CHECKMATE_SCORE,
+                    Some(Outcome::Decisive { .. }) => -CHECKMATE_SCORE,
+                    _ => 0
+                },
+                depth: 0,
+                seldepth: 0,
+                pv: Vec::new(),
+                nodes: 0,
+                hashfull: 0,
+                time: self.clock.now().saturating_sub(start_time),
+                stats: SearchStats::default()
+            };
+        }
+
+        //Analysis should show what the engine thinks rather than what the book says, and the book can't score other
+        //moves or keep to the moves searches are restricted to
+        let skip_book = limits.infinite || self.multi_pv > 1 || !self.search_moves.is_empty() || in_repertoire;
+        if let (false, Some((book, random))) = (skip_book, &mut self.book) {
+            let random = if self.deterministic {position_random(chess)} else {random.next()};
+            if let Some(book_move) = book.pick(chess, random) {
+                self.previous_search = None;
+                return SearchResult {
+                    best_move: Some(book_move.clone()),
+                    score: 0,
+                    depth: 0,
+                    seldepth: 0,
+                    pv: vec![book_move],
+                    nodes: 0,
+                    hashfull: 0,
+                    time: self.clock.now().saturating_sub(start_time),
+                    stats: SearchStats::default()
+                };
+            }
+        }
+
+        let limits = self.strength_limits(limits);
+        let max_depth = limits.max_depth;
+        let table = self.cleared_table();
+        let mut helper_hashes;
+        let mut context = SearchContext::new(previously_seen_hashes, limits.max_nodes, self.evaluator.clone(), table);
+        context.report_info = self.report_info;
+        context.contempt = self.contempt + self.personality.contempt();
+        context.eval_params = self.eval_params;
+        context.piece_square_tables = self.piece_square_tables.clone();
+        context.search_params = self.search_params;
+        context.multi_pv = self.multi_pv.max(if self.skill.is_some() {SKILL_MULTI_PV} else {1});
+        context.stop = self.stop.clone();
+        context.clock = self.clock.clone();
+        context.start_time = start_time;
+        context.last_report = start_time;
+        #[cfg(feature = "trace")]
+        {
+            context.tracer = self.trace_depth.map(Tracer::new);
+        }
+
+        let limits_budget = TimeBudget::from_limits(&limits, chess.turn(), self.move_overhead.unwrap_or(DEFAULT_MOVE_OVERHEAD));
+        let max_move_time = self.max_move_time.filter(|_| !limits.infinite);
+        let max_move_time_budget = max_move_time.map(|max_move_time| TimeBudget { soft: max_move_time, hard: max_move_time });
+        let budget = match (limits_budget, max_move_time_budget) {
+            (Some(limits_budget), Some(max_move_time_budget)) => Some(limits_budget.min(max_move_time_budget)),
+            (limits_budget, max_move_time_budget) => limits_budget.or(max_move_time_budget)
+        };
+        //Deterministic searches spend their time as nodes, so they don't depend on how fast the machine is
+        let budget = match (self.deterministic, budget) {
+            (true, Some(budget)) => {
+                let nodes = budget.deterministic_nodes();
+                context.max_nodes = Some(context.max_nodes.map_or(nodes, |max_nodes| max_nodes.min(nodes)));
+                None
+            },
+            (_, budget) => budget
+        };
+        #[cfg(feature = "tracing")]
+        if let Some(budget) = budget {
+            tracing::debug!(soft_ms = budget.soft.as_millis() as u64, hard_ms = budget.hard.as_millis() as u64,
+                            max_move_time_ms = max_move_time.map(|max_move_time| max_move_time.as_millis() as u64), "time budget");
+        }
+        if let (true, Some(budget)) = (self.report_info, budget) {
+            match (limits_budget, max_move_time) {
+                (Some(limits_budget), Some(max_move_time)) if max_move_time < limits_budget.hard => {
+                    report_line(&mut self.progress_callback, format!("info string time {}ms hard {}ms clamped to MaxMoveTime {}ms",
+                                limits_budget.soft.as_millis(), limits_budget.hard.as_millis(), max_move_time.as_millis()));
+                },
+                _ => report_line(&mut self.progress_callback, format!("info string time {}ms hard {}ms", budget.soft.as_millis(), budget.hard.as_millis()))
+            }
+        }
+        context.deadline = budget.map(|budget| start_time + budget.hard);
+        //Each helper thread searches with its own copy of the game's positions
+        let helper_threads = if context.max_nodes.is_none() && context.multi_pv == 1 && !self.deterministic {self.helper_threads} else {0};
+        helper_hashes = vec![context.repetitions.hashes().to_vec(); helper_threads];
+        context.helpers = helper_hashes.iter_mut().map(|hashes| {
+            let mut helper = SearchContext::new(hashes, None, self.evaluator.clone(), context.transposition_table.clone());
+            helper.contempt = context.contempt;
+            helper.eval_params = context.eval_params;
+            helper.piece_square_tables = context.piece_square_tables.clone();
+            helper.search_params = context.search_params;
+            helper.stop = context.stop.clone();
+            helper.clock = context.clock.clone();
+            helper.deadline = context.deadline;
+            helper
+        }).collect();
+
+        let mut time_manager = TimeManager::with_params(self.clock.clone(), budget, self.search_params);
+
+        let seed = if self.deterministic {SearchSeed::default()} else {self.search_seed(chess)};
+        if let Some(first_move) = &seed.first_move {
+            root_moves.prioritize(first_move);
+        }
+
+        //Every move draws in a dead position or once the 75 move rule applies, so searching is pointless
+        let drawn = chess.is_insufficient_material() || chess.halfmoves() >= SEVENTY_FIVE_MOVE_RULE_HALFMOVES;
+        if root_moves.len() == 1 || drawn {
+            return self.finish_search(chess, root_moves, &mut context, 0);
+        }
+
+        let root = NodePosition::new(chess.clone());
+        let mut depth = 1;
+        let mut completed_depth = 0;
+        let mut aspiration_score = None;
+        let mut iteration_nodes = Vec::new();
+
+        while max_depth.is_none_or(|max_depth| depth <= max_depth) {
+            let predicted_nodes = predict_iteration_nodes(&iteration_nodes);
+            let predicted_time = predicted_nodes.and_then(|predicted_nodes| {
+                self.search_speed.predict_time(predicted_nodes, context.nodes, context.elapsed())
+            });
+            if let (true, Some(predicted_nodes), Some(predicted_time), Some(remaining_time)) =
+                (self.debug, predicted_nodes, predicted_time, time_manager.soft_remaining()) {
+                report_line(&mut self.progress_callback, format!("info string depth {} forecast {} nodes in {}ms with {}ms left",
+                            depth, predicted_nodes, predicted_time.as_millis(), remaining_time.as_millis()));
+            }
+
+            if !time_manager.should_start_iteration(predicted_time) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(depth, elapsed_ms = context.elapsed().as_millis() as u64,
+                                predicted_ms = predicted_time.map(|predicted_time| predicted_time.as_millis() as u64),
+                                soft_remaining_ms = time_manager.soft_remaining().map(|remaining| remaining.as_millis() as u64),
+                                "not starting iteration");
+                break;
+            }
+
+            //The best move stays first, the rest are tried in the order of their scores from the last iteration
+            if completed_depth > 0 {
+                root_moves.sort_by_score();
+            }
+
+            let nodes_before = context.nodes;
+            let best_move_before = root_moves.best().mv.clone();
+            //The window is centered on the previous iteration's score, or on the previous search's score at the depth that
+            //search reached here. Aspiration windows would leave the scores of the moves after the best as bounds
+            let center = seed.score.filter(|_| depth == seed.depth).or(aspiration_score);
+            let (alpha, beta) = match center.filter(|_| context.multi_pv == 1) {
+                Some(score) => (score.saturating_sub(self.search_params.aspiration_window), score.saturating_add(self.search_params.aspiration_window)),
+                None => (NEG_INFINITY, INFINITY)
+            };
+
+            #[cfg(feature = "trace")]
+            if let Some(tracer) = &mut context.tracer {
+                tracer.begin_iteration(depth);
+            }
+
+            let mut best_score = search_root(&root, &mut root_moves, depth, alpha, beta, &mut context, &mut self.progress_callback);
+
+            //A seeded window that fails is re-searched with a full window
+            if (best_score <= alpha || best_score >= beta) && (alpha, beta) != (NEG_INFINITY, INFINITY) && !context.stopped() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(depth, alpha, beta, score = best_score, "aspiration window failed");
+                context.stats.re_searches += 1;
+                best_score = search_root(&root, &mut root_moves, depth, NEG_INFINITY, INFINITY, &mut context, &mut self.progress_callback);
+            }
+
+            //Root moves only move to the front once fully searched, so the previous iteration's best move
+            //is kept unless this iteration already found a better one
+            if context.stopped() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(depth, elapsed_ms = context.elapsed().as_millis() as u64, "iteration stopped");
+                break;
+            }
+
+            iteration_nodes.push(context.nodes - nodes_before);
+            aspiration_score = Some(best_score);
+            time_manager.record_iteration(root_moves.best().mv != best_move_before, best_score);
+            completed_depth = depth;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(depth, score = best_score, best_move = %root_moves.best().mv, best_move_changed = root_moves.best().mv != best_move_before,
+                            nodes = context.nodes - nodes_before, elapsed_ms = context.elapsed().as_millis() as u64,
+                            soft_limit_ms = time_manager.soft_limit().map(|soft_limit| soft_limit.as_millis() as u64),
+                            hashfull = context.hashfull(), "iteration");
+
+            if let Some(progress_callback) = &mut self.progress_callback {
+                progress_callback(&SearchEvent::Iteration(iteration_result(chess, &root_moves, &context, completed_depth)));
+            }
+
+            context.last_report = context.clock.now();
+
+            //Recaptures and only moves aren't worth the whole budget, but a search without a soft limit to cut isn't slowed down checking
+            if depth == EASY_MOVE_DEPTH && context.multi_pv == 1 && time_manager.is_scalable() && best_score.abs() < REALLY_BIG_CHECKMATE_NUMBER
+                && is_easy_move(&root, &root_moves, depth, &mut context) {
+                time_manager.set_easy_move();
+                if self.debug {
+                    report_line(&mut self.progress_callback, format!("info string easy move, soft limit {}ms",
+                                time_manager.soft_limit().unwrap_or_default().as_millis()));
+                }
+            }
+
+            //A mate for the engine is played once the iteration is as deep as the mate is long, by when a shorter
+            //one would have been found. Getting mated keeps searching for the longest defense or a swindle.
+            if best_score >= REALLY_BIG_CHECKMATE_NUMBER && depth as i32 >= CHECKMATE_SCORE - best_score {
+                break;
+            }
+            if depth >= MAX_PLY {
+                break;
+            }
+
+            depth += 1;
+        }
+
+        self.finish_search(chess, root_moves, &mut context, completed_depth)
+    }
+
+    /// Searches `chess` like [`Engine::search`], but also scores the runners-up to the best move and returns up to
+    /// four reasonable moves, best first. Each is given a probability of being played that falls off the further
+    /// it trails the best move, more slowly at a higher `temperature` in centipawns. At a temperature of 0 only the
+    /// best move is ever played.
+    pub fn find_candidate_moves(&mut self, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>,
+                                temperature: f64) -> Vec<CandidateMove> {
+        let (_, root_moves) = self.search_multi_pv(chess, limits, previously_seen_hashes, CANDIDATE_MOVES);
+        let mut candidates: Vec<CandidateMove> = root_moves.into_iter().map(|root_move| CandidateMove {
+            mv: root_move.mv,
+            score: root_move.score,
+            pv: root_move.pv,
+            probability: 0.0
+        }).collect();
+
+        let Some(best_score) = candidates.iter().map(|candidate| candidate.score).max() else {
+            return candidates;
+        };
+        for candidate in &mut candidates {
+            candidate.probability = if temperature > 0.0 {
+                ((candidate.score - best_score) as f64 / temperature).exp()
+            }
+            else if candidate.score == best_score {1.0} else {0.0};
+        }
+        let total: f64 = candidates.iter().map(|candidate| candidate.probability).sum();
+        for candidate in &mut candidates {
+            candidate.probability /= total;
+        }
+
+        candidates
+    }
+
+    /// Searches `chess` like [`Engine::search`] but gives every legal move an exact score, and returns them all
+    /// best first. Moves with equal scores keep the order the search found them in. Every move is searched
+    /// as deeply as the best one, so this takes longer than finding the best move alone at the same depth.
+    pub fn evaluate_all_root_moves(&mut self, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>) -> Vec<ScoredMove> {
+        let (_, root_moves) = self.search_multi_pv(chess, limits, previously_seen_hashes, usize::MAX);
+        let mut scored_moves: Vec<ScoredMove> = root_moves.into_iter().map(|root_move| ScoredMove {
+            mv: root_move.mv,
+            score: root_move.score,
+            pv: root_move.pv
+        }).collect();
+        scored_moves.sort_by_key(|scored_move| std::cmp::Reverse(scored_move.score));
+        scored_moves
+    }
+
+    /// Searches `chess` like [`Engine::search`], but only considers the root moves in `search_moves`.
+    /// Moves that aren't legal are ignored, and if none of them are every legal move is searched.
+    pub fn search_moves(&mut self, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>,
+                        search_moves: &[Move]) -> SearchResult {
+        self.search_moves = search_moves.to_vec();
+        let result = self.search(chess, limits, previously_seen_hashes);
+        self.search_moves.clear();
+        result
+    }
+
+    /// Searches `chess` like [`Engine::search`] but without the book and with the best `multi_pv` root moves
+    /// searched for exact scores. Returns the result along with those root moves, best first.
+    pub(crate) fn search_multi_pv(&mut self, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>,
+                                  multi_pv: usize) -> (SearchResult, Vec<RootMove>) {
+        self.multi_pv = multi_pv;
+        let result = self.search(chess, limits, previously_seen_hashes);
+        self.multi_pv = 1;
+
+        let root_moves = self.previous_search.as_ref().map_or_else(Vec::new, |previous_search| {
+            previous_search.root_moves.iter().take(multi_pv).cloned().collect()
+        });
+        (result, root_moves)
+    }
+
+    /// Fills in root move PVs, remembers the search for the next move and builds the result.
+    fn finish_search(&mut self, chess: &P, mut root_moves: RootMoves, context: &mut SearchContext<E>,
+                     completed_depth: u16) -> SearchResult {
+        self.search_speed.update(context.nodes, context.elapsed());
+        #[cfg(feature = "trace")]
+        {
+            self.trace = context.tracer.take().map(Tracer::finish);
+        }
+
+        for root_move in root_moves.iter_mut() {
+            let mut new_chess = chess.clone();
+            new_chess.play_unchecked(&root_move.mv);
+            root_move.pv.truncate(1);
+            root_move.pv.extend(principal_variation(&new_chess, MAX_PV_LENGTH, &context.transposition_table));
+        }
+
+        //Below full skill one of the exactly searched moves is played instead of the best
+        let played = match &mut self.skill {
+            Some((skill_level, random)) if completed_depth > 0 => {
+                let scores: Vec<i32> = root_moves.iter().take(context.multi_pv).map(|root_move| root_move.score).collect();
+                let random = if self.deterministic {position_random(chess)} else {random.next()};
+                pick_move(&scores, *skill_level, random)
+            },
+            _ => 0
+        };
+
+        let result = SearchResult {
+            best_move: Some(root_moves.get(played).mv.clone()),
+            score: root_moves.get(played).score,
+            depth: completed_depth,
+            seldepth: context.seldepth,
+            pv: root_moves.get(played).pv.clone(),
+            nodes: context.nodes,
+            hashfull: context.hashfull(),
+            time: context.elapsed(),
+            stats: context.stats
+        };
+
+        #[cfg(feature = "tracing")]
+        {
+            tracing::info!(depth = result.depth, score = result.score, best_move = %root_moves.get(played).mv, nodes = result.nodes,
+                           elapsed_ms = result.time.as_millis() as u64, "search finished");
+            tracing::debug!(hashfull = result.hashfull, probes = context.stats.table_probes, hit_rate = context.stats.table_hit_rate(),
+                            "transposition table");
+        }
+
+        if self.debug {
+            let stats = &context.stats;
+            report_line(&mut self.progress_callback, format!("info string cutoffs {} first move {:.1}% table hits {:.1}% eval cache hits {:.1}% quiescence nodes {:.1}% re-searches {}",
+                        stats.beta_cutoffs, stats.first_move_cutoff_rate() * 100.0, stats.table_hit_rate() * 100.0,
+                        stats.eval_cache_hit_rate() * 100.0, stats.quiescence_ratio(context.nodes) * 100.0, stats.re_searches));
+        }
+
+        self.previous_search = Some(PreviousSearch {
+            root: chess.clone(),
+            root_moves,
+            depth: result.depth
+        });
+        result
+    }
+
+    /// The transposition table emptied for a new search, allocated the first time it is needed.
+    /// A loaded or kept table is left as it is for the first search after loading or keeping it.
+    fn cleared_table(&mut self) -> Arc<TranspositionTable> {
+        if std::mem::take(&mut self.keep_table) && !self.deterministic {
+            return self.transposition_table.clone();
+        }
+        if self.transposition_table.entries.is_empty() {
+            self.transposition_table = Arc::new(TranspositionTable::new());
+        }
+        else {
+            self.transposition_table.clear();
+        }
+        self.transposition_table.clone()
+    }
+
+    /// `limits` with the depth and node caps of the skill level and Elo limit added.
+    fn strength_limits(&self, mut limits: SearchLimits) -> SearchLimits {
+        if let Some((skill_level, _)) = self.skill {
+            limits.max_depth = Some(limits.max_depth.map_or(skill_depth(skill_level), |max_depth| max_depth.min(skill_depth(skill_level))));
+        }
+        if let Some(elo) = self.elo_limit {
+            limits.max_nodes = Some(limits.max_nodes.map_or(elo_nodes(elo), |max_nodes| max_nodes.min(elo_nodes(elo))));
+        }
+        limits
+    }
+
+    /// Checks whether `chess` is reached from the previous search's root by one move from
+    /// each side, and if so returns what that search expected to happen next.
+    fn search_seed(&self, chess: &P) -> SearchSeed {
+        let Some(previous_search) = &self.previous_search else {
+            return SearchSeed::default();
+        };
+
+        let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+
+        for (index, root_move) in previous_search.root_moves.iter().enumerate() {
+            let mut after_move = previous_search.root.clone();
+            after_move.play_unchecked(&root_move.mv);
+
+            for reply in after_move.legal_moves() {
+                let mut after_reply = after_move.clone();
+                after_reply.play_unchecked(&reply);
+                let reply_hash: Zobrist64 = after_reply.zobrist_hash(EnPassantMode::Legal);
+                if reply_hash != hash || after_reply.board() != chess.board() {
+                    continue;
+                }
+
+                //Only the PV of a move the opponent answered as expected says anything about this position
+                if root_move.pv.get(1) != Some(&reply) {
+                    return SearchSeed::default();
+                }
+
+                return SearchSeed {
+                    //Scores of moves other than the best are only bounds
+                    score: if index == 0 {Some(root_move.score)} else {None},
+                    //The position was two plies into the previous search's lines
+                    depth: previous_search.depth.saturating_sub(2),
+                    first_move: root_move.pv.get(2).cloned()
+                };
+            }
+        }
+
+        SearchSeed::default()
+    }
+}
+
+/// Searches `chess` within the given limits, see [`time_management`] for how time limits are used.
+pub fn search<P: SearchPosition>(chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>) -> SearchResult {
+    Engine::new().search(chess, limits, previously_seen_hashes)
+}
+
+/// Searches the standard chess position described by `fen` within the given limits, see [`search`].
+/// Castling rights written the Chess960 way are understood too.
+pub fn find_best_move_from_fen(fen: &str, limits: SearchLimits) -> Result<SearchResult, EngineError> {
+    Ok(search(&chess_from_fen(fen)?, limits, &mut Vec::new()))
+}
+
+/// The standard chess position described by `fen`, with the castling mode its castling rights are written in.
+pub(crate) fn chess_from_fen(fen: &str) -> Result<Chess, EngineError> {
+    let setup = Fen::from_ascii(fen.as_bytes()).map_err(EngineError::InvalidFen)?.0;
+    let castling_mode = CastlingMode::detect(&setup);
+    Chess::from_setup(setup, castling_mode).map_err(|error| EngineError::InvalidPosition(error.kinds()))
+}
+
+/// Finds a few reasonable moves in `chess` along with how likely each should be played, see [`Engine::find_candidate_moves`].
+pub fn find_candidate_moves<P: SearchPosition>(chess: &P, limits: SearchLimits, temperature: f64) -> Vec<CandidateMove> {
+    Engine::new().find_candidate_moves(chess, limits, &mut Vec::new(), temperature)
+}
+
+/// Scores every legal move in `chess`, best first, see [`Engine::evaluate_all_root_moves`].
+pub fn evaluate_all_root_moves<P: SearchPosition>(chess: &P, limits: SearchLimits) -> Vec<ScoredMove> {
+    Engine::new().evaluate_all_root_moves(chess, limits, &mut Vec::new())
+}
+
+/// State shared by every node of a single search.
+struct SearchContext<'a, E> {
+    evaluator: Arc<E>,
+    transposition_table: Arc<TranspositionTable>,
+    repetitions: Repetitions<'a>,
+    nodes: u64,
+    seldepth: u16,
+    max_nodes: Option<u64>,
+    stop: Arc<AtomicBool>,
+    filled_entries: usize,
+    report_info: bool,
+    clock: Arc<dyn Clock>,
+    start_time: Duration,
+    last_report: Duration,
+    /// When the hard time limit runs out, as the clock tells it.
+    deadline: Option<Duration>,
+    contempt: i32,
+    eval_params: EvalParams,
+    piece_square_tables: Option<Arc<PieceSquareTables>>,
+    search_params: SearchParams,
+    /// How many root moves are searched for an exact score rather than just to show they are worse than the best.
+    multi_pv: usize,
+    /// Quiet moves that caused a cutoff, indexed by the piece and target square of the move they answered.
+    countermoves: Vec<Option<Move>>,
+    /// The order moves are searched in at each ply, kept between nodes so they don't allocate.
+    search_orders: Vec<Vec<usize>>,
+    eval_cache: EvalCache,
+    pawn_table: PawnTable,
+    stats: SearchStats,
+    /// The contexts of the threads sharing out the root moves with this one, see [`search_root_parallel`].
+    helpers: Vec<SearchContext<'a, E>>,
+    #[cfg(feature = "trace")]
+    tracer: Option<Tracer>
+}
+
+impl<E> SearchContext<'_, E> {
+    /// Starts searching with `transposition_table`, which other threads of the same search may share.
+    fn new(previously_seen_hashes: &mut Vec<u64>, max_nodes: Option<u64>, evaluator: Arc<E>,
+           transposition_table: Arc<TranspositionTable>) -> SearchContext<'_, E> {
+
+        SearchContext {
+            evaluator,
+            transposition_table,
+            repetitions: Repetitions::new(previously_seen_hashes),
+            nodes: 0,
+            seldepth: 0,
+            max_nodes,
+            stop: Arc::new(AtomicBool::new(false)),
+            filled_entries: 0,
+            report_info: false,
+            clock: default_clock(),
+            start_time: Duration::ZERO,
+            last_report: Duration::ZERO,
+            deadline: None,
+            contempt: 0,
+            eval_params: EvalParams::default(),
+            piece_square_tables: None,
+            search_params: SearchParams::DEFAULT,
+            multi_pv: 1,
+            countermoves: vec![None; 2 * 6 * 64],
+            search_orders: vec![Vec::new(); MAX_PLY as usize],
+            eval_cache: EvalCache::new(),
+            pawn_table: PawnTable::new(),
+            stats: SearchStats::default(),
+            helpers: Vec::new(),
+            #[cfg(feature = "trace")]
+            tracer: None
+        }
+    }
+
+    /// The score of a draw for the side to move `ply` plies from the root, where the engine is to move at even plies.
+    fn draw_score(&self, ply: u16) -> i32 {
+        if ply.is_multiple_of(2) {-self.contempt} else {self.contempt}
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Counts a node, stopping the search once the node limit is reached.
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.max_nodes.is_some_and(|max_nodes| self.nodes >= max_nodes) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        if self.nodes.is_multiple_of(DEADLINE_CHECK_NODES) && self.deadline.is_some_and(|deadline| self.clock.now() >= deadline) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Time since the search started.
+    fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_sub(self.start_time)
+    }
+
+    /// How full the transposition table is in permille, as UCI reports it.
+    fn hashfull(&self) -> usize {
+        self.filled_entries * 1000 / TRANSPOSITION_TABLE_LENGTH
+    }
+
+    /// The static evaluation of `chess` for the side to move, from the evaluation cache if it was evaluated recently.
+    fn evaluate<P: SearchPosition>(&mut self, chess: &NodePosition<P>) -> i32 where E: Evaluator<P> {
+        self.stats.eval_probes += 1;
+        if let Some(evaluation) = self.eval_cache.get(chess.hash()) {
+            self.stats.eval_cache_hits += 1;
+            return evaluation;
+        }
+        let evaluation = static_evaluation(chess, self.evaluator.as_ref(), &self.eval_params, self.piece_square_tables.as_deref(), &mut self.pawn_table);
+        self.eval_cache.insert(chess.hash(), evaluation);
+        evaluation
+    }
+
+    /// The entry at `table_index`, counted as a hit if it is for the position with `hash`.
+    fn probe_table(&mut self, table_index: usize, hash: u64) -> TranspositionTableData {
+        let entry = self.transposition_table.get(table_index);
+        self.stats.table_probes += 1;
+        if entry.matches(hash) {
+            self.stats.table_hits += 1;
+        }
+        entry
+    }
+
+    /// Counts the entry at `table_index` if it is about to be filled for the first time.
+    fn note_table_write(&mut self, table_index: usize) {
+        if self.transposition_table.get(table_index).flag == TranspositionTableFlag::None {
+            self.filled_entries += 1;
+        }
+    }
+
+    /// Takes over the counts of a helper thread's context, so they are counted once for the whole search.
+    fn absorb(&mut self, helper: &mut SearchContext<E>) {
+        self.nodes += std::mem::take(&mut helper.nodes);
+        self.seldepth = self.seldepth.max(helper.seldepth);
+        self.filled_entries += std::mem::take(&mut helper.filled_entries);
+        self.stats.add(&std::mem::take(&mut helper.stats));
+    }
+
+    /// Updates the node being searched at `ply` in the trace, when tracing.
+    #[cfg(feature = "trace")]
+    fn trace(&mut self, ply: u16, note: impl FnOnce(&mut trace::TraceNode)) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.note(ply, note);
+        }
+    }
+
+    fn report_progress(&mut self, depth: u16, progress_callback: &mut Option<ProgressCallback>) {
+        if self.report_info {
+            report_line(progress_callback, format!("info depth {} seldepth {} nodes {} hashfull {}", depth, self.seldepth, self.nodes, self.hashfull()));
+        }
+        self.last_report = self.clock.now();
+    }
+}
+
+/// A random number that only depends on `chess`, for the choices of deterministic searches.
+fn position_random<P: SearchPosition>(chess: &P) -> u64 {
+    let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+    XorShift64::new(hash.0).next()
+}
+
+/// What the search knows after an iteration, with the PV of the best move read from the table.
+fn iteration_result<P: SearchPosition, E: Evaluator<P>>(chess: &P, root_moves: &RootMoves, context: &SearchContext<E>, depth: u16) -> SearchResult {
+    let best = root_moves.best();
+    let mut new_chess = chess.clone();
+    new_chess.play_unchecked(&best.mv);
+    let mut pv = vec![best.mv.clone()];
+    pv.extend(principal_variation(&new_chess, MAX_PV_LENGTH, &context.transposition_table));
+
+    SearchResult {
+        best_move: Some(best.mv.clone()),
+        score: best.score,
+        depth,
+        seldepth: context.seldepth,
+        pv,
+        nodes: context.nodes,
+        hashfull: context.hashfull(),
+        time: context.elapsed(),
+        stats: context.stats
+    }
+}
+
+/// Searches every root move so the iteration reaches `depth` plies, keeping the best `multi_pv` moves
+/// at the front in order of their scores. Stops early once the search is stopped or a move reaches `beta`.
+/// Returns the best score found, which can be outside the window like the scores of every other node.
+/// With helper threads, the moves after the first are shared out between them and `context`'s thread.
+fn search_root<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, root_moves: &mut RootMoves, depth: u16, alpha: i32, beta: i32,
+                                                   context: &mut SearchContext<E>, progress_callback: &mut Option<ProgressCallback>) -> i32 {
+    let mut best_score = NEG_INFINITY;
+    root_moves.start_pass();
+
+    for index in 0..root_moves.len() {
+        //The first move sets the bar the rest have to beat, so it is searched before they are shared out
+        if index == 1 && !context.helpers.is_empty() && context.multi_pv == 1 {
+            return search_root_parallel(chess, root_moves, depth, alpha.max(best_score), beta, context, progress_callback);
+        }
+
+        //The end of each iteration is always reported, so this only covers long iterations
+        if index > 0 && context.clock.now().saturating_sub(context.last_report) >= INFO_INTERVAL {
+            context.report_progress(depth, progress_callback);
+        }
+
+        if let (Some(progress_callback), true) = (progress_callback.as_mut(), context.elapsed() >= CURRENT_MOVE_DELAY) {
+            progress_callback(&SearchEvent::CurrentMove { depth, mv: root_moves.get(index).mv.clone(), number: index + 1 });
+        }
+
+        //A move only needs an exact score if it could make it into the best multi_pv moves
+        let threshold = if index < context.multi_pv {alpha} else {root_moves.get(context.multi_pv - 1).score.max(alpha)};
+
+        let nodes_before = context.nodes;
+        let root_move = root_moves.get(index);
+        let score = search_root_move(chess, &root_move.mv, root_move.penalty, depth, threshold, beta, context);
+
+        //An interrupted search returns garbage, so keep what is already known
+        if context.stopped() {
+            break;
+        }
+
+        let root_move = root_moves.get_mut(index);
+        root_move.score = score;
+        root_move.nodes += context.nodes - nodes_before;
+        root_move.searched = true;
+        //Only moves searched before this one can be passed, so the moves still to come keep their places
+        root_moves.rank(context.multi_pv, alpha);
+
+        if score > best_score {
+            best_score = score;
+            if best_score >= beta {
+                break;
+            }
+        }
+    }
+
+    best_score
+}
+
+/// Searches the root moves after the first on `context`'s thread and one thread per helper context, each thread taking
+/// the next move nobody has taken yet. Moves are searched against the best score found by any thread so far,
+/// and the scores are applied to `root_moves` in move order once every thread is done.
+fn search_root_parallel<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, root_moves: &mut RootMoves, depth: u16, alpha: i32,
+                                                            beta: i32, context: &mut SearchContext<E>,
+                                                            progress_callback: &mut Option<ProgressCallback>) -> i32 {
+    let shared = SharedRootMoves {
+        moves: root_moves.iter().map(|root_move| root_move.mv.clone()).collect(),
+        penalties: root_moves.iter().map(|root_move| root_move.penalty).collect(),
+        next: AtomicUsize::new(1),
+        alpha: AtomicI32::new(alpha),
+        beta
+    };
+    let mut helpers = std::mem::take(&mut context.helpers);
+    let results = std::thread::scope(|scope| {
+        let threads: Vec<_> = helpers.iter_mut().map(|helper| {
+            let shared = &shared;
+            scope.spawn(move || search_shared_root_moves(chess, shared, depth, helper, &mut None))
+        }).collect();
+
+        let mut results = search_shared_root_moves(chess, &shared, depth, context, progress_callback);
+        for thread in threads {
+            results.extend(thread.join().expect("search threads shouldn't panic"));
+        }
+        results
+    });
+
+    for helper in helpers.iter_mut() {
+        context.absorb(helper);
+    }
+    context.helpers = helpers;
+
+    //Moves a thread didn't finish keep their old scores and stay out of the ranking
+    let mut best_score = root_moves.get(0).score;
+    for (index, score, nodes) in results {
+        let root_move = root_moves.get_mut(index);
+        root_move.score = score;
+        root_move.nodes += nodes;
+        root_move.searched = true;
+        best_score = best_score.max(score);
+    }
+    root_moves.rank(1, alpha);
+
+    best_score
+}
+
+/// Whether every root move but the best scores at least the easy move margin below it in a search to `depth`.
+/// The other moves are searched with a null window at the margin, so most of them are refuted quickly.
+fn is_easy_move<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, root_moves: &RootMoves, depth: u16, context: &mut SearchContext<E>) -> bool {
+    let bar = root_moves.best().score - context.search_params.easy_move_margin;
+    root_moves.iter().skip(1).all(|root_move| {
+        let score = search_root_move(chess, &root_move.mv, root_move.penalty, depth, bar - 1, bar, context);
+        score < bar && !context.stopped()
+    })
+}
+
+/// Searches the root move `mv` to `depth` and returns its score less `penalty`. The window is raised by the penalty
+/// so the lowered score is bounded by `alpha` and `beta`. Mate scores are left alone so they still count the moves to mate.
+fn search_root_move<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, mv: &Move, penalty: i32, depth: u16, alpha: i32, beta: i32,
+                                                        context: &mut SearchContext<E>) -> i32 {
+    let new_chess = chess.play(mv);
+    let score = -nega_max(&new_chess, depth - 1, 1, -beta.saturating_add(penalty), -alpha.saturating_add(penalty), Some(mv), context);
+    if score.abs() >= REALLY_BIG_CHECKMATE_NUMBER {score} else {score - penalty}
+}
+
+/// The root moves being shared out between the threads of a search.
+struct SharedRootMoves {
+    moves: Vec<Move>,
+    penalties: Vec<i32>,
+    /// The index of the next move no thread has taken yet.
+    next: AtomicUsize,
+    /// The best score found so far, which every thread searches against.
+    alpha: AtomicI32,
+    beta: i32
+}
+
+/// Takes root moves from `shared` until there are none left, one reaches beta or the search is stopped.
+/// Returns the index, score and nodes of each move searched to the end.
+fn search_shared_root_moves<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, shared: &SharedRootMoves, depth: u16,
+                                                                context: &mut SearchContext<E>,
+                                                                progress_callback: &mut Option<ProgressCallback>) -> Vec<(usize, i32, u64)> {
+    let mut results = Vec::new();
+
+    loop {
+        let index = shared.next.fetch_add(1, Ordering::Relaxed);
+        let alpha = shared.alpha.load(Ordering::Relaxed);
+        if index >= shared.moves.len() || alpha >= shared.beta {
+            break;
+        }
+
+        if let (Some(progress_callback), true) = (progress_callback.as_mut(), context.elapsed() >= CURRENT_MOVE_DELAY) {
+            progress_callback(&SearchEvent::CurrentMove { depth, mv: shared.moves[index].clone(), number: index + 1 });
+        }
+
+        let nodes_before = context.nodes;
+        let score = search_root_move(chess, &shared.moves[index], shared.penalties[index], depth, alpha, shared.beta, context);
+
+        if context.stopped() {
+            break;
+        }
+
+        results.push((index, score, context.nodes - nodes_before));
+        shared.alpha.fetch_max(score, Ordering::Relaxed);
+    }
+
+    results
+}
+
+/// Follows the best moves stored in the transposition table from `chess`.
+fn principal_variation<P: SearchPosition>(chess: &P, max_length: usize, transposition_table: &TranspositionTable) -> Vec<Move> {
+    let mut pv = Vec::new();
+    let mut chess = NodePosition::new(chess.clone());
+
+    while pv.len() < max_length {
+        let hash = chess.hash();
+        let entry = transposition_table.get(hash as usize & TABLE_INDEX_MASK);
+        if !entry.matches(hash) || entry.depth == 0 {
+            break;
+        }
+
+        //Moves are sorted the same way nega_max sorts them so the stored index lines up
+        let mut moves = chess.legal_moves();
+        moves.sort_unstable_by_key(move_score);
+        let Some(m) = moves.get(entry.best_move_index as usize).cloned() else {
+            break;
+        };
+
+        chess = chess.play(&m);
+        pv.push(m);
+    }
+
+    pv
+}
+
+/// Mate scores are stored relative to the node rather than the root, so that a hashed mate
+/// stays the right distance away when the position is reached at a different ply.
+fn score_to_table(score: i32, ply: u16) -> i32 {
+    if score >= REALLY_BIG_CHECKMATE_NUMBER {
+        score + ply as i32
+    }
+    else if score <= -REALLY_BIG_CHECKMATE_NUMBER {
+        score - ply as i32
+    }
+    else {
+        score
+    }
+}
+
+/// Undoes [`score_to_table`] for a node at `ply`.
+fn score_from_table(score: i32, ply: u16) -> i32 {
+    if score >= REALLY_BIG_CHECKMATE_NUMBER {
+        score - ply as i32
+    }
+    else if score <= -REALLY_BIG_CHECKMATE_NUMBER {
+        score + ply as i32
+    }
+    else {
+        score
+    }
+}
+
+/// `previous_move` is the opponent's move that led to `chess`, `None` if it isn't known.
+fn nega_max<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, depth: u16, ply: u16, alpha: i32, beta: i32, previous_move: Option<&Move>, context: &mut SearchContext<E>) -> i32 {
+    #[cfg(feature = "trace")]
+    if let Some(tracer) = &mut context.tracer {
+        tracer.enter(previous_move, ply, depth, alpha, beta);
+    }
+
+    let score = search_node(chess, depth, ply, alpha, beta, previous_move, context);
+
+    #[cfg(feature = "trace")]
+    if let Some(tracer) = &mut context.tracer {
+        tracer.leave(ply, score);
+    }
+
+    score
+}
+
+/// The body of [`nega_max`], which wraps it so the tracer sees every way out of a node.
+fn search_node<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, depth: u16, ply: u16, mut alpha: i32, mut beta: i32, previous_move: Option<&Move>, context: &mut SearchContext<E>) -> i32 {
+    context.count_node();
+    context.seldepth = context.seldepth.max(ply);
+
+    if context.stopped() {
+        return 0;
+    }
+
+    if let Some(outcome) = chess.outcome() {
+        return outcome_score(outcome, chess.turn(), ply, context);
+    }
+
+    //Checked after the outcome because a checkmate on the move that reaches the limit still counts
+    if chess.halfmoves() >= FIFTY_MOVE_RULE_HALFMOVES {
+        return context.draw_score(ply);
+    }
+
+    let hash = chess.hash();
+    
+    //Engine will evaluate a draw if a single repetition occurs
+    if context.repetitions.is_repeated(hash, chess.halfmoves()) {
+        return context.draw_score(ply);
+    }
+
+    if ply >= MAX_PLY {
+        return context.evaluate(chess);
+    }
+
+    if depth == 0 {
+        return quiescence_search(chess, ply, alpha, beta, context);
+    }
+
+    let original_alpha = alpha;
+
+    let table_index = hash as usize & TABLE_INDEX_MASK;
+    let entry = context.probe_table(table_index, hash);
+    if entry.matches(hash) && entry.depth() >= depth {
+        let table_score = score_from_table(entry.score(), ply);
+        if entry.flag == TranspositionTableFlag::Exact {
+            #[cfg(feature = "trace")]
+            context.trace(ply, |node| node.table_hit = true);
+            return table_score;
+        }
+        else if entry.flag == TranspositionTableFlag::Lowerbound {
+            alpha = alpha.max(table_score);
+        }
+        else if entry.flag == TranspositionTableFlag::Upperbound {
+            beta = beta.min(table_score);
+        }
+        
+        if alpha >= beta {
+            #[cfg(feature = "trace")]
+            context.trace(ply, |node| node.table_hit = true);
+            return table_score;
+        }
+    }
+
+    //Without a table move the ordering is mostly guesswork, so a deep search would be wasted effort.
+    //The shallower search fills the table with a move for when this node is searched again.
+    let entry = context.transposition_table.get(table_index);
+    let depth = if !(entry.matches(hash) && entry.depth > 0) && depth >= INTERNAL_ITERATIVE_REDUCTION_DEPTH {
+        depth - 1
+    }
+    else {
+        depth
+    };
+
+    //Positions in check are too sharp to judge by their static evaluation
+    let static_eval = (depth <= FUTILITY_DEPTH.max(REVERSE_FUTILITY_DEPTH) && !chess.is_check()).then(|| context.evaluate(chess));
+
+    //Replies to root moves are always searched so sacrifices played at the root aren't dismissed
+    if let (true, Some(static_eval)) = (depth <= REVERSE_FUTILITY_DEPTH && ply > 1, static_eval) {
+        let reduced_eval = static_eval - context.search_params.reverse_futility_margin * depth as i32;
+        if reduced_eval >= beta && beta < REALLY_BIG_CHECKMATE_NUMBER {
+            context.stats.reverse_futility_cutoffs += 1;
+            return reduced_eval;
+        }
+    }
+
+    let futility_value = static_eval
+        .filter(|_| depth <= FUTILITY_DEPTH)
+        .map(|static_eval| static_eval + context.search_params.futility_margin * depth as i32);
+    let late_move_count = (depth <= LATE_MOVE_PRUNING_DEPTH && !chess.is_check()).then(|| 3 + depth as usize * depth as usize);
+    let mut moves_searched = 0;
+
+    context.repetitions.push(hash);
+
+    let mut value = NEG_INFINITY;
+    let mut best_move_index = 0;
+
+    //Table entries point into this order, so the countermove only changes the order moves are searched in
+    let mut moves = chess.legal_moves();
+    moves.sort_unstable_by_key(move_score);
+    let countermove_index = previous_move.map(|previous_move| countermove_index(previous_move, !chess.turn()));
+    let countermove = countermove_index.and_then(|countermove_index| context.countermoves[countermove_index].as_ref());
+    //Each ply reuses its own buffer, which is handed back once the moves are searched
+    let mut search_order = std::mem::take(&mut context.search_orders[ply as usize]);
+    search_order.clear();
+    search_order.extend(0..moves.len());
+    if let Some(countermove) = countermove {
+        //Only quiet moves are stored, so the countermove goes first among the quiet moves and stays behind every
+        //capture. The index breaks ties like a stable sort would, without the stable sort's allocation
+        search_order.sort_unstable_by_key(|&index| (move_score(&moves[index]), &moves[index] != countermove, index));
+    }
+
+    //Entries stored by quiescence search (depth 0) don't have a best move
+    let entry = context.transposition_table.get(table_index);
+    let table_move_index = (entry.matches(hash) && entry.depth > 0 && (entry.best_move_index as usize) < moves.len())
+        .then_some(entry.best_move_index as usize);
+
+    if let Some(table_move_index) = table_move_index {
+        //Search best move first if there is an entry in the transposition table
+        best_move_index = table_move_index;
+        let new_chess = chess.play(&moves[best_move_index]);
+        let score = -nega_max(&new_chess, depth - 1, ply + 1, -beta, -alpha, Some(&moves[best_move_index]), context);
+        moves_searched += 1;
+        value = value.max(score);
+        alpha = alpha.max(value);
+    }
+    
+    if alpha < beta && !context.stopped() {
+        for &index in &search_order {
+            if table_move_index == Some(index) {
+                continue;
+            }
+
+            let m = &moves[index];
+            let mut new_position = P::clone(chess);
+            new_position.play_unchecked(m);
+
+            let quiet = !m.is_capture() && !m.is_promotion() && !new_position.is_check();
+
+            //A quiet move this close to the horizon won't make up the difference, unless it gives check
+            if let (true, Some(futility_value)) = (quiet, futility_value) {
+                if futility_value <= alpha && alpha < REALLY_BIG_CHECKMATE_NUMBER {
+                    value = value.max(futility_value);
+                    context.stats.pruned_moves += 1;
+                    continue;
+                }
+            }
+
+            //Quiet moves this late in the order rarely turn out best this close to the horizon
+            if quiet && late_move_count.is_some_and(|late_move_count| moves_searched >= late_move_count) {
+                context.stats.pruned_moves += 1;
+                continue;
+            }
+
+            let new_chess = chess.child(new_position);
+            let score = -nega_max(&new_chess, depth - 1, ply + 1, -beta, -alpha, Some(m), context);
+            moves_searched += 1;
+            if context.stopped() {
+                break;
+            }
+
+            if score > value {
+                value = score;
+                best_move_index = index;
+                alpha = alpha.max(value);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+    }
+
+    context.repetitions.pop();
+    context.search_orders[ply as usize] = search_order;
+
+    //Scores from an interrupted search can't be trusted, so they aren't stored
+    if context.stopped() {
+        return 0;
+    }
+
+    if value >= beta {
+        context.stats.beta_cutoffs += 1;
+        if moves_searched == 1 {
+            context.stats.first_move_cutoffs += 1;
+        }
+        #[cfg(feature = "trace")]
+        context.trace(ply, |node| node.cutoff = true);
+    }
+
+    let best_move = &moves[best_move_index];
+    if let (true, Some(countermove_index)) = (value >= beta && !best_move.is_capture() && !best_move.is_promotion(), countermove_index) {
+        context.countermoves[countermove_index] = Some(best_move.clone());
+    }
+
+    if context.transposition_table.get(table_index).depth() < depth {
+        context.note_table_write(table_index);
+        let flag = if value <= original_alpha {
+            TranspositionTableFlag::Upperbound
+        }
+        else if value >= beta {
+            TranspositionTableFlag::Lowerbound
+        }
+        else {
+            TranspositionTableFlag::Exact
+        };
+        context.transposition_table.set(table_index, TranspositionTableData::store(hash, score_to_table(value, ply), depth, best_move_index, flag));
+    }
+    
+    value
+}
+
+/// The score of a finished game for `turn`, the side to move.
+fn outcome_score<E>(outcome: Outcome, turn: Color, ply: u16, context: &SearchContext<E>) -> i32 {
+    //Some variants are won by the side to move, such as by losing every piece in antichess
+    match outcome {
+        Outcome::Draw => context.draw_score(ply),
+        Outcome::Decisive { winner } if winner == turn => CHECKMATE_SCORE - ply as i32,
+        Outcome::Decisive { .. } => ply as i32 - CHECKMATE_SCORE
+    }
+}
+
+/// Where the answer to `previous_move`, played by `color`, is kept in the countermove table.
+fn countermove_index(previous_move: &Move, color: Color) -> usize {
+    ((color as usize * 6) + (previous_move.role() as usize - 1)) * 64 + previous_move.to() as usize
+}
+
+/// The evaluation from the point of view of the side to move. With tables other than the engine's own, the board
+/// evaluation is worked out with them and the params are already weighed into it.
+fn static_evaluation<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, evaluator: &E, eval_params: &EvalParams,
+                                                         tables: Option<&PieceSquareTables>, pawn_table: &mut PawnTable) -> i32 {
+    let evaluation = match tables {
+        Some(tables) => evaluator.evaluate_with_board(chess, evaluate_board_detailed_with_tables(chess.board(), eval_params, tables).total()),
+        None => evaluator.evaluate_with_params(chess, chess.board_evaluation(pawn_table), eval_params)
+    };
+    evaluation * if chess.turn().is_white() {1} else {-1}
+}
+
+fn quiescence_search<P: SearchPosition, E: Evaluator<P>>(chess: &NodePosition<P>, ply: u16, mut alpha: i32, mut beta: i32, context: &mut SearchContext<E>) -> i32 {
+    context.count_node();
+    context.stats.quiescence_nodes += 1;
+    context.seldepth = context.seldepth.max(ply);
+
+    if context.stopped() {
+        return 0;
+    }
+
+    if ply >= MAX_PLY {
+        return context.evaluate(chess);
+    }
+
+    let hash = chess.hash();
+
+    //Any entry is deep enough to be used here, including ones from the main search
+    let table_index = hash as usize & TABLE_INDEX_MASK;
+    let entry = context.probe_table(table_index, hash);
+    if entry.matches(hash) {
+        let table_score = score_from_table(entry.score(), ply);
+        if entry.flag == TranspositionTableFlag::Exact {
+            return table_score;
+        }
+        else if entry.flag == TranspositionTableFlag::Lowerbound {
+            alpha = alpha.max(table_score);
+        }
+        else if entry.flag == TranspositionTableFlag::Upperbound {
+            beta = beta.min(table_score);
+        }
+
+        if alpha >= beta {
+            return table_score;
+        }
+    }
+
+    let original_alpha = alpha;
+
+    //Fail-soft, so the best score found is returned even when it is outside the window
+    let value = 'search: {
+        let stand_pat = context.evaluate(chess);
+
+        //A capture that leaves a bare king with no moves is a stalemate, not the win the evaluation says it is
+        let board = chess.board();
+        let pieces = board.by_color(chess.turn()) & !board.kings() & !board.pawns();
+        if stand_pat <= -STALEMATE_CHECK_MARGIN && pieces.is_empty() {
+            if let Some(outcome) = chess.outcome() {
+                break 'search outcome_score(outcome, chess.turn(), ply, context);
+            }
+        }
+        
+        if stand_pat >= beta || chess.is_known_endgame() {
+            break 'search stand_pat;
+        }
+
+        let mut best_score = stand_pat;
+        alpha = alpha.max(stand_pat);
+        
+        let mut capture_moves = chess.capture_moves();
+        capture_moves.sort_unstable_by_key(capture_score);
+
+        for m in &capture_moves {
+            let new_chess = chess.play(m);
+            let score = -quiescence_search(&new_chess, ply + 1, -beta, -alpha, context);
+            if context.stopped() {
+                break 'search 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                if score >= beta {
+                    break;
+                }
+                alpha = alpha.max(score);
+            }
+        }
+        
+        best_score
+    };
+
+    //Quiescence entries never replace entries from the main search
+    if context.transposition_table.get(table_index).depth == 0 && !context.stopped() {
+        context.note_table_write(table_index);
+        let flag = if value <= original_alpha {
+            TranspositionTableFlag::Upperbound
+        }
+        else if value >= beta {
+            TranspositionTableFlag::Lowerbound
+        }
+        else {
+            TranspositionTableFlag::Exact
+        };
+        context.transposition_table.set(table_index, TranspositionTableData::store(hash, score_to_table(value, ply), 0, 0, flag));
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use shakmaty::fen::Fen;
+    use std::time::Instant;
+    use shakmaty::{CastlingMode, Chess, FromSetup, Position, Role, Square};
+
+    fn best_move_with_depth(chess: &Chess, max_depth: u16) -> Move {
+        let limits = SearchLimits { max_depth: Some(max_depth), ..SearchLimits::default() };
+        search(chess, limits, &mut Vec::new()).best_move.expect("position should have a legal move")
+    }
+
+    #[test]
+    //This is just to test performace, it asserts nothing
+    fn test_fens_time() {
+        for fen in test_fens::WIN_AT_CHESS {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+            best_move_with_depth(&chess, 1);
+        }
+    }
+
+    #[test]
+    //This is just to test performace, it asserts nothing
+    fn test_position_time() {
+        let setup = Fen::from_ascii("2rq1bk1/1b4pp/pn3n2/1p1Ppp2/1PP1P3/7P/3N1PP1/R2QRBK1 w - - 0 23".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        best_move_with_depth(&chess, 7);
+    }
+
+    #[test]
+    //Checks the the program successfully solves the lasker position
+    fn lasker_position() {
+        let setup = Fen::from_ascii("8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - -".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        assert!(best_move_with_depth(&chess, 21).to_string() == "Ka1-b1");
+    }
+
+    #[test]
+    //Makes sure both my methods agree on best move from a test position
+    //This might stop working if my searches become faster. It is kinda luck and hardware based.
+    fn time_and_depth_agree() {
+        let setup = Fen::from_ascii("2rq1bk1/1b4pp/pn3n2/1p1Ppp2/1PP1P3/7P/3N1PP1/R2QRBK1 w - - 0 23".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let m1 = best_move_with_depth(&chess, 7);
+        let m2 = search(&chess, SearchLimits { move_time: Some(Duration::from_millis(500)), ..SearchLimits::default() }, &mut Vec::new()).best_move;
+        assert!(Some(m1) == m2);
+    }
+
+    #[test]
+    //The engine should recognise a position two plies after its last search and reuse its PV and score
+    fn continuation_is_detected() {
+        let mut engine = Engine::new();
+        let chess = Chess::new();
+        engine.search(&chess, SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+
+        let pv = engine.previous_search.as_ref().expect("search should be remembered").root_moves.best().pv.clone();
+        assert!(pv.len() >= 3);
+
+        let mut continuation = chess.clone();
+        continuation.play_unchecked(&pv[0]);
+        continuation.play_unchecked(&pv[1]);
+        let seed = engine.search_seed(&continuation);
+        assert!(seed.first_move == Some(pv[2].clone()));
+        assert!(seed.score.is_some() && seed.depth == 1);
+
+        //A position that didn't come from the last search gets no seed
+        assert!(engine.search_seed(&chess).first_move.is_none());
+    }
+
+    #[test]
+    //A max move time should cut even a very deep search short
+    fn max_move_time_is_enforced() {
+        let setup = Fen::from_ascii("2rq1bk1/1b4pp/pn3n2/1p1Ppp2/1PP1P3/7P/3N1PP1/R2QRBK1 w - - 0 23".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let mut engine = Engine::new();
+        engine.set_max_move_time(Some(Duration::from_millis(200)));
+
+        let start_time = Instant::now();
+        let best_move = engine.search(&chess, SearchLimits { max_depth: Some(40), ..SearchLimits::default() }, &mut Vec::new()).best_move;
+        assert!(Instant::now() - start_time < Duration::from_secs(2));
+        assert!(best_move.is_some_and(|best_move| chess.is_legal(&best_move)));
+    }
+
+    #[test]
+    //Mate scores should give the exact distance to mate no matter how deep the search went
+    fn mate_scores_survive_the_table() {
+        //Mate in 1 and mate in 2 (1. Ra6 bxa6 2. b7#)
+        for (fen, best_move, mate_in) in [("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "Rd1-d8", 1),
+                                          ("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", "Ra1-a6", 2)] {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+
+            for max_depth in [3, 5, 7] {
+                let result = search(&chess, SearchLimits { max_depth: Some(max_depth), ..SearchLimits::default() }, &mut Vec::new());
+                assert!(result.best_move.as_ref().expect("position should have a legal move").to_string() == best_move);
+                assert!(result.score == CHECKMATE_SCORE - (2 * mate_in - 1));
+                assert!(result.mate_in() == Some(mate_in));
+            }
+        }
+    }
+
+    #[test]
+    //A found mate should end the search, while getting mated should keep it going to look for a defense
+    fn mates_end_the_search_for_the_mating_side() {
+        let load = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+        };
+        let limits = SearchLimits { max_depth: Some(8), ..SearchLimits::default() };
+        let mating = search(&load("k7/8/1K6/8/8/8/8/7R w - - 0 1"), limits, &mut Vec::new());
+        assert!(mating.mate_in() == Some(1) && mating.depth == 1);
+
+        let mated = search(&load("k7/8/1K6/8/8/2p5/8/7R b - - 0 1"), limits, &mut Vec::new());
+        assert!(mated.mate_in() == Some(-1) && mated.depth == 8);
+    }
+
+    #[test]
+    //Converting a score for the table and back should give the original score
+    fn table_score_round_trip() {
+        for score in [0, 150, -150, CHECKMATE_SCORE - 7, 7 - CHECKMATE_SCORE] {
+            for ply in [0, 1, 9] {
+                assert!(score_from_table(score_to_table(score, ply), ply) == score);
+            }
+        }
+        //A mate found 3 plies below a node at ply 2 is 3 plies from that node wherever it is reached
+        assert!(score_from_table(score_to_table(CHECKMATE_SCORE - 5, 2), 6) == CHECKMATE_SCORE - 9);
+    }
+
+    #[test]
+    //Every limit should stop the search on its own, and together the first one reached wins
+    fn combined_limits() {
+        let setup = Fen::from_ascii("2rq1bk1/1b4pp/pn3n2/1p1Ppp2/1PP1P3/7P/3N1PP1/R2QRBK1 w - - 0 23".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+
+        //Stopping at 1000 nodes happens long before depth 40 or a minute
+        let limits = SearchLimits { max_depth: Some(40), max_nodes: Some(1000), move_time: Some(Duration::from_secs(60)), ..SearchLimits::default() };
+        let start_time = Instant::now();
+        let best_move = search(&chess, limits, &mut Vec::new()).best_move.expect("position should have a legal move");
+        assert!(Instant::now() - start_time < Duration::from_secs(5));
+        assert!(chess.is_legal(&best_move));
+
+        //A depth limit alone gives the same move as the depth search
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        assert!(search(&chess, limits, &mut Vec::new()).best_move == Some(best_move_with_depth(&chess, 3)));
+
+        //Depth and time together stop at the depth if it comes first
+        let limits = SearchLimits { max_depth: Some(3), move_time: Some(Duration::from_secs(60)), ..SearchLimits::default() };
+        let start_time = Instant::now();
+        search(&chess, limits, &mut Vec::new());
+        assert!(Instant::now() - start_time < Duration::from_secs(5));
+    }
+
+    /// Moves on a millisecond every time it is read.
+    struct TickingClock(ManualClock);
+
+    impl Clock for TickingClock {
+        fn now(&self) -> Duration {
+            self.0.advance(Duration::from_millis(1));
+            self.0.now()
+        }
+    }
+
+    #[test]
+    //Time limits should be measured with the engine's clock rather than the system's
+    fn injected_clock() {
+        let chess = Chess::new();
+        let mut engine: Engine = Engine::new();
+        engine.set_clock(Arc::new(ManualClock::new()));
+        let limits = SearchLimits { max_depth: Some(4), move_time: Some(Duration::from_millis(1)), ..SearchLimits::default() };
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        assert!(result.depth == 4 && result.time == Duration::ZERO);
+
+        engine.set_clock(Arc::new(TickingClock(ManualClock::new())));
+        let limits = SearchLimits { move_time: Some(Duration::from_millis(100)), ..SearchLimits::default() };
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move.is_some_and(|best_move| chess.is_legal(&best_move)));
+        assert!(result.time >= Duration::from_millis(50) && result.time < Duration::from_secs(1));
+
+        //The hard limit is checked against the clock as nodes are counted
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+        let clock = Arc::new(ManualClock::new());
+        context.clock = clock.clone();
+        context.deadline = Some(Duration::from_secs(1));
+        for _ in 0..DEADLINE_CHECK_NODES {
+            context.count_node();
+        }
+        assert!(!context.stopped());
+        clock.advance(Duration::from_secs(1));
+        for _ in 0..DEADLINE_CHECK_NODES {
+            context.count_node();
+        }
+        assert!(context.stopped());
+    }
+
+    #[test]
+    //The result should describe the search that produced it
+    fn search_result_contents() {
+        let chess = Chess::new();
+        let result = search(&chess, SearchLimits { max_depth: Some(5), ..SearchLimits::default() }, &mut Vec::new());
+
+        assert!(result.depth == 5);
+        assert!(result.seldepth >= result.depth);
+        assert!(result.nodes > 0);
+        assert!(result.mate_in().is_none());
+
+        //The PV starts with the best move and is playable
+        assert!(result.pv.first() == result.best_move.as_ref());
+        let mut position = chess.clone();
+        for m in &result.pv {
+            assert!(position.is_legal(m));
+            position.play_unchecked(m);
+        }
+    }
+
+    #[test]
+    //Degenerate positions should be searched without panicking and give draw or terminal results
+    fn degenerate_positions() {
+        let load = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+        };
+
+        //Checkmated and stalemated positions have no move to return
+        let checkmated = search(&load("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"), SearchLimits::default(), &mut Vec::new());
+        assert!(checkmated.best_move.is_none() && checkmated.mate_in() == Some(0));
+        let stalemated = search(&load("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), SearchLimits::default(), &mut Vec::new());
+        assert!(stalemated.best_move.is_none() && stalemated.score == 0);
+
+        //Dead draws and 75 move draws return straight away even without any limits
+        for fen in ["8/8/8/4k3/8/8/8/4K3 w - - 0 1", "8/8/3bk3/8/8/8/8/4K3 b - - 0 1", "8/8/8/4k3/8/2R5/8/4K3 w - - 150 200"] {
+            let chess = load(fen);
+            let best_move = search(&chess, SearchLimits::default(), &mut Vec::new()).best_move.expect("position should have a legal move");
+            assert!(chess.is_legal(&best_move));
+        }
+
+        //Past the fifty move rule every line is a draw
+        let result = search(&load("8/8/8/4k3/8/2R5/8/4K3 w - - 120 200"), SearchLimits { max_depth: Some(6), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(result.score == 0);
+
+        //Boards full of promoted queens
+        let chess = load("rnbqkbnr/qqqqqqqq/8/8/8/8/QQQQQQQQ/RNBQKBNR w - - 0 1");
+        let best_move = search(&chess, SearchLimits { max_nodes: Some(200_000), ..SearchLimits::default() }, &mut Vec::new()).best_move;
+        assert!(best_move.is_some_and(|best_move| chess.is_legal(&best_move)));
+    }
+
+    #[test]
+    //A search with no limits should stop when told to and still return a legal move
+    fn handle_stops_search() {
+        let chess = Chess::default();
+        let mut engine = Engine::new();
+        let handle = engine.handle();
+
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            handle.stop();
+        });
+        let start_time = Instant::now();
+        let result = engine.search(&chess, SearchLimits::default(), &mut Vec::new());
+        stopper.join().expect("stopping thread shouldn't panic");
+        assert!(Instant::now() - start_time < Duration::from_secs(5));
+        assert!(result.best_move.is_some_and(|best_move| chess.is_legal(&best_move)));
+
+        //The old stop shouldn't cut the next search short
+        assert!(engine.handle().is_stopped());
+        let result = engine.search(&chess, SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(result.depth == 3);
+    }
+
+    #[test]
+    //Every completed iteration should be reported with the last one matching the result
+    fn progress_events() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+        let sink = events.clone();
+        engine.set_progress_callback(Some(Box::new(move |event| {
+            let SearchEvent::Iteration(result) = event else {
+                return;
+            };
+            sink.lock().unwrap().push((result.depth, result.best_move.clone(), result.pv.len()));
+        })));
+
+        let result = engine.search(&Chess::default(), SearchLimits { max_depth: Some(5), ..SearchLimits::default() }, &mut Vec::new());
+        let events = events.lock().unwrap();
+        let depths: Vec<u16> = events.iter().map(|event| event.0).collect();
+        assert!(depths == vec![1, 2, 3, 4, 5]);
+        assert!(events.iter().all(|event| event.2 > 0));
+        assert!(events.last().unwrap().1 == result.best_move);
+    }
+
+    #[test]
+    //Winning a loose queen should be found to be an easy move, while the start position isn't
+    fn easy_moves() {
+        let easy_move = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+            let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let sink = lines.clone();
+            let mut engine = Engine::new();
+            engine.set_debug(true);
+            engine.set_progress_callback(Some(Box::new(move |event| {
+                if let SearchEvent::Info(line) = event {
+                    sink.lock().unwrap().push(line.clone());
+                }
+            })));
+            let limits = SearchLimits { max_depth: Some(EASY_MOVE_DEPTH), white_time: Some(Duration::from_secs(60)), ..SearchLimits::default() };
+            let result = engine.search(&chess, limits, &mut Vec::new());
+            let easy = lines.lock().unwrap().iter().any(|line| line.starts_with("info string easy move"));
+            (result.best_move.expect("position should have a legal move").to_string(), easy)
+        };
+
+        assert!(easy_move("rnb1kbnr/pppp1ppp/8/4p3/3q4/5N2/PPPPPPPP/RNBQKB1R w KQkq - 0 1") == ("Nf3xd4".to_string(), true));
+        assert!(!easy_move("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").1);
+    }
+
+    #[test]
+    //Searching with helper threads should find the same forced wins and count every thread's nodes
+    fn helper_threads() {
+        let mut engine = Engine::new();
+        engine.set_threads(4);
+
+        //Mate in 2 (1. Ra6 bxa6 2. b7#)
+        let setup = Fen::from_ascii("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let result = engine.search(&chess, SearchLimits { max_depth: Some(5), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(result.best_move.as_ref().expect("position should have a legal move").to_string() == "Ra1-a6");
+        assert!(result.mate_in() == Some(2));
+
+        let result = engine.search(&Chess::default(), SearchLimits { max_depth: Some(5), ..SearchLimits::default() }, &mut Vec::new());
+        let root_nodes: u64 = engine.previous_search.as_ref().expect("search should be kept").root_moves.iter().map(|root_move| root_move.nodes).sum();
+        assert!(result.depth == 5 && result.nodes >= root_nodes);
+    }
+
+    #[test]
+    //A table kept from an earlier search should be emptied so it can't change the next one
+    fn reused_table_is_cleared() {
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        let mut engine = Engine::new();
+        let first = engine.search(&Chess::default(), limits, &mut Vec::new());
+        let second = engine.search(&Chess::default(), limits, &mut Vec::new());
+        assert!(first.nodes == second.nodes && first.pv == second.pv);
+    }
+
+    #[test]
+    //Mate scores should survive being packed into an entry exactly, and other scores up to the clamp
+    fn packed_table_scores() {
+        let hash = 0xabcd_0000_0000_1234;
+        for score in [0, 57, -3_000, CHECKMATE_SCORE, CHECKMATE_SCORE - 37, -CHECKMATE_SCORE + 12, REALLY_BIG_CHECKMATE_NUMBER, -REALLY_BIG_CHECKMATE_NUMBER] {
+            let entry = TranspositionTableData::store(hash, score, 3, 5, TranspositionTableFlag::Exact);
+            assert!(entry.score() == score);
+        }
+        let entry = TranspositionTableData::store(hash, 1_000_000, 300, 5, TranspositionTableFlag::Lowerbound);
+        assert!(entry.score() == MAX_TABLE_SCORE && entry.depth() == u8::MAX as u16);
+        assert!(entry.matches(hash) && !entry.matches(hash ^ 1 << 60));
+        assert!(!TranspositionTableData::new().matches(0));
+    }
+
+    #[test]
+    //Search counters should be filled in and consistent with each other
+    fn search_stats() {
+        let result = search(&Chess::default(), SearchLimits { max_depth: Some(5), ..SearchLimits::default() }, &mut Vec::new());
+        let stats = result.stats;
+        assert!(stats.beta_cutoffs > 0 && stats.first_move_cutoffs <= stats.beta_cutoffs);
+        assert!(stats.table_hits > 0 && stats.table_hits <= stats.table_probes);
+        assert!(stats.quiescence_nodes > 0 && stats.quiescence_nodes < result.nodes);
+        assert!((0.0..=1.0).contains(&stats.first_move_cutoff_rate()) && (0.0..=1.0).contains(&stats.quiescence_ratio(result.nodes)));
+        assert!(stats.eval_cache_hits > 0 && stats.eval_cache_hits <= stats.eval_probes);
+        assert!(SearchStats::default().table_hit_rate() == 0.0);
+    }
+
+    #[test]
+    //Root moves should be reported once the search has run for a while, numbered from 1
+    fn current_move_events() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+        let sink = events.clone();
+        engine.set_progress_callback(Some(Box::new(move |event| {
+            if let SearchEvent::CurrentMove { mv, number, .. } = event {
+                sink.lock().unwrap().push((mv.clone(), *number));
+            }
+        })));
+
+        let limits = SearchLimits { move_time: Some(CURRENT_MOVE_DELAY * 2), ..SearchLimits::default() };
+        engine.search(&Chess::default(), limits, &mut Vec::new());
+        let events = events.lock().unwrap();
+        let legal_moves = Chess::default().legal_moves();
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|(mv, number)| legal_moves.contains(mv) && (1..=legal_moves.len()).contains(number)));
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    //Traces should hold every iteration, with the root moves at ply 1 and nothing past the traced depth
+    fn traced_search() {
+        fn deepest_ply(nodes: &[trace::TraceNode]) -> u16 {
+            nodes.iter().map(|node| node.ply.max(deepest_ply(&node.children))).max().unwrap_or(0)
+        }
+
+        let mut engine = Engine::new();
+        engine.set_trace_depth(Some(2));
+        engine.search(&Chess::default(), SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+        let trace = engine.take_trace().expect("the search was traced");
+        assert!(engine.take_trace().is_none());
+
+        let depths: Vec<u16> = trace.iterations.iter().map(|iteration| iteration.depth).collect();
+        assert!(depths == vec![1, 2, 3]);
+        let legal_moves = Chess::default().legal_moves();
+        assert!(trace.iterations[0].nodes.len() == legal_moves.len());
+        assert!(trace.iterations.iter().flat_map(|iteration| &iteration.nodes)
+            .all(|node| node.ply == 1 && node.mv.as_ref().is_some_and(|mv| legal_moves.contains(mv))));
+        assert!(trace.iterations.iter().all(|iteration| deepest_ply(&iteration.nodes) <= 2));
+        assert!(trace.iterations.iter().any(|iteration| deepest_ply(&iteration.nodes) == 2));
+        assert!(trace.to_json().starts_with("{\"iterations\":[{\"depth\":1,\"nodes\":[{\"move\":"));
+    }
+
+    #[test]
+    //Book moves should be played without searching, and positions out of book searched as usual
+    fn book_moves_are_played() {
+        let chess = Chess::new();
+        let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+        //d2d4 with a weight of 1
+        let mut bytes = hash.0.to_be_bytes().to_vec();
+        bytes.extend([0x02, 0xdb, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+
+        let mut engine = Engine::new();
+        engine.set_book(Some(Book::from_bytes(&bytes).expect("book should be valid")));
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move.is_some_and(|best_move| best_move.to_string() == "d2-d4"));
+        assert!(result.nodes == 0);
+
+        let mut after_d4 = chess.clone();
+        after_d4.play_unchecked(&engine.search(&chess, limits, &mut Vec::new()).best_move.unwrap());
+        assert!(engine.search(&after_d4, limits, &mut Vec::new()).nodes > 0);
+        assert!(engine.search(&chess, SearchLimits { infinite: true, ..limits }, &mut Vec::new()).nodes > 0);
+    }
+
+    #[test]
+    //Searches should keep to the repertoire while the game is in it and search as usual once out of it
+    fn repertoire_is_followed() {
+        let chess = Chess::new();
+        let mut engine = Engine::new();
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        engine.set_repertoire(Some(Repertoire::from_text("a2a3 a7a6\nh2h3").expect("lines should be legal")));
+        let best_move = engine.search(&chess, limits, &mut Vec::new()).best_move.expect("position should have a legal move");
+        assert!(["a2-a3", "h2-h3"].contains(&best_move.to_string().as_str()));
+
+        let after_e4 = chess.clone().play(&Move::Normal { role: Role::Pawn, from: Square::E2, capture: None, to: Square::E4, promotion: None })
+            .expect("move should be legal");
+        let mut engine = Engine::new();
+        engine.set_repertoire(Some(Repertoire::from_text("a2a3 a7a6\nh2h3").expect("lines should be legal")));
+        assert!(engine.search(&after_e4, limits, &mut Vec::new()).best_move == search(&after_e4, limits, &mut Vec::new()).best_move);
+    }
+
+    #[test]
+    //A node limit should stop the search after exactly that many nodes, giving the same result every time
+    fn node_limit_is_exact() {
+        let chess = Chess::default();
+        for max_nodes in [5_000, 20_000] {
+            let limits = SearchLimits { max_nodes: Some(max_nodes), ..SearchLimits::default() };
+            let first = search(&chess, limits, &mut Vec::new());
+            let second = search(&chess, limits, &mut Vec::new());
+            assert!(first.nodes == max_nodes);
+            assert!(first.best_move == second.best_move && first.score == second.score && first.pv == second.pv);
+        }
+    }
+
+    #[test]
+    //A repeated quiescence search should be answered by the table with the same score
+    fn quiescence_uses_table() {
+        let setup = Fen::from_ascii("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+
+        let first_score = quiescence_search(&NodePosition::new(chess.clone()), 0, NEG_INFINITY, INFINITY, &mut context);
+        let first_nodes = context.nodes;
+        let second_score = quiescence_search(&NodePosition::new(chess.clone()), 0, NEG_INFINITY, INFINITY, &mut context);
+        assert!(first_score == second_score);
+        assert!(context.nodes - first_nodes == 1);
+    }
+
+    #[test]
+    //Searches reaching the maximum ply should stop and return the static evaluation
+    fn max_ply_is_enforced() {
+        let setup = Fen::from_ascii("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 5 4".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+
+        assert!(nega_max(&NodePosition::new(chess.clone()), 6, MAX_PLY, NEG_INFINITY, INFINITY, None, &mut context) == static_evaluation(&NodePosition::new(chess.clone()), &PieceSquareEvaluator, &EvalParams::default(), None, &mut PawnTable::new()));
+        assert!(quiescence_search(&NodePosition::new(chess.clone()), MAX_PLY, NEG_INFINITY, INFINITY, &mut context) == static_evaluation(&NodePosition::new(chess.clone()), &PieceSquareEvaluator, &EvalParams::default(), None, &mut PawnTable::new()));
+        assert!(context.nodes == 2);
+        assert!(context.seldepth == MAX_PLY);
+    }
+
+    #[test]
+    //Cutoffs by quiet moves should be remembered as answers to the move before them
+    fn countermoves_are_recorded() {
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+        let e4 = Move::Normal { role: Role::Pawn, from: Square::E2, capture: None, to: Square::E4, promotion: None };
+        let mut chess = Chess::new();
+        chess.play_unchecked(&e4);
+        nega_max(&NodePosition::new(chess.clone()), 4, 1, NEG_INFINITY, INFINITY, Some(&e4), &mut context);
+
+        let countermoves: Vec<&Move> = context.countermoves.iter().flatten().collect();
+        assert!(!countermoves.is_empty());
+        assert!(countermoves.iter().all(|m| !m.is_capture() && !m.is_promotion()));
+    }
+
+    #[test]
+    //A hopeless position where every move is quiet should still get a real score
+    fn futility_keeps_a_score() {
+        let setup = Fen::from_ascii("4k3/8/8/8/8/8/3QQ3/4K3 b - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+
+        for depth in 1..=FUTILITY_DEPTH {
+            let score = nega_max(&NodePosition::new(chess.clone()), depth, 1, -500, 500, None, &mut context);
+            assert!(score > NEG_INFINITY && score <= -500);
+        }
+    }
+
+    #[test]
+    //Scores outside the window should come back as found rather than clamped to it
+    fn fail_soft_scores() {
+        let setup = Fen::from_ascii("k7/8/8/8/8/8/3QQ3/4K3 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = NodePosition::new(Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid"));
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+        let static_eval = context.evaluate(&chess);
+
+        assert!(quiescence_search(&chess, 1, -100, 100, &mut context) == static_eval);
+        context.transposition_table.clear();
+        let score = quiescence_search(&chess.play(&chess.legal_moves()[0]), 1, -100, 100, &mut context);
+        assert!(score < -100 && score > -static_eval - 1_000);
+        context.transposition_table.clear();
+        assert!(nega_max(&chess, 2, 1, -100, 100, None, &mut context) > 100);
+    }
+
+    #[test]
+    //A side far ahead near the horizon should fail high without searching any moves
+    fn reverse_futility_cuts_off() {
+        let setup = Fen::from_ascii("k7/8/8/8/8/8/3QQ3/4K3 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+
+        let score = nega_max(&NodePosition::new(chess.clone()), REVERSE_FUTILITY_DEPTH, 2, -100, 100, None, &mut context);
+        assert!(score >= 100);
+        assert!(context.nodes == 1);
+
+        //Deeper searches still look at the moves, two plies deeper since one is lost without a table move
+        nega_max(&NodePosition::new(chess.clone()), REVERSE_FUTILITY_DEPTH + 2, 2, -100, 100, None, &mut context);
+        assert!(context.nodes > 2);
+    }
+
+    #[test]
+    //Only the first few quiet moves should be searched near the horizon, but all of them further away
+    fn late_moves_are_pruned() {
+        let chess = Chess::new();
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+
+        //Each searched child counts a node and another for its quiescence search, which has no captures here
+        nega_max(&NodePosition::new(chess.clone()), 1, 2, NEG_INFINITY, INFINITY, None, &mut context);
+        assert!(context.nodes == 1 + 2 * 4);
+
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+        nega_max(&NodePosition::new(chess.clone()), LATE_MOVE_PRUNING_DEPTH + 1, 2, NEG_INFINITY, INFINITY, None, &mut context);
+        assert!(context.nodes > 1 + 2 * 20);
+    }
+
+    #[test]
+    //Deep nodes without a table move should be searched and stored a ply shallower
+    fn reduction_without_table_move() {
+        let chess = Chess::new();
+        let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+
+        nega_max(&NodePosition::new(chess.clone()), INTERNAL_ITERATIVE_REDUCTION_DEPTH, 2, NEG_INFINITY, INFINITY, None, &mut context);
+        let entry = context.transposition_table.get(hash.0 as usize & TABLE_INDEX_MASK);
+        assert!(entry.depth() == INTERNAL_ITERATIVE_REDUCTION_DEPTH - 1);
+
+        //With a table move from that search the next one goes to full depth
+        nega_max(&NodePosition::new(chess.clone()), INTERNAL_ITERATIVE_REDUCTION_DEPTH, 2, NEG_INFINITY, INFINITY, None, &mut context);
+        let entry = context.transposition_table.get(hash.0 as usize & TABLE_INDEX_MASK);
+        assert!(entry.depth() == INTERNAL_ITERATIVE_REDUCTION_DEPTH);
+    }
+
+    #[test]
+    //Every newly filled table entry should be counted once
+    fn hashfull_counts_entries() {
+        let mut previously_seen_hashes = Vec::new();
+        let mut context = SearchContext::new(&mut previously_seen_hashes, None, Arc::new(PieceSquareEvaluator), Arc::new(TranspositionTable::new()));
+        nega_max(&NodePosition::new(Chess::new()), 3, 0, NEG_INFINITY, INFINITY, None, &mut context);
+
+        let filled = (0..TRANSPOSITION_TABLE_LENGTH).filter(|&index| context.transposition_table.get(index).flag != TranspositionTableFlag::None).count();
+        assert!(filled > 0);
+        assert!(context.filled_entries == filled);
+        assert!(context.hashfull() == filled * 1000 / TRANSPOSITION_TABLE_LENGTH);
+    }
+
+    #[test]
+    //Variants should be searched by their own rules, including ones won by the side to move
+    fn variants_are_searched() {
+        use shakmaty::variant::{Atomic, Variant, VariantPosition};
+
+        //Capturing next to the king blows it up
+        let setup = Fen::from_ascii("k7/1p6/8/8/8/8/8/KR6 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let atomic = Atomic::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let result = search(&atomic, SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(result.best_move.as_ref().is_some_and(|best_move| best_move.to_string() == "Rb1xb7"));
+        assert!(result.mate_in() == Some(1));
+
+        //Antichess is won by losing every piece, which the rook does by moving where it has to be taken
+        let setup = Fen::from_ascii("r7/8/8/8/8/8/8/7R w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let antichess = VariantPosition::from_setup(Variant::Antichess, setup, CastlingMode::Standard).expect("position should be valid");
+        let result = search(&antichess, SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(result.best_move.as_ref().is_some_and(|best_move| antichess.is_legal(best_move)));
+        assert!(result.mate_in() == Some(1));
+    }
+
+    #[test]
+    //Chess960 castling should be searched like any other move
+    fn chess960_castling() {
+        let setup = Fen::from_ascii("r3k3/8/8/8/8/8/8/4K3 b q - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Chess960).expect("position should be valid");
+        let best_move = best_move_with_depth(&chess, 2);
+        assert!(matches!(best_move, Move::Castle { king: Square::E8, rook: Square::A8 }));
+        assert!(best_move.to_uci(CastlingMode::Chess960).to_string() == "e8a8");
+    }
+
+    #[test]
+    //Crazyhouse drops should be searched, so a drop that mates is found
+    fn crazyhouse_drops() {
+        use shakmaty::variant::Crazyhouse;
+
+        let setup = Fen::from_ascii("7k/6pp/8/8/8/8/8/K7[R] w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let crazyhouse = Crazyhouse::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let result = search(&crazyhouse, SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(matches!(result.best_move, Some(Move::Put { role: Role::Rook, .. })));
+        assert!(result.mate_in() == Some(1));
+    }
+
+    #[test]
+    //The search should follow whatever evaluation it is given
+    fn custom_evaluator() {
+        struct KnightEvaluator;
+
+        impl Evaluator for KnightEvaluator {
+            fn evaluate(&self, chess: &Chess) -> i32 {
+                chess.board().knights().intersect(chess.board().white()).count() as i32 * 1000
+            }
+        }
+
+        let setup = Fen::from_ascii("8/P6p/8/8/8/8/k6P/4K3 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let limits = SearchLimits { max_depth: Some(1), ..SearchLimits::default() };
+
+        let result = Engine::new().search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move.is_some_and(|mv| mv.promotion() == Some(Role::Queen)));
+
+        let mut engine = Engine::with_evaluator(KnightEvaluator);
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move.is_some_and(|mv| mv.promotion() == Some(Role::Knight)));
+        assert!(result.score == 1000);
+    }
+
+    #[test]
+    //Positive contempt should avoid a repetition and negative contempt should seek it out
+    fn contempt() {
+        let chess = Chess::default();
+        let knight_move = Move::Normal { role: Role::Knight, from: Square::G1, capture: None, to: Square::F3, promotion: None };
+        let mut after_knight_move = chess.clone();
+        after_knight_move.play_unchecked(&knight_move);
+        let hash: Zobrist64 = after_knight_move.zobrist_hash(EnPassantMode::Legal);
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+
+        let mut engine = Engine::new();
+        engine.set_contempt(-500);
+        let result = engine.search(&chess, limits, &mut vec![hash.0]);
+        assert!(result.best_move == Some(knight_move.clone()));
+        assert!(result.score == 500);
+
+        let mut engine = Engine::new();
+        engine.set_contempt(500);
+        let result = engine.search(&chess, limits, &mut vec![hash.0]);
+        assert!(result.best_move.as_ref().is_some_and(|mv| *mv != knight_move));
+    }
+
+    #[test]
+    //Weakened engines should stay within their caps but still never miss a mate
+    fn limited_strength() {
+        let setup = Fen::from_ascii("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let mut engine = Engine::new();
+        engine.set_skill_level(0);
+        for _ in 0..5 {
+            let result = engine.search(&chess, SearchLimits::default(), &mut Vec::new());
+            assert!(result.depth == 1 && result.mate_in() == Some(1));
+        }
+
+        let mut engine = Engine::new();
+        engine.set_elo_limit(Some(strength::MIN_ELO));
+        let result = engine.search(&Chess::default(), SearchLimits::default(), &mut Vec::new());
+        assert!(result.nodes <= 50 && result.best_move.is_some());
+    }
+
+    #[test]
+    //Candidates should come best first with probabilities that add up to 1
+    fn candidate_moves() {
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let candidates = find_candidate_moves(&Chess::default(), limits, 50.0);
+        assert!(candidates.len() == 4);
+        assert!(candidates.windows(2).all(|pair| pair[0].score >= pair[1].score && pair[0].probability >= pair[1].probability));
+        assert!((candidates.iter().map(|candidate| candidate.probability).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(candidates.iter().all(|candidate| candidate.pv.first() == Some(&candidate.mv)));
+        assert!(candidates[0].mv == search(&Chess::default(), limits, &mut Vec::new()).best_move.expect("there are legal moves"));
+
+        let setup = Fen::from_ascii("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let candidates = find_candidate_moves(&chess, limits, 0.0);
+        assert!(candidates[0].probability == 1.0 && candidates[1..].iter().all(|candidate| candidate.probability == 0.0));
+    }
+
+    #[test]
+    //Every legal move should be scored, best first, with the best matching a normal search
+    fn all_root_moves() {
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let scored_moves = evaluate_all_root_moves(&Chess::default(), limits);
+        assert!(scored_moves.len() == 20);
+        assert!(scored_moves.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(scored_moves.iter().all(|scored_move| scored_move.pv.first() == Some(&scored_move.mv)));
+        assert!(scored_moves[0].mv == search(&Chess::default(), limits, &mut Vec::new()).best_move.expect("there are legal moves"));
+
+        //Only the mate is scored as one
+        let setup = Fen::from_ascii("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let scored_moves = evaluate_all_root_moves(&chess, limits);
+        let mate = Move::Normal { role: Role::Rook, from: Square::D1, capture: None, to: Square::D8, promotion: None };
+        assert!(scored_moves.len() == chess.legal_moves().len() && scored_moves[0].mv == mate);
+        assert!(scored_moves[0].score > REALLY_BIG_CHECKMATE_NUMBER && scored_moves[1].score < REALLY_BIG_CHECKMATE_NUMBER);
+    }
+    #[test]
+    //Restricted searches should only play the allowed moves, and ignore restrictions to illegal moves
+    fn search_moves() {
+        let setup = Fen::from_ascii("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let mut engine = Engine::new();
+
+        let mate = Move::Normal { role: Role::Rook, from: Square::D1, capture: None, to: Square::D8, promotion: None };
+        let quiet = Move::Normal { role: Role::King, from: Square::A1, capture: None, to: Square::B2, promotion: None };
+        let result = engine.search_moves(&chess, limits, &mut Vec::new(), std::slice::from_ref(&quiet));
+        assert!(result.best_move == Some(quiet.clone()));
+        let result = engine.search_moves(&chess, limits, &mut Vec::new(), &[quiet, mate.clone()]);
+        assert!(result.best_move == Some(mate.clone()));
+
+        let illegal = Move::Normal { role: Role::Rook, from: Square::D1, capture: None, to: Square::H5, promotion: None };
+        let result = engine.search_moves(&chess, limits, &mut Vec::new(), &[illegal]);
+        assert!(result.best_move == Some(mate));
+    }
+
+    #[test]
+    //FENs should be searched as they are, and ones that can't be read or aren't legal positions should say why
+    fn search_from_fen() {
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let result = find_best_move_from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", limits).expect("position should be searched");
+        assert!(result.best_move == Some(Move::Normal { role: Role::Rook, from: Square::A1, capture: None, to: Square::A6, promotion: None }));
+
+        assert!(matches!(find_best_move_from_fen("8/8/8 w - - 0 1", limits), Err(EngineError::InvalidFen(_))));
+        let missing_king = find_best_move_from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1", limits);
+        assert!(missing_king == Err(EngineError::InvalidPosition(PositionErrorKinds::MISSING_KING)));
+        assert!(missing_king.unwrap_err().to_string() == "illegal position: missing king");
+    }
+
+    #[test]
+    //Moves should be written the way people read them, with the PV played out move by move
+    fn san_output() {
+        let setup = Fen::from_ascii("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let result = search(&chess, SearchLimits { max_depth: Some(3), ..SearchLimits::default() }, &mut Vec::new());
+        assert!(result.best_move_san(&chess).map(|san| san.to_string()) == Some("Ra6".to_string()));
+        let pv: Vec<String> = result.pv_san(&chess).iter().map(ToString::to_string).collect();
+        assert!(pv.len() == 3 && pv[0] == "Ra6" && pv[2].ends_with('#'));
+    }
+
+    #[test]
+    //Options should read back as they were applied, starting from the defaults
+    fn engine_options() {
+        let mut engine: Engine = Engine::new();
+        assert!(engine.options() == EngineOptions::default());
+        let options = EngineOptions {
+            max_move_time: Some(Duration::from_secs(5)),
+            move_overhead: Duration::from_millis(300),
+            contempt: 20,
+            skill_level: 7,
+            elo_limit: Some(1_800),
+            threads: 4,
+            deterministic: true,
+            personality: Personality::Solid
+        };
+        engine.set_options(options);
+        assert!(engine.options() == options);
+    }
+
+    #[test]
+    //A personality should weigh the evaluation differently, which balanced doesn't
+    fn personalities() {
+        let setup = Fen::from_ascii("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4".as_bytes()).expect("Fen should be valid").0;
+        let position = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let chess = NodePosition::new(position.clone());
+        let evaluate = |personality: Personality| static_evaluation(&chess, &PieceSquareEvaluator, &personality.eval_params(), None, &mut PawnTable::new());
+        assert!(evaluate(Personality::Balanced) == static_evaluation(&chess, &PieceSquareEvaluator, &EvalParams::default(), None, &mut PawnTable::new()));
+        assert!(evaluate(Personality::Gambit) != evaluate(Personality::Balanced));
+
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        let mut engine: Engine = Engine::new();
+        engine.set_personality(Personality::Aggressive);
+        assert!(engine.search(&position, limits, &mut Vec::new()).nodes != search(&position, limits, &mut Vec::new()).nodes);
+    }
+
+    #[test]
+    //Eval params should change what the search sees, and be kept until a personality replaces them
+    fn eval_params() {
+        let mut engine: Engine = Engine::new();
+        assert!(engine.eval_params() == EvalParams::DEFAULT);
+        let params = EvalParams { piece_squares: 50, ..EvalParams::DEFAULT };
+        engine.set_eval_params(params);
+        assert!(engine.eval_params() == params);
+
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        assert!(engine.search(&Chess::default(), limits, &mut Vec::new()).nodes != search(&Chess::default(), limits, &mut Vec::new()).nodes);
+        engine.set_personality(Personality::Solid);
+        assert!(engine.eval_params() == Personality::Solid.eval_params());
+    }
+
+    #[test]
+    //The engine's own tables given from outside should search the same as without them, and other tables differently
+    fn custom_piece_square_tables() {
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        let search_with = |tables: Option<PieceSquareTables>| {
+            let mut engine: Engine = Engine::new();
+            engine.set_piece_square_tables(tables);
+            engine.search(&Chess::default(), limits, &mut Vec::new())
+        };
+        let own = search_with(None);
+        let default = search_with(Some(PieceSquareTables::DEFAULT));
+        assert!(default.nodes == own.nodes && default.score == own.score);
+
+        let mut tables = PieceSquareTables::DEFAULT;
+        tables.knight = [300; 64];
+        assert!(search_with(Some(tables)).nodes != own.nodes);
+    }
+
+    #[test]
+    //A root move that keeps losing games should be given up for a move that is nearly as good
+    fn experience_bias() {
+        let chess = Chess::new();
+        let limits = SearchLimits { max_depth: Some(5), ..SearchLimits::default() };
+        let mut engine: Engine = Engine::new();
+        let usual = engine.search(&chess, limits, &mut Vec::new());
+        let usual_move = usual.best_move.clone().expect("position should have a legal move");
+
+        engine.set_experience(Some(Experience::new()));
+        let experience = engine.experience_mut().expect("experience should be set");
+        for _ in 0..10 {
+            experience.record(&chess, &usual_move, Outcome::Decisive { winner: Color::Black });
+        }
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move.is_some_and(|best_move| best_move != usual_move));
+        assert!(result.score > usual.score - 60);
+
+        engine.set_experience(None);
+        assert!(engine.search(&chess, limits, &mut Vec::new()).best_move == Some(usual_move));
+    }
+
+    #[test]
+    //A search told to keep the table should pick up where the last one left off, the way a ponderhit does
+    fn kept_table() {
+        let chess = Chess::new();
+        let limits = SearchLimits { max_depth: Some(6), ..SearchLimits::default() };
+        let mut engine: Engine = Engine::new();
+        let expected = engine.search(&chess, limits, &mut Vec::new());
+        engine.keep_table();
+        let result = engine.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move == expected.best_move && result.nodes < expected.nodes / 2);
+    }
+
+    #[test]
+    //A saved table should give a restarted engine the knowledge of the search that filled it, and bad files should be refused
+    fn saved_table() {
+        let chess = Chess::new();
+        let limits = SearchLimits { max_depth: Some(6), ..SearchLimits::default() };
+        let path = std::env::temp_dir().join(format!("hodgey_table_{}.bin", std::process::id()));
+
+        let mut engine: Engine = Engine::new();
+        let expected = engine.search(&chess, limits, &mut Vec::new());
+        engine.save_tt(&path).expect("table should be saved");
+
+        let mut restarted: Engine = Engine::new();
+        restarted.load_tt(&path).expect("table should be loaded");
+        let result = restarted.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move == expected.best_move && result.nodes < expected.nodes / 2);
+        //Only the first search after loading keeps the table
+        assert!(restarted.search(&chess, limits, &mut Vec::new()).nodes == expected.nodes);
+
+        let bytes = std::fs::read(&path).expect("table should be written");
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).expect("file should be written");
+        assert!(restarted.load_tt(&path).is_err());
+        std::fs::write(&path, b"not a table at all").expect("file should be written");
+        assert!(restarted.load_tt(&path).is_err());
+        std::fs::remove_file(&path).expect("file should be removed");
+    }
+
+    #[test]
+    //Deterministic searches should give the same result whatever came before them, with time spent as nodes
+    fn deterministic_search() {
+        let setup = Fen::from_ascii("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4".as_bytes()).expect("Fen should be valid").0;
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        let limits = SearchLimits { move_time: Some(Duration::from_millis(40)), ..SearchLimits::default() };
+
+        let mut fresh: Engine = Engine::new();
+        fresh.set_deterministic(true);
+        let expected = fresh.search(&chess, limits, &mut Vec::new());
+        assert!(expected.nodes == 20_000);
+
+        //A different history of searches, more threads and a slower clock change nothing
+        let mut used: Engine = Engine::new();
+        used.set_deterministic(true);
+        used.set_threads(2);
+        used.set_clock(Arc::new(ManualClock::new()));
+        used.search(&Chess::new(), SearchLimits { max_depth: Some(5), ..SearchLimits::default() }, &mut Vec::new());
+        let result = used.search(&chess, limits, &mut Vec::new());
+        assert!(result.best_move == expected.best_move && result.pv == expected.pv);
+        assert!(result.score == expected.score && result.nodes == expected.nodes && result.depth == expected.depth);
+
+        //Weaker play picks the same move every time
+        let limits = SearchLimits { max_depth: Some(4), ..SearchLimits::default() };
+        fresh.set_skill_level(3);
+        let weak_move = fresh.search(&chess, limits, &mut Vec::new()).best_move;
+        for _ in 0..3 {
+            let mut engine: Engine = Engine::new();
+            engine.set_deterministic(true);
+            engine.set_skill_level(3);
+            assert!(engine.search(&chess, limits, &mut Vec::new()).best_move == weak_move);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,98 @@
+//! Searching for forced mates only.
+//!
+//! Unlike the main search, nothing is evaluated and nothing is pruned on a guess: a line only counts once
+//! every defence has been shown to lose. Mates are looked for one move deeper at a time, so the first one
+//! found is the shortest, and no line is ever searched past the mate distance already being tried.
+
+use shakmaty::{Move, Outcome};
+
+use crate::variant::SearchPosition;
+
+/// Finds a forced mate for the side to move in at most `max_moves` of its moves.
+/// Returns the mating line, with the most stubborn defence, or `None` if there is no such mate.
+pub fn find_mate<P: SearchPosition>(chess: &P, max_moves: u32) -> Option<Vec<Move>> {
+    (1..=max_moves).find_map(|moves| attack(chess, moves))
+}
+
+/// A move that mates within `moves_left` moves of the side to move, followed by the rest of the line.
+fn attack<P: SearchPosition>(chess: &P, moves_left: u32) -> Option<Vec<Move>> {
+    let mut legal_moves = chess.legal_moves();
+    //Forcing moves first, since they are the most likely to mate
+    legal_moves.sort_by_key(|mv| !mv.is_capture());
+
+    for mv in legal_moves {
+        let mut new_chess = chess.clone();
+        new_chess.play_unchecked(&mv);
+
+        match new_chess.outcome() {
+            Some(Outcome::Decisive { winner }) if winner == chess.turn() => return Some(vec![mv]),
+            Some(_) => continue,
+            None if moves_left == 1 => continue,
+            None => if let Some(line) = defend(&new_chess, moves_left - 1) {
+                let mut full_line = vec![mv];
+                full_line.extend(line);
+                return Some(full_line);
+            }
+        }
+    }
+
+    None
+}
+
+/// The longest line after which the side to move is mated within `moves_left` moves of the other side,
+/// `None` if any defence holds out.
+fn defend<P: SearchPosition>(chess: &P, moves_left: u32) -> Option<Vec<Move>> {
+    let mut longest: Option<Vec<Move>> = None;
+
+    for mv in chess.legal_moves() {
+        let mut new_chess = chess.clone();
+        new_chess.play_unchecked(&mv);
+
+        //A defender that wins or draws has escaped
+        if new_chess.outcome().is_some() {
+            return None;
+        }
+        let line = attack(&new_chess, moves_left)?;
+        if longest.as_ref().is_none_or(|longest| line.len() + 1 > longest.len()) {
+            let mut full_line = vec![mv];
+            full_line.extend(line);
+            longest = Some(full_line);
+        }
+    }
+
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, Chess, FromSetup, Position};
+
+    fn position(fen: &str) -> Chess {
+        let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+        Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+    }
+
+    #[test]
+    //Mates should be found at their shortest and only when they are forced
+    fn forced_mates() {
+        let mate_in_one = position("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1");
+        let line = find_mate(&mate_in_one, 3).expect("there is a mate in one");
+        assert!(line.len() == 1);
+
+        //Two rooks mate by rolling the king up the board
+        let mate_in_two = position("7k/8/8/8/8/8/R7/1R5K w - - 0 1");
+        assert!(find_mate(&mate_in_two, 1).is_none());
+        let line = find_mate(&mate_in_two, 2).expect("there is a mate in two");
+        assert!(line.len() == 3);
+        let mut chess = mate_in_two.clone();
+        for mv in &line {
+            chess.play_unchecked(mv);
+        }
+        assert!(chess.is_checkmate());
+
+        assert!(find_mate(&Chess::default(), 2).is_none());
+        assert!(find_mate(&position("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 3).is_none());
+    }
+}
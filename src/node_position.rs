@@ -0,0 +1,187 @@
+use std::ops::Deref;
+
+use shakmaty::zobrist::{Zobrist64, ZobristHash, ZobristValue};
+use shakmaty::{ByColor, CastlingSide, Color, EnPassantMode, File, Move, Position, Role};
+
+use crate::evaluation::{evaluate_piece_sums, piece_square_value, piece_sums};
+use crate::pawn_table::PawnTable;
+
+/// A position along with what the search keeps up to date from what each move changes, instead of working
+/// it out from scratch at every node: its Zobrist hash and the piece square sums of its board. The hash is
+/// always the one shakmaty computes with [`EnPassantMode::Legal`], and debug builds check both after every move.
+#[derive(Clone)]
+pub struct NodePosition<P> {
+    position: P,
+    hash: u64,
+    //The en passant file included in the hash, kept so it doesn't have to be found again for the next move
+    en_passant: Option<File>,
+    piece_sums: ByColor<i32>
+}
+
+impl<P: Position + Clone> NodePosition<P> {
+    pub fn new(position: P) -> NodePosition<P> {
+        let hash: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+        let en_passant = en_passant_file(&position);
+        let piece_sums = piece_sums(position.board());
+        NodePosition {
+            position,
+            hash: hash.0,
+            en_passant,
+            piece_sums
+        }
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The evaluation of the board from the perspective of white, the same as
+    /// [`evaluate_board`](crate::evaluation::evaluate_board), with the pawn structure from `pawn_table`.
+    pub fn board_evaluation(&self, pawn_table: &mut PawnTable) -> i32 {
+        evaluate_piece_sums(self.position.board(), self.piece_sums, &pawn_table.probe(self.position.board()))
+    }
+
+    /// The position after `m`, which must be legal.
+    pub fn play(&self, m: &Move) -> NodePosition<P> {
+        let mut position = self.position.clone();
+        position.play_unchecked(m);
+        self.child(position)
+    }
+
+    /// Hashes `position`, which must be this position after a legal move. Moves can be played on the
+    /// position alone first, so the hash is only worked out for the ones that are searched.
+    pub fn child(&self, position: P) -> NodePosition<P> {
+        let en_passant = en_passant_file(&position);
+
+        let mut piece_sums = self.piece_sums;
+        let mut hash = Zobrist64(self.hash) ^ Zobrist64::zobrist_for_white_turn();
+        hash ^= board_difference(&self.position, &position, &mut piece_sums);
+        hash ^= state_difference(&self.position, &position);
+        if let Some(file) = self.en_passant {
+            hash ^= Zobrist64::zobrist_for_en_passant_file(file);
+        }
+        if let Some(file) = en_passant {
+            hash ^= Zobrist64::zobrist_for_en_passant_file(file);
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            let full_hash: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+            assert!(hash == full_hash, "incremental hash differs from the full hash");
+            assert!(piece_sums == crate::evaluation::piece_sums(position.board()), "incremental piece sums differ from the full sums");
+        }
+
+        NodePosition {
+            position,
+            hash: hash.0,
+            en_passant,
+            piece_sums
+        }
+    }
+}
+
+impl<P> Deref for NodePosition<P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        &self.position
+    }
+}
+
+/// The file of the en passant square the hash includes. Whether the capture is legal is only worth
+/// checking right after a double pawn push, which is the only time there can be one.
+fn en_passant_file<P: Position>(position: &P) -> Option<File> {
+    position.ep_square(EnPassantMode::Always)?;
+    position.ep_square(EnPassantMode::Legal).map(|square| square.file())
+}
+
+/// The hash of the pieces that appeared or disappeared between `before` and `after`, which is only the few
+/// squares a move touches, or a few more for variants such as atomic. Their values are added to or taken
+/// off `piece_sums`, except for kings, which aren't part of the sums.
+fn board_difference<P: Position>(before: &P, after: &P, piece_sums: &mut ByColor<i32>) -> Zobrist64 {
+    let mut hash = Zobrist64::default();
+    for role in Role::ALL {
+        for color in Color::ALL {
+            let piece = role.of(color);
+            let after_pieces = after.board().by_piece(piece);
+            for square in before.board().by_piece(piece) ^ after_pieces {
+                hash ^= Zobrist64::zobrist_for_piece(square, piece);
+                if role != Role::King {
+                    let value = piece_square_value(piece, square);
+                    *piece_sums.get_mut(color) += if after_pieces.contains(square) {value} else {-value};
+                }
+            }
+        }
+    }
+    for square in before.promoted() ^ after.promoted() {
+        hash ^= Zobrist64::zobrist_for_promoted(square);
+    }
+    hash
+}
+
+/// The hash of the castling rights, pockets and remaining checks that changed between `before` and `after`.
+fn state_difference<P: Position>(before: &P, after: &P) -> Zobrist64 {
+    let mut hash = Zobrist64::default();
+
+    for color in Color::ALL {
+        for side in CastlingSide::ALL {
+            if before.castles().has(color, side) != after.castles().has(color, side) {
+                hash ^= Zobrist64::zobrist_for_castling_right(color, side);
+            }
+        }
+    }
+
+    if let (Some(before_pockets), Some(after_pockets)) = (before.pockets(), after.pockets()) {
+        for color in Color::ALL {
+            for role in Role::ALL {
+                let (before_count, after_count) = (*before_pockets.get(color).get(role), *after_pockets.get(color).get(role));
+                if before_count != after_count {
+                    hash ^= Zobrist64::zobrist_for_pocket(color, role, before_count);
+                    hash ^= Zobrist64::zobrist_for_pocket(color, role, after_count);
+                }
+            }
+        }
+    }
+
+    if let (Some(before_checks), Some(after_checks)) = (before.remaining_checks(), after.remaining_checks()) {
+        for color in Color::ALL {
+            let (before_remaining, after_remaining) = (*before_checks.get(color), *after_checks.get(color));
+            if before_remaining != after_remaining {
+                hash ^= Zobrist64::zobrist_for_remaining_checks(color, before_remaining);
+                hash ^= Zobrist64::zobrist_for_remaining_checks(color, after_remaining);
+            }
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::variant::{Variant, VariantPosition};
+    use crate::random::XorShift64;
+
+    #[test]
+    //Hashes and evaluations kept up to date move by move should match the full ones in every variant,
+    //through castling, en passant, promotions, drops, explosions and checks
+    fn incremental_updates_match() {
+        let mut random = XorShift64::new(7);
+        let mut pawn_table = PawnTable::new();
+        for variant in Variant::ALL {
+            for _ in 0..20 {
+                let mut position = NodePosition::new(VariantPosition::new(variant));
+                for _ in 0..120 {
+                    let moves = position.legal_moves();
+                    if moves.is_empty() || position.is_variant_end() {
+                        break;
+                    }
+                    position = position.play(&moves[(random.next() % moves.len() as u64) as usize]);
+                    let full_hash: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+                    assert!(position.hash() == full_hash.0);
+                    assert!(position.board_evaluation(&mut pawn_table) == crate::evaluation::evaluate_board(position.board()));
+                }
+            }
+        }
+    }
+}
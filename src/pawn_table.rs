@@ -0,0 +1,71 @@
+use shakmaty::{Bitboard, Board, Color};
+
+use crate::evaluation::{pawn_structure, PawnStructure};
+
+const TABLE_LENGTH: usize = 1 << 14;
+
+#[derive(Clone, Copy)]
+struct PawnEntry {
+    white_pawns: Bitboard,
+    black_pawns: Bitboard,
+    structure: PawnStructure
+}
+
+/// Pawn structures of recently evaluated boards, keyed by where the pawns are. The pawns stay put through most
+/// moves, so most evaluations find their structure here. Entries keep the pawns themselves rather than a hash of
+/// them, so a lookup can't be fooled by a collision.
+pub struct PawnTable {
+    entries: Box<[PawnEntry]>
+}
+
+impl PawnTable {
+    pub fn new() -> PawnTable {
+        //Empty entries are right for boards without pawns, whichever slot those land in
+        let empty = PawnEntry {
+            white_pawns: Bitboard::EMPTY,
+            black_pawns: Bitboard::EMPTY,
+            structure: PawnStructure::default()
+        };
+        PawnTable {
+            entries: vec![empty; TABLE_LENGTH].into_boxed_slice()
+        }
+    }
+
+    /// The pawn structure of `board`, worked out and stored if it isn't in the table.
+    pub fn probe(&mut self, board: &Board) -> PawnStructure {
+        let white_pawns = board.by_piece(Color::White.pawn());
+        let black_pawns = board.by_piece(Color::Black.pawn());
+        let entry = &mut self.entries[pawn_index(white_pawns, black_pawns)];
+
+        if entry.white_pawns != white_pawns || entry.black_pawns != black_pawns {
+            *entry = PawnEntry {
+                white_pawns,
+                black_pawns,
+                structure: pawn_structure(board)
+            };
+        }
+        entry.structure
+    }
+}
+
+//Mixes both sides' pawns into an index with a multiplicative hash, keeping the top bits
+fn pawn_index(white_pawns: Bitboard, black_pawns: Bitboard) -> usize {
+    let key = (white_pawns.0 ^ black_pawns.0.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    (key >> (64 - TABLE_LENGTH.trailing_zeros())) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //Lookups should give the same structure as working it out, whether or not the slot held other pawns
+    fn stored_structures() {
+        let mut table = PawnTable::new();
+        for fen in ["4k3/6p1/7p/8/8/p1P5/2P5/4K3", "4k3/8/8/8/8/8/8/4K3", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"] {
+            let board = Board::from_ascii_board_fen(fen.as_bytes()).expect("Fen should be valid");
+            assert!(table.probe(&board) == pawn_structure(&board));
+            assert!(table.probe(&board) == pawn_structure(&board));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Move generation counts for checking the engine's move handling.
+//!
+//! `perft` counts the leaf nodes of the full move tree to a fixed depth. The counts are well known for
+//! many positions, so any difference points to a position or move handling bug.
+
+use shakmaty::{Move, Position};
+
+/// Counts the positions reached by playing every sequence of `depth` legal moves from `position`.
+pub fn perft<P: Position + Clone>(position: &P, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = position.legal_moves();
+    //The last ply only needs the number of moves, not the positions they lead to
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves.iter().map(|m| {
+        let mut new_position = position.clone();
+        new_position.play_unchecked(m);
+        perft(&new_position, depth - 1)
+    }).sum()
+}
+
+/// [`perft`] split up by the first move, for narrowing down which move a wrong count comes from.
+pub fn divide<P: Position + Clone>(position: &P, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    position.legal_moves().into_iter().map(|m| {
+        let mut new_position = position.clone();
+        new_position.play_unchecked(&m);
+        let nodes = perft(&new_position, depth - 1);
+        (m, nodes)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::variant::Crazyhouse;
+    use shakmaty::{CastlingMode, Chess, FromSetup};
+
+    #[test]
+    //Counts should match the well known values for the starting position and a few tricky ones
+    fn known_counts() {
+        let chess = Chess::new();
+        assert!([1, 20, 400, 8_902, 197_281].into_iter().enumerate().all(|(depth, nodes)| perft(&chess, depth as u32) == nodes));
+
+        //Kiwipete, full of castling, en passant and promotions
+        let setup = Fen::from_ascii("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let kiwipete = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        assert!(perft(&kiwipete, 3) == 97_862);
+
+        //A Chess960 position where the king and rook start next to each other
+        let setup = Fen::from_ascii("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9".as_bytes()).expect("Fen should be valid").0;
+        let chess960 = Chess::from_setup(setup, CastlingMode::Chess960).expect("position should be valid");
+        assert!(perft(&chess960, 3) == 12_189);
+
+        //Drops only start once a piece has been captured
+        assert!(perft(&Crazyhouse::default(), 4) == 197_281);
+        assert!(perft(&Crazyhouse::default(), 5) == 4_888_832);
+    }
+
+    #[test]
+    //The divided counts should add up to the full count
+    fn divide_sums_to_perft() {
+        let chess = Chess::new();
+        let divided = divide(&chess, 3);
+        assert!(divided.len() == 20);
+        assert!(divided.iter().map(|&(_, nodes)| nodes).sum::<u64>() == perft(&chess, 3));
+        assert!(divide(&chess, 0).is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+//! Named playing styles, set with [`crate::Engine::set_personality`] or the `Personality` UCI option.
+//!
+//! A personality weighs the terms of the engine's own evaluation differently (see [`EvalParams`]) and adds
+//! to the engine's contempt. Aggressive and gambit players value active pieces and attacks on the king over
+//! material and avoid trades and draws, while a solid player keeps its pawns together and trades down when ahead.
+
+use crate::evaluator::EvalParams;
+
+/// A playing style, see the [module docs](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Personality {
+    /// The engine's usual evaluation.
+    #[default]
+    Balanced,
+    /// Values piece activity and keeps pieces on the board.
+    Aggressive,
+    /// Values a sound pawn structure and trades down when ahead.
+    Solid,
+    /// Gives up material for activity and plays for a win.
+    Gambit
+}
+
+impl Personality {
+    pub const ALL: [Personality; 4] = [Personality::Balanced, Personality::Aggressive, Personality::Solid, Personality::Gambit];
+
+    /// The lowercase name used by the UCI option.
+    pub fn name(self) -> &'static str {
+        match self {
+            Personality::Balanced => "balanced",
+            Personality::Aggressive => "aggressive",
+            Personality::Solid => "solid",
+            Personality::Gambit => "gambit"
+        }
+    }
+
+    /// The personality called `name`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Personality> {
+        Personality::ALL.into_iter().find(|personality| personality.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The evaluation's params for the personality, weighing its terms.
+    pub fn eval_params(self) -> EvalParams {
+        match self {
+            Personality::Balanced => EvalParams::default(),
+            Personality::Aggressive => EvalParams { piece_squares: 130, trade_bonus: 50, pawn_structure: 90, king_attack: 130, ..EvalParams::DEFAULT },
+            Personality::Solid => EvalParams { piece_squares: 90, trade_bonus: 130, pawn_structure: 130, king_attack: 85, ..EvalParams::DEFAULT },
+            Personality::Gambit => EvalParams { material: 85, piece_squares: 140, trade_bonus: 40, pawn_structure: 80, king_attack: 140, ..EvalParams::DEFAULT }
+        }
+    }
+
+    /// Centipawns added to the engine's contempt, so styles that play for a win avoid draws.
+    pub fn contempt(self) -> i32 {
+        match self {
+            Personality::Balanced | Personality::Solid => 0,
+            Personality::Aggressive => 20,
+            Personality::Gambit => 30
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //Every personality should read back from its name, and only balanced should leave the evaluation alone
+    fn names_and_params() {
+        for personality in Personality::ALL {
+            assert!(Personality::from_name(&personality.name().to_uppercase()) == Some(personality));
+            assert!((personality.eval_params() == EvalParams::default()) == (personality == Personality::Balanced));
+        }
+        assert!(Personality::from_name("reckless").is_none());
+    }
+}
@@ -0,0 +1,376 @@
+//! Reading and writing games as PGN, only built with the `pgn` feature, which is on by default.
+//!
+//! [`PgnReader`] streams games one at a time out of a file of any size. Variations and NAGs are skipped,
+//! while comments are kept with the move they follow. Games from a set up position need the FEN tag,
+//! and Chess960 games are recognised by their Variant tag. Writing a [`PgnGame`] with `to_string` gives
+//! standard PGN with the movetext wrapped at 80 characters.
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+use shakmaty::fen::Fen;
+use shakmaty::san::{San, SanPlus};
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Move, Outcome, Position};
+
+const PGN_LINE_LENGTH: usize = 80;
+
+/// A move of a game along with the comment that follows it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub comment: Option<String>
+}
+
+/// A game with its tags, in the order they are written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start: Chess,
+    pub moves: Vec<PgnMove>,
+    /// A comment after the last move, such as why the game ended.
+    pub final_comment: Option<String>,
+    /// `None` for games that are unfinished or whose result is unknown.
+    pub outcome: Option<Outcome>
+}
+
+impl PgnGame {
+    /// A game without tags or moves starting from `start`.
+    pub fn new(start: Chess) -> PgnGame {
+        PgnGame {
+            tags: Vec::new(),
+            start,
+            moves: Vec::new(),
+            final_comment: None,
+            outcome: None
+        }
+    }
+
+    /// The value of the tag called `name`, if there is one.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the tag called `name`, replacing its value if it is already there.
+    pub fn set_tag(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value,
+            None => self.tags.push((name.to_string(), value))
+        }
+    }
+
+    /// Appends `mv` without a comment.
+    pub fn push(&mut self, mv: Move) {
+        self.moves.push(PgnMove { mv, comment: None });
+    }
+
+    /// The moves without their comments.
+    pub fn move_list(&self) -> Vec<Move> {
+        self.moves.iter().map(|pgn_move| pgn_move.mv.clone()).collect()
+    }
+
+    /// The position after the last move.
+    pub fn end(&self) -> Chess {
+        let mut chess = self.start.clone();
+        for pgn_move in &self.moves {
+            chess.play_unchecked(&pgn_move.mv);
+        }
+        chess
+    }
+
+    fn result(&self) -> String {
+        self.outcome.map_or_else(|| "*".to_string(), |outcome| outcome.to_string())
+    }
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        //Without the starting position the moves can't be read back
+        if self.start != Chess::default() && self.tag("FEN").is_none() {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", Fen::from_position(self.start.clone(), EnPassantMode::Legal))?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        let mut chess = self.start.clone();
+        for (index, pgn_move) in self.moves.iter().enumerate() {
+            let move_number = chess.fullmoves();
+            if chess.turn().is_white() {
+                tokens.push(format!("{}.", move_number));
+            }
+            else if index == 0 {
+                tokens.push(format!("{}...", move_number));
+            }
+
+            tokens.push(SanPlus::from_move_and_play_unchecked(&mut chess, &pgn_move.mv).to_string());
+            if let Some(comment) = &pgn_move.comment {
+                tokens.push(format!("{{{}}}", comment));
+            }
+        }
+        if let Some(comment) = &self.final_comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        tokens.push(self.result());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > PGN_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            }
+            else if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+            line_length += token.len();
+            write!(f, "{}", token)?;
+        }
+        writeln!(f)?;
+        writeln!(f)
+    }
+}
+
+/// Reads the games of a PGN file one at a time.
+pub struct PgnReader<R> {
+    reader: R,
+    //A tag line read while looking for the end of the game before it
+    pending_line: Option<String>
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            pending_line: None
+        }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        if let Some(line) = self.pending_line.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        Ok((self.reader.read_line(&mut line)? > 0).then_some(line))
+    }
+
+    fn read_game(&mut self) -> io::Result<Option<PgnGame>> {
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+
+        while let Some(line) = self.next_line()? {
+            let trimmed = line.trim();
+            //Lines starting with % are escaped from PGN altogether
+            if trimmed.is_empty() || trimmed.starts_with('%') {
+                continue;
+            }
+
+            if trimmed.starts_with('[') {
+                //Games without a result end where the next game's tags start
+                if !movetext.trim().is_empty() {
+                    self.pending_line = Some(line);
+                    break;
+                }
+                tags.push(parse_tag(trimmed)?);
+                continue;
+            }
+
+            movetext.push_str(&line);
+            movetext.push('\n');
+            if tokenize(&movetext).iter().any(|token| matches!(token, Token::Result(_))) {
+                break;
+            }
+        }
+
+        if tags.is_empty() && movetext.trim().is_empty() {
+            return Ok(None);
+        }
+        parse_game(tags, &movetext).map(Some)
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = io::Result<PgnGame>;
+
+    fn next(&mut self) -> Option<io::Result<PgnGame>> {
+        self.read_game().transpose()
+    }
+}
+
+/// Reads a single game, such as one pasted in by a user.
+pub fn parse_pgn(pgn: &str) -> io::Result<PgnGame> {
+    PgnReader::new(pgn.as_bytes()).next().unwrap_or_else(|| Err(invalid_data("no game found".to_string())))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_tag(line: &str) -> io::Result<(String, String)> {
+    let inner = line.trim_start_matches('[').trim_end().trim_end_matches(']');
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(|| invalid_data(format!("invalid tag {}", line)))?;
+    let value = value.trim().trim_start_matches('"').trim_end_matches('"');
+    Ok((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+fn parse_game(tags: Vec<(String, String)>, movetext: &str) -> io::Result<PgnGame> {
+    let mut game = PgnGame::new(Chess::default());
+    game.tags = tags;
+
+    if let Some(fen) = game.tag("FEN") {
+        let chess960 = game.tag("Variant").is_some_and(|variant| variant.eq_ignore_ascii_case("chess960"));
+        let setup = Fen::from_ascii(fen.as_bytes()).map_err(|error| invalid_data(format!("invalid FEN {}: {}", fen, error)))?;
+        game.start = setup.into_position(CastlingMode::from_chess960(chess960))
+            .map_err(|error| invalid_data(format!("invalid position {}: {}", fen, error)))?;
+    }
+
+    let mut chess = game.start.clone();
+    for token in tokenize(movetext) {
+        match token {
+            Token::Move(san) => {
+                let mv = San::from_ascii(san.trim_end_matches(['+', '#', '!', '?']).as_bytes()).ok()
+                    .and_then(|san| san.to_move(&chess).ok())
+                    .ok_or_else(|| invalid_data(format!("illegal move {} after {} moves", san, game.moves.len())))?;
+                chess.play_unchecked(&mv);
+                game.push(mv);
+            },
+            Token::Comment(comment) => {
+                let comment = comment.trim().to_string();
+                match game.moves.last_mut() {
+                    Some(last) if last.comment.is_none() => last.comment = Some(comment),
+                    Some(last) => {
+                        let joined = format!("{} {}", last.comment.take().unwrap_or_default(), comment);
+                        last.comment = Some(joined);
+                    },
+                    //Comments before the first move describe the game rather than a move
+                    None => continue
+                }
+            },
+            Token::Result(result) => {
+                game.outcome = Outcome::from_ascii(result.as_bytes()).ok();
+                break;
+            }
+        }
+    }
+
+    Ok(game)
+}
+
+enum Token {
+    Move(String),
+    Comment(String),
+    Result(String)
+}
+
+/// Splits movetext into moves, comments and the result, dropping move numbers, NAGs and variations.
+fn tokenize(movetext: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Comment(chars.by_ref().take_while(|&c| c != '}').collect())),
+            ';' => tokens.push(Token::Comment(chars.by_ref().take_while(|&c| c != '\n').collect())),
+            '(' => skip_variation(&mut chars),
+            c if c.is_whitespace() || c == ')' => continue,
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "{};()".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&word.as_str()) {
+                    tokens.push(Token::Result(word));
+                    continue;
+                }
+                //Move numbers may be written right before the move, as in "1.e4" or "12...Nf6"
+                let word = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !word.is_empty() && !word.starts_with('$') {
+                    tokens.push(Token::Move(word.to_string()));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+//Variations may hold comments and variations of their own
+fn skip_variation(chars: &mut impl Iterator<Item = char>) {
+    let mut depth = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '{' => while chars.next().is_some_and(|c| c != '}') {},
+            _ => {}
+        }
+        if depth == 0 {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::Color;
+
+    const GAMES: &str = r#"[Event "Casual"]
+[White "A \"Quoted\" Name"]
+[Result "1-0"]
+
+1. e4 {Best by test} e5 2. Bc4 (2. Nf3 Nc6 {the usual} (2... d6) 3. Bb5) 2... Nc6 3. Qh5 $4 Nf6?? ; too late
+4. Qxf7# 1-0
+
+[Event "Unfinished"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1"]
+
+1... Kd7 2.e4
+[Event "Next"]
+1. d4 *
+"#;
+
+    #[test]
+    //Games should be read one at a time with their tags, moves and comments
+    fn reading() {
+        let games: Vec<PgnGame> = PgnReader::new(GAMES.as_bytes()).collect::<io::Result<_>>().expect("games should be valid");
+        assert!(games.len() == 3);
+
+        assert!(games[0].tag("White") == Some("A \"Quoted\" Name"));
+        assert!(games[0].moves.len() == 7);
+        assert!(games[0].moves[0].comment.as_deref() == Some("Best by test"));
+        assert!(games[0].moves[5].comment.as_deref() == Some("too late"));
+        assert!(games[0].outcome == Some(Outcome::Decisive { winner: Color::White }));
+        assert!(games[0].end().is_checkmate());
+
+        assert!(games[1].start.turn() == Color::Black && games[1].moves.len() == 2 && games[1].outcome.is_none());
+        assert!(games[2].tag("Event") == Some("Next") && games[2].moves.len() == 1);
+
+        assert!(parse_pgn("1. e4 e4 *").is_err());
+        assert!(parse_pgn("").is_err());
+    }
+
+    #[test]
+    //Written games should read back the same
+    fn round_trip() {
+        for game in PgnReader::new(GAMES.as_bytes()) {
+            let game = game.expect("game should be valid");
+            let written = game.to_string();
+            assert!(written.lines().all(|line| line.len() <= PGN_LINE_LENGTH));
+            let read_back = parse_pgn(&written).expect("written game should be valid");
+            assert!(read_back.moves == game.moves && read_back.start == game.start && read_back.outcome == game.outcome);
+        }
+
+        let written = parse_pgn(GAMES).expect("game should be valid").to_string();
+        assert!(written.contains("1. e4 {Best by test} e5 2. Bc4 Nc6 3. Qh5 Nf6 {too late} 4. Qxf7# 1-0"));
+    }
+}
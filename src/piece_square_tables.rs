@@ -2,6 +2,8 @@
 
 //All my tables must have values > 0 to avoid division by 0
 
+use shakmaty::Role;
+
 pub const PAWN: [i32; 64] = [
   100, 100, 100, 100, 100, 100, 100, 100,       
   198, 234, 161, 195, 168, 226, 134, 89,        
@@ -68,6 +70,53 @@ pub const KING: [i32; 64] = [
   51, 102,  78,  12,  74,  38,  90,  80
 ];
 
+/// A full set of tables, so evaluations can be run with tables other than the ones above, such as while tuning.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PieceSquareTables {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::table"))]
+    pub pawn: [i32; 64],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::table"))]
+    pub knight: [i32; 64],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::table"))]
+    pub bishop: [i32; 64],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::table"))]
+    pub rook: [i32; 64],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::table"))]
+    pub queen: [i32; 64],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::table"))]
+    pub king: [i32; 64]
+}
+
+impl PieceSquareTables {
+    pub const DEFAULT: PieceSquareTables = PieceSquareTables {
+        pawn: PAWN,
+        knight: KNIGHT,
+        bishop: BISHOP,
+        rook: ROOK,
+        queen: QUEEN,
+        king: KING
+    };
+
+    /// The table for pieces of type `role`, laid out from white's side of the board.
+    pub const fn table(&self, role: Role) -> &[i32; 64] {
+        match role {
+            Role::Pawn => &self.pawn,
+            Role::Knight => &self.knight,
+            Role::Bishop => &self.bishop,
+            Role::Rook => &self.rook,
+            Role::Queen => &self.queen,
+            Role::King => &self.king
+        }
+    }
+}
+
+impl Default for PieceSquareTables {
+    fn default() -> PieceSquareTables {
+        PieceSquareTables::DEFAULT
+    }
+}
+
 
 
 //Previous evaluation tables I used
@@ -0,0 +1,174 @@
+//! Engines for playing several games at once, such as a bot accepting more than one challenge.
+//!
+//! Each game in an [`EnginePool`] has an engine of its own, so transposition tables, what was learned from
+//! earlier searches and settings aren't shared between games. Games can search at the same time from
+//! different threads, sharing out the pool's threads: each search gets an equal share of them for the
+//! games in the pool, or whatever is left if other searches already hold more, and waits if none are left.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use shakmaty::Chess;
+
+use crate::variant::SearchPosition;
+use crate::{Engine, EngineOptions, SearchHandle, SearchLimits, SearchResult};
+
+/// Engines for several games, keyed by an id such as the game's id on the server.
+pub struct EnginePool<P: SearchPosition = Chess> {
+    max_threads: usize,
+    games: Mutex<HashMap<String, PoolGame<P>>>,
+    free_threads: Mutex<usize>,
+    threads_freed: Condvar
+}
+
+struct PoolGame<P: SearchPosition> {
+    engine: Arc<Mutex<Engine<P>>>,
+    handle: SearchHandle
+}
+
+/// Threads taken from the pool for one search, given back when it is dropped.
+struct ThreadGrant<'a, P: SearchPosition> {
+    pool: &'a EnginePool<P>,
+    threads: usize
+}
+
+impl<P: SearchPosition> Drop for ThreadGrant<'_, P> {
+    fn drop(&mut self) {
+        *lock(&self.pool.free_threads) += self.threads;
+        self.pool.threads_freed.notify_all();
+    }
+}
+
+//A search that panicked leaves nothing half done that later searches would trip over
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<P: SearchPosition> EnginePool<P> {
+    /// An empty pool whose searches use at most `max_threads` threads between them, and at least one.
+    pub fn new(max_threads: usize) -> EnginePool<P> {
+        let max_threads = max_threads.max(1);
+        EnginePool {
+            max_threads,
+            games: Mutex::new(HashMap::new()),
+            free_threads: Mutex::new(max_threads),
+            threads_freed: Condvar::new()
+        }
+    }
+
+    pub fn max_threads(&self) -> usize {
+        self.max_threads
+    }
+
+    /// How many games are in the pool.
+    pub fn len(&self) -> usize {
+        lock(&self.games).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a game with a new engine set up with `options`, whose thread count is decided by the pool instead.
+    /// Returns false and leaves the game alone if the pool already has a game with this id.
+    pub fn add_game(&self, game_id: &str, options: EngineOptions) -> bool {
+        let mut games = lock(&self.games);
+        if games.contains_key(game_id) {
+            return false;
+        }
+
+        let mut engine = Engine::new();
+        engine.set_options(options);
+        let handle = engine.handle();
+        games.insert(game_id.to_string(), PoolGame { engine: Arc::new(Mutex::new(engine)), handle });
+        true
+    }
+
+    /// Takes a game out of the pool, stopping its search if it is searching. Returns false for unknown games.
+    pub fn remove_game(&self, game_id: &str) -> bool {
+        match lock(&self.games).remove(game_id) {
+            Some(game) => {
+                game.handle.stop();
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Stops the game's search, which still returns the best move found so far. Returns false for unknown games.
+    pub fn stop(&self, game_id: &str) -> bool {
+        lock(&self.games).get(game_id).map(|game| game.handle.stop()).is_some()
+    }
+
+    /// Searches `chess` with the game's engine, as [`Engine::search`] does. Blocks until the search has
+    /// threads to run on, and until any search the same game is already running has finished.
+    /// Returns `None` for unknown games.
+    pub fn search(&self, game_id: &str, chess: &P, limits: SearchLimits, previously_seen_hashes: &mut Vec<u64>) -> Option<SearchResult> {
+        let engine = lock(&self.games).get(game_id)?.engine.clone();
+        let mut engine = lock(&engine);
+        let grant = self.take_threads();
+        engine.set_threads(grant.threads);
+        Some(engine.search(chess, limits, previously_seen_hashes))
+    }
+
+    fn take_threads(&self) -> ThreadGrant<'_, P> {
+        let share = (self.max_threads / self.len().max(1)).max(1);
+        let mut free_threads = lock(&self.free_threads);
+        while *free_threads == 0 {
+            free_threads = self.threads_freed.wait(free_threads).unwrap_or_else(PoisonError::into_inner);
+        }
+        let threads = share.min(*free_threads);
+        *free_threads -= threads;
+        ThreadGrant { pool: self, threads }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    //Games should be added once, searched independently and removed
+    fn games() {
+        let pool: EnginePool = EnginePool::new(2);
+        assert!(pool.is_empty() && pool.max_threads() == 2);
+        assert!(pool.add_game("first", EngineOptions::default()));
+        assert!(!pool.add_game("first", EngineOptions::default()));
+        assert!(pool.add_game("second", EngineOptions { skill_level: 0, ..EngineOptions::default() }));
+        assert!(pool.len() == 2);
+
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        assert!(pool.search("third", &Chess::new(), limits, &mut Vec::new()).is_none());
+        let result = pool.search("first", &Chess::new(), limits, &mut Vec::new()).expect("game should be in the pool");
+        assert!(result.depth == 3);
+        assert!(*lock(&pool.free_threads) == 2);
+
+        assert!(pool.stop("second") && !pool.stop("third"));
+        assert!(pool.remove_game("second") && !pool.remove_game("second"));
+        assert!(pool.len() == 1);
+    }
+
+    #[test]
+    //More games than threads should all get to search at once or in turn, and give their threads back
+    fn concurrent_searches() {
+        let pool: EnginePool = EnginePool::new(3);
+        let games = ["a", "b", "c", "d"];
+        for game_id in games {
+            pool.add_game(game_id, EngineOptions::default());
+        }
+
+        thread::scope(|scope| {
+            for game_id in games {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let limits = SearchLimits { move_time: Some(Duration::from_millis(50)), ..SearchLimits::default() };
+                    let result = pool.search(game_id, &Chess::new(), limits, &mut Vec::new()).expect("game should be in the pool");
+                    assert!(result.best_move.is_some());
+                });
+            }
+        });
+        assert!(*lock(&pool.free_threads) == 3);
+    }
+}
@@ -0,0 +1,162 @@
+//! Python bindings, for scripting the engine from Python, such as from a notebook.
+//!
+//! `cargo rustc --release --lib --features python,pyo3/extension-module --crate-type cdylib` builds the
+//! bindings into a library that Python imports as `hodgey_chess_engine` once it is renamed to
+//! `hodgey_chess_engine.so` (`.pyd` on Windows). It holds a single class, `Engine`:
+//!
+//! ```python
+//! engine = hodgey_chess_engine.Engine()
+//! engine.push_move("e2e4")
+//! engine.search(depth=6)   # the best move, such as "e7e5"
+//! engine.pv                # the expected continuation, starting with the best move
+//! engine.score             # centipawns for the side to move
+//! engine.eval()            # the static evaluation, from white's point of view
+//! ```
+
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, EnPassantMode, Position};
+
+use crate::evaluator::Evaluator;
+use crate::{chess_from_fen, Engine, SearchLimits, SearchResult};
+
+/// An engine along with the game it is searching and what its last search found.
+#[pyclass(name = "Engine", unsendable)]
+pub struct PyEngine {
+    engine: Engine,
+    chess: Chess,
+    previously_seen_hashes: Vec<u64>,
+    last_search: Option<SearchResult>
+}
+
+#[pymethods]
+impl PyEngine {
+    /// A new engine at the starting position.
+    #[new]
+    pub fn new() -> PyEngine {
+        let chess = Chess::default();
+        PyEngine {
+            engine: Engine::new(),
+            previously_seen_hashes: vec![hash(&chess)],
+            chess,
+            last_search: None
+        }
+    }
+
+    /// The current position as a FEN.
+    #[getter]
+    pub fn fen(&self) -> String {
+        Fen::from_position(self.chess.clone(), EnPassantMode::Legal).to_string()
+    }
+
+    /// Sets up the position in `fen`, raising `ValueError` if it can't be read or isn't legal.
+    pub fn set_fen(&mut self, fen: &str) -> PyResult<()> {
+        let chess = chess_from_fen(fen).map_err(|error| PyValueError::new_err(error.to_string()))?;
+        self.previously_seen_hashes = vec![hash(&chess)];
+        self.chess = chess;
+        Ok(())
+    }
+
+    /// Plays `uci_move`, such as "e2e4", raising `ValueError` if it isn't legal.
+    pub fn push_move(&mut self, uci_move: &str) -> PyResult<()> {
+        let m = UciMove::from_ascii(uci_move.as_bytes()).ok().and_then(|m| m.to_move(&self.chess).ok())
+            .ok_or_else(|| PyValueError::new_err(format!("illegal move {}", uci_move)))?;
+
+        if m.is_zeroing() {
+            self.previously_seen_hashes.clear();
+        }
+        self.chess.play_unchecked(&m);
+        self.previously_seen_hashes.push(hash(&self.chess));
+        Ok(())
+    }
+
+    /// Searches the current position to `depth` plies or for `movetime` seconds, whichever comes first, and
+    /// returns the best move. At least one of them is needed. Returns `None` if the game is already over.
+    #[pyo3(signature = (depth = None, movetime = None))]
+    pub fn search(&mut self, depth: Option<u16>, movetime: Option<f64>) -> PyResult<Option<String>> {
+        if depth.is_none() && movetime.is_none() {
+            return Err(PyValueError::new_err("search needs a depth or a movetime"));
+        }
+        let move_time = movetime.map(Duration::try_from_secs_f64).transpose()
+            .map_err(|_| PyValueError::new_err("movetime should be a number of seconds"))?;
+
+        let limits = SearchLimits { max_depth: depth, move_time, ..SearchLimits::default() };
+        let result = self.engine.search(&self.chess, limits, &mut self.previously_seen_hashes);
+        let best_move = result.best_move.as_ref().map(|m| self.uci(m));
+        self.last_search = Some(result);
+        Ok(best_move)
+    }
+
+    /// The principal variation of the last search in UCI notation, empty before the first search.
+    #[getter]
+    pub fn pv(&self) -> Vec<String> {
+        self.last_search.as_ref().map_or_else(Vec::new, |result| result.pv.iter().map(|m| self.uci(m)).collect())
+    }
+
+    /// The score of the last search in centipawns for the side to move, `None` before the first search.
+    #[getter]
+    pub fn score(&self) -> Option<i32> {
+        self.last_search.as_ref().map(|result| result.score)
+    }
+
+    /// The static evaluation of the current position in centipawns, from white's point of view.
+    pub fn eval(&self) -> i32 {
+        self.engine.evaluator().evaluate(&self.chess)
+    }
+}
+
+impl Default for PyEngine {
+    fn default() -> PyEngine {
+        PyEngine::new()
+    }
+}
+
+impl PyEngine {
+    //Castling is written the way the FEN wrote the castling rights, as the king taking its rook for Chess960
+    fn uci(&self, m: &shakmaty::Move) -> String {
+        m.to_uci(self.chess.castles().mode()).to_string()
+    }
+}
+
+fn hash(chess: &Chess) -> u64 {
+    let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+    hash.0
+}
+
+#[pymodule]
+fn hodgey_chess_engine(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEngine>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //The class should search the positions it is given and refuse bad input
+    fn engine_class() {
+        let mut engine = PyEngine::new();
+        assert!(engine.pv().is_empty() && engine.score().is_none());
+        assert!(engine.search(None, None).is_err());
+        assert!(engine.search(None, Some(-1.0)).is_err());
+
+        assert!(engine.set_fen("8/8/8 w - - 0 1").is_err());
+        engine.set_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").expect("position should be set");
+        assert!(engine.search(Some(3), None).expect("search should run") == Some("a1a6".to_string()));
+        assert!(engine.pv().len() == 3 && engine.score().is_some_and(|score| score > 0));
+
+        assert!(engine.push_move("a1a9").is_err());
+        engine.push_move("a1a6").expect("move should be legal");
+        engine.push_move("b7a6").expect("move should be legal");
+        assert!(engine.fen() == "kbK5/p7/pP6/8/8/8/8/8 w - - 0 2");
+        assert!(engine.search(None, Some(0.05)).expect("search should run") == Some("b6b7".to_string()));
+
+        engine.set_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").expect("position should be set");
+        assert!(engine.eval() > 500);
+    }
+}
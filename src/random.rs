@@ -0,0 +1,36 @@
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small deterministic random number generator so results are reproducible without extra dependencies.
+pub struct XorShift64 {
+    state: u64
+}
+
+impl XorShift64 {
+    pub fn new(seed: u64) -> XorShift64 {
+        //The state must never be zero
+        XorShift64 { state: seed ^ 0x9E37_79B9_7F4A_7C15 | 1 }
+    }
+
+    /// A generator seeded from the current time, for when results shouldn't repeat.
+    /// On wasm32-unknown-unknown, which can't tell the time, each generator takes the next of a fixed run of seeds.
+    pub fn from_time() -> XorShift64 {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        let nanos = {
+            static GENERATORS: AtomicU64 = AtomicU64::new(0);
+            GENERATORS.fetch_add(1, Ordering::Relaxed)
+        };
+        XorShift64::new(nanos)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
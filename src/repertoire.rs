@@ -0,0 +1,123 @@
+//! Openings the engine has to keep to, for practicing them against it.
+//!
+//! A [`Repertoire`] lists the moves allowed in each of its positions, from move lists or from Polyglot
+//! books. Searches with a repertoire set (see [`crate::Engine::set_repertoire`]) only consider the allowed
+//! moves while the game is in the repertoire and pick the best of them, then search every move once the
+//! game has left it. Unlike a book, the repertoire doesn't choose between its moves at random.
+//!
+//! Repertoire files are plain text with a line of UCI moves from the standard start position per line
+//! of the repertoire. Every move in a line is allowed, so a line covers the moves of both sides.
+
+use std::collections::HashMap;
+use std::io;
+
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, EnPassantMode, Move, Position};
+
+use crate::book::Book;
+
+/// The moves allowed in each position of an opening repertoire, see the [module docs](self).
+#[derive(Clone, Debug, Default)]
+pub struct Repertoire {
+    moves: HashMap<u64, Vec<Move>>,
+    books: Vec<Book>
+}
+
+impl Repertoire {
+    pub fn new() -> Repertoire {
+        Repertoire::default()
+    }
+
+    /// Allows `mv` in `position`.
+    pub fn add_move<P: Position>(&mut self, position: &P, mv: Move) {
+        let moves = self.moves.entry(key(position)).or_default();
+        if !moves.contains(&mv) {
+            moves.push(mv);
+        }
+    }
+
+    /// Allows every move of a line played from `start`, up to the first illegal one.
+    pub fn add_line<P: Position + Clone>(&mut self, start: &P, moves: &[Move]) {
+        let mut position = start.clone();
+        for mv in moves {
+            if !position.is_legal(mv) {
+                break;
+            }
+            self.add_move(&position, mv.clone());
+            position.play_unchecked(mv);
+        }
+    }
+
+    /// Allows every move `book` has for a position, whatever its weight.
+    pub fn add_book(&mut self, book: Book) {
+        self.books.push(book);
+    }
+
+    /// The moves allowed in `position`, empty once the game has left the repertoire.
+    pub fn moves<P: Position>(&self, position: &P) -> Vec<Move> {
+        let mut moves = self.moves.get(&key(position)).cloned().unwrap_or_default();
+        for book in &self.books {
+            for (mv, _) in book.moves(position) {
+                if !moves.contains(&mv) {
+                    moves.push(mv);
+                }
+            }
+        }
+        moves
+    }
+
+    /// Whether `position` is in the repertoire.
+    pub fn contains<P: Position>(&self, position: &P) -> bool {
+        !self.moves(position).is_empty()
+    }
+
+    /// Reads a repertoire file.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Repertoire> {
+        Repertoire::from_text(&std::fs::read_to_string(path)?)
+    }
+
+    /// Reads a repertoire from the contents of a repertoire file.
+    pub fn from_text(text: &str) -> io::Result<Repertoire> {
+        let mut repertoire = Repertoire::new();
+        for line in text.lines() {
+            let mut chess = Chess::new();
+            for uci in line.split_whitespace() {
+                let mv = UciMove::from_ascii(uci.as_bytes()).ok().and_then(|uci| uci.to_move(&chess).ok()).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("illegal move {} in repertoire line {}", uci, line))
+                })?;
+                repertoire.add_move(&chess, mv.clone());
+                chess.play_unchecked(&mv);
+            }
+        }
+        Ok(repertoire)
+    }
+}
+
+fn key<P: Position>(position: &P) -> u64 {
+    let hash: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+    hash.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //Lines should allow their moves in the positions they were played in and nowhere else
+    fn lines() {
+        let repertoire = Repertoire::from_text("e2e4 e7e5 g1f3\ne2e4 c7c5\n\nd2d4").expect("lines should be legal");
+        let chess = Chess::new();
+        let moves: Vec<String> = repertoire.moves(&chess).iter().map(|mv| mv.to_string()).collect();
+        assert!(moves == vec!["e2-e4", "d2-d4"]);
+
+        let after_e4 = chess.clone().play(&repertoire.moves(&chess)[0]).expect("move should be legal");
+        assert!(repertoire.moves(&after_e4).len() == 2);
+        let after_d4 = chess.clone().play(&repertoire.moves(&chess)[1]).expect("move should be legal");
+        assert!(!repertoire.contains(&after_d4));
+
+        assert!(Repertoire::from_text("e2e4 e2e4").is_err());
+        assert!(Repertoire::from_text("e2e4 castle").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+const FILTER_LENGTH: usize = 4096;
+const FILTER_MASK: u64 = FILTER_LENGTH as u64 - 1;
+
+/// The hashes of the positions leading to the node being searched, from the game followed by the search's
+/// own path, in the order they were reached. A table counting the hashes by their low bits rules out most
+/// positions without looking at the list, and the rest only need the positions since the last capture or
+/// pawn move checked, since nothing before those can be repeated.
+pub struct Repetitions<'a> {
+    hashes: &'a mut Vec<u64>,
+    filter: Box<[u16; FILTER_LENGTH]>
+}
+
+impl<'a> Repetitions<'a> {
+    /// Starts from the game's `hashes`, which the search pushes its path onto and pops it back off.
+    pub fn new(hashes: &'a mut Vec<u64>) -> Repetitions<'a> {
+        let mut filter = Box::new([0; FILTER_LENGTH]);
+        for &hash in hashes.iter() {
+            filter[(hash & FILTER_MASK) as usize] += 1;
+        }
+        Repetitions { hashes, filter }
+    }
+
+    pub fn push(&mut self, hash: u64) {
+        self.filter[(hash & FILTER_MASK) as usize] += 1;
+        self.hashes.push(hash);
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(hash) = self.hashes.pop() {
+            self.filter[(hash & FILTER_MASK) as usize] -= 1;
+        }
+    }
+
+    pub fn hashes(&self) -> &[u64] {
+        self.hashes
+    }
+
+    /// Whether the position with `hash` was already reached within the last `halfmoves` positions.
+    pub fn is_repeated(&self, hash: u64, halfmoves: u32) -> bool {
+        if self.filter[(hash & FILTER_MASK) as usize] == 0 {
+            return false;
+        }
+        let reversible = self.hashes.len().saturating_sub(halfmoves as usize);
+        self.hashes[reversible..].contains(&hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //Only positions since the last irreversible move and still on the path should count
+    fn repeated_positions() {
+        let mut hashes = vec![1, 2, 3];
+        let mut repetitions = Repetitions::new(&mut hashes);
+        assert!(repetitions.is_repeated(2, 10));
+        assert!(!repetitions.is_repeated(1, 2));
+        assert!(!repetitions.is_repeated(FILTER_LENGTH as u64 + 2, 10));
+
+        repetitions.push(FILTER_LENGTH as u64 + 2);
+        assert!(repetitions.is_repeated(FILTER_LENGTH as u64 + 2, 1));
+        repetitions.pop();
+        assert!(!repetitions.is_repeated(FILTER_LENGTH as u64 + 2, 10));
+        assert!(hashes == vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::cmp::Reverse;
+
+use shakmaty::{Move, Position};
+
+/// A legal move at the root along with what the search has learned about it.
+#[derive(Clone)]
+pub struct RootMove {
+    pub mv: Move,
+    pub score: i32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+    /// Centipawns taken off the move's score for the games it lost before, see [`crate::experience`].
+    pub penalty: i32,
+    /// Whether the score is from the pass over the root moves under way, rather than an earlier iteration
+    /// or a pass whose window failed.
+    pub searched: bool
+}
+
+/// All legal root moves, kept with the current best move at the front.
+#[derive(Clone)]
+pub struct RootMoves {
+    moves: Vec<RootMove>
+}
+
+impl RootMoves {
+    pub fn new<P: Position>(position: &P) -> RootMoves {
+        RootMoves {
+            moves: position.legal_moves().into_iter().map(|mv| RootMove {
+                pv: vec![mv.clone()],
+                mv,
+                score: 0,
+                nodes: 0,
+                penalty: 0,
+                searched: false
+            }).collect()
+        }
+    }
+
+    /// Keeps only the root moves in `moves`, or all of them if none of `moves` are legal.
+    pub fn restrict(&mut self, moves: &[Move]) {
+        if self.moves.iter().any(|root_move| moves.contains(&root_move.mv)) {
+            self.moves.retain(|root_move| moves.contains(&root_move.mv));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn best(&self) -> &RootMove {
+        &self.moves[0]
+    }
+
+    pub fn get(&self, index: usize) -> &RootMove {
+        &self.moves[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut RootMove {
+        &mut self.moves[index]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RootMove> {
+        self.moves.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut RootMove> {
+        self.moves.iter_mut()
+    }
+
+    /// Moves the root move at `index` to the front, keeping the order of the others.
+    pub fn move_to_front(&mut self, index: usize) {
+        self.moves[..=index].rotate_right(1);
+    }
+
+    /// Marks every move as not yet searched, before a new pass over the root moves.
+    pub fn start_pass(&mut self) {
+        for root_move in &mut self.moves {
+            root_move.searched = false;
+        }
+    }
+
+    /// Puts the best `count` of the moves searched this pass first, in order of score, and leaves every other
+    /// move in the order it was in. Scores up to `floor` are bounds below the window, so they count as equal.
+    /// Moves with equal scores keep their order, so a move has to beat another to get ahead of it.
+    pub fn rank(&mut self, count: usize, floor: i32) {
+        let mut best: Vec<usize> = (0..self.moves.len()).filter(|&index| self.moves[index].searched).collect();
+        best.sort_by_key(|&index| Reverse(self.moves[index].score.max(floor)));
+        best.truncate(count);
+
+        let mut ranked: Vec<(usize, RootMove)> = std::mem::take(&mut self.moves).into_iter().enumerate().map(|(index, root_move)| {
+            (best.iter().position(|&best| best == index).unwrap_or(count), root_move)
+        }).collect();
+        ranked.sort_by_key(|&(rank, _)| rank);
+        self.moves = ranked.into_iter().map(|(_, root_move)| root_move).collect();
+    }
+
+    /// Sorts all but the best move by score, highest first. Moves with equal scores keep their order.
+    pub fn sort_by_score(&mut self) {
+        self.moves[1..].sort_by_key(|root_move| Reverse(root_move.score));
+    }
+
+    /// Moves `mv` to the front if it is one of the root moves.
+    pub fn prioritize(&mut self, mv: &Move) {
+        if let Some(index) = self.moves.iter().position(|root_move| &root_move.mv == mv) {
+            self.move_to_front(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{Chess, Role, Square};
+
+    #[test]
+    //Prioritizing a move should put it first without shuffling the rest
+    fn prioritize_keeps_order() {
+        let mut root_moves = RootMoves::new(&Chess::new());
+        let before: Vec<Move> = root_moves.iter().map(|root_move| root_move.mv.clone()).collect();
+        let e4 = Move::Normal { role: Role::Pawn, from: Square::E2, capture: None, to: Square::E4, promotion: None };
+        root_moves.prioritize(&e4);
+
+        assert!(root_moves.best().mv == e4);
+        let after: Vec<Move> = root_moves.iter().skip(1).map(|root_move| root_move.mv.clone()).collect();
+        let expected: Vec<Move> = before.into_iter().filter(|m| *m != e4).collect();
+        assert!(after == expected);
+    }
+
+    #[test]
+    //Sorting should order the moves after the best one by score and leave the best one alone
+    fn sort_keeps_best_first() {
+        let mut root_moves = RootMoves::new(&Chess::new());
+        for (index, root_move) in root_moves.iter_mut().enumerate() {
+            root_move.score = index as i32 % 5;
+        }
+        let best = root_moves.best().mv.clone();
+        root_moves.sort_by_score();
+
+        assert!(root_moves.best().mv == best);
+        let scores: Vec<i32> = root_moves.iter().skip(1).map(|root_move| root_move.score).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    //Ranking should only reorder moves searched this pass, and only when a score beats another
+    fn rank_searched_moves() {
+        let mut root_moves = RootMoves::new(&Chess::new());
+        let before: Vec<Move> = root_moves.iter().map(|root_move| root_move.mv.clone()).collect();
+        for (index, root_move) in root_moves.iter_mut().enumerate() {
+            root_move.score = index as i32;
+        }
+        root_moves.start_pass();
+        for index in [0, 2, 3, 5] {
+            root_moves.get_mut(index).searched = true;
+        }
+        root_moves.get_mut(3).score = -10;
+        root_moves.rank(2, 0);
+
+        //Move 19 has the best score but wasn't searched, move 3 is below the floor like move 0
+        let order: Vec<usize> = root_moves.iter().map(|root_move| before.iter().position(|mv| *mv == root_move.mv).expect("move should be known")).collect();
+        assert!(order[..2] == [5, 2]);
+        assert!(order[2..] == (0..20).filter(|index| ![5, 2].contains(index)).collect::<Vec<_>>()[..]);
+
+        root_moves.rank(3, 0);
+        let order: Vec<usize> = root_moves.iter().map(|root_move| before.iter().position(|mv| *mv == root_move.mv).expect("move should be known")).collect();
+        assert!(order[..3] == [5, 2, 0]);
+    }
+}
@@ -0,0 +1,41 @@
+//! The numbers behind the search's pruning and time decisions, set with [`crate::Engine::set_search_params`].
+//!
+//! They are kept apart from the evaluation's [`crate::evaluator::EvalParams`] so they can be tuned by playing
+//! games, see [`crate::spsa`], without rebuilding the engine for every value tried.
+
+/// Margins and scales the search uses. Scales are in percent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchParams {
+    /// How far either side of the previous iteration's score the next iteration's window starts out.
+    pub aspiration_window: i32,
+    /// Quiet moves near the horizon are skipped if the static evaluation plus this much per ply of depth
+    /// can't reach alpha.
+    pub futility_margin: i32,
+    /// A static evaluation near the horizon beating beta by this much per ply of depth is trusted to hold up
+    /// without searching.
+    pub reverse_futility_margin: i32,
+    /// How far below the best move every other move has to score for the best move to be played early.
+    pub easy_move_margin: i32,
+    /// The soft time limit after an iteration that changed the best move.
+    pub best_move_change_scale: i32,
+    /// The soft time limit once the best move has stayed the same for a few iterations.
+    pub stable_scale: i32
+}
+
+impl SearchParams {
+    pub const DEFAULT: SearchParams = SearchParams {
+        aspiration_window: 50,
+        futility_margin: 120,
+        reverse_futility_margin: 100,
+        easy_move_margin: 250,
+        best_move_change_scale: 160,
+        stable_scale: 60
+    };
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        SearchParams::DEFAULT
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+//How much each new search moves the smoothed nps
+const NPS_SMOOTHING: f64 = 0.3;
+//Depth goes up by 1 each iteration, this is a guess at how many more nodes that takes
+const DEFAULT_BRANCHING_FACTOR: f64 = 3.0;
+
+/// Keeps a smoothed nodes per second estimate across all the searches of a game.
+#[derive(Default, Clone, Copy)]
+pub struct SearchSpeed {
+    smoothed_nps: Option<f64>
+}
+
+impl SearchSpeed {
+    pub fn update(&mut self, nodes: u64, elapsed: Duration) {
+        //Very short searches say more about overhead than speed
+        if elapsed < Duration::from_millis(10) {
+            return;
+        }
+
+        let nps = nodes as f64 / elapsed.as_secs_f64();
+        self.smoothed_nps = Some(match self.smoothed_nps {
+            Some(smoothed_nps) => smoothed_nps + NPS_SMOOTHING * (nps - smoothed_nps),
+            None => nps
+        });
+    }
+
+    /// The game's smoothed nps, or the current search's nps until a search has finished.
+    pub fn nps(&self, nodes: u64, elapsed: Duration) -> Option<f64> {
+        self.smoothed_nps.or_else(|| {
+            (elapsed >= Duration::from_millis(10)).then(|| nodes as f64 / elapsed.as_secs_f64())
+        })
+    }
+
+    /// Predicts how long searching `nodes` nodes will take.
+    pub fn predict_time(&self, nodes: u64, searched_nodes: u64, elapsed: Duration) -> Option<Duration> {
+        let nps = self.nps(searched_nodes, elapsed)?;
+        (nps > 0.0).then(|| Duration::from_secs_f64(nodes as f64 / nps))
+    }
+}
+
+/// Predicts the nodes the next iteration will take from the nodes of the completed ones.
+pub fn predict_iteration_nodes(iteration_nodes: &[u64]) -> Option<u64> {
+    let (&last, rest) = iteration_nodes.split_last()?;
+    let branching_factor = match rest.last() {
+        Some(&previous) if previous > 0 => (last as f64 / previous as f64).max(1.0),
+        _ => DEFAULT_BRANCHING_FACTOR
+    };
+    Some((last as f64 * branching_factor) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //The smoothed nps should move toward new measurements without jumping to them
+    fn smoothing() {
+        let mut speed = SearchSpeed::default();
+        speed.update(1_000_000, Duration::from_secs(1));
+        assert!(speed.nps(0, Duration::ZERO) == Some(1_000_000.0));
+        speed.update(2_000_000, Duration::from_secs(1));
+        let nps = speed.nps(0, Duration::ZERO).unwrap();
+        assert!(nps > 1_000_000.0 && nps < 2_000_000.0);
+    }
+
+    #[test]
+    //Predictions should follow the growth between the last two iterations
+    fn iteration_prediction() {
+        assert!(predict_iteration_nodes(&[]).is_none());
+        assert!(predict_iteration_nodes(&[100]) == Some(300));
+        assert!(predict_iteration_nodes(&[100, 400]) == Some(1600));
+    }
+}
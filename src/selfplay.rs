@@ -0,0 +1,274 @@
+//! Games between two engines, usually the engine against itself or against a differently configured copy.
+//!
+//! Games are played out until checkmate or stalemate, or adjudicated as soon as a draw can be claimed
+//! by threefold repetition or the fifty move rule or neither side can mate. A side that overruns its
+//! clock loses on time. With the `pgn` feature, finished games can be written out as PGN.
+
+use std::time::Duration;
+
+#[cfg(feature = "pgn")]
+use shakmaty::fen::Fen;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, Color, EnPassantMode, Move, Outcome, Position};
+
+#[cfg(feature = "pgn")]
+use crate::pgn::{PgnGame, PgnMove};
+use crate::{Engine, SearchLimits, SearchResult};
+
+const FIFTY_MOVE_RULE_HALFMOVES: u32 = 100;
+const REPETITIONS_FOR_DRAW: usize = 3;
+
+/// How long each side may think.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeControl {
+    /// Each side starts with `base` on its clock and gains `increment` after every move.
+    Clock { base: Duration, increment: Duration },
+    /// Every move is searched with the same limits and no clock is kept.
+    /// Depth or node limits make games reproducible.
+    Fixed(SearchLimits)
+}
+
+/// Why a game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    Repetition,
+    FiftyMoveRule,
+    TimeForfeit
+}
+
+impl Termination {
+    /// How the game ended in a few words, such as "threefold repetition".
+    pub fn description(self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::Repetition => "threefold repetition",
+            Termination::FiftyMoveRule => "fifty move rule",
+            Termination::TimeForfeit => "time forfeit"
+        }
+    }
+}
+
+/// A move of the game along with the search that chose it.
+#[derive(Clone, Debug)]
+pub struct PlayedMove {
+    pub mv: Move,
+    pub search: SearchResult
+}
+
+/// A finished game.
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub start: Chess,
+    pub moves: Vec<PlayedMove>,
+    pub outcome: Outcome,
+    pub termination: Termination
+}
+
+/// Plays a game from `start` with `white` and `black` choosing the moves.
+/// Passing two differently configured engines plays them against each other.
+pub fn play_game(white: &mut Engine, black: &mut Engine, start: &Chess, time_control: TimeControl) -> Game {
+    let mut chess = start.clone();
+    let mut moves = Vec::new();
+    //Every position of the game, for spotting threefold repetitions
+    let mut game_hashes = vec![hash(&chess)];
+    //Positions since the last capture or pawn move, which are the only ones that can repeat
+    let mut previously_seen_hashes = vec![hash(&chess)];
+    let mut clocks = match time_control {
+        TimeControl::Clock { base, .. } => Some([base, base]),
+        TimeControl::Fixed(_) => None
+    };
+
+    let (outcome, termination) = loop {
+        if let Some(result) = adjudicate(&chess, &game_hashes) {
+            break result;
+        }
+
+        let turn = chess.turn();
+        let limits = match time_control {
+            TimeControl::Clock { increment, .. } => {
+                let clocks = clocks.expect("clock games should keep clocks");
+                SearchLimits {
+                    white_time: Some(clocks[Color::White as usize]),
+                    black_time: Some(clocks[Color::Black as usize]),
+                    white_increment: Some(increment),
+                    black_increment: Some(increment),
+                    ..SearchLimits::default()
+                }
+            },
+            TimeControl::Fixed(limits) => limits
+        };
+
+        let engine = match turn {
+            Color::White => &mut *white,
+            Color::Black => &mut *black
+        };
+        let search = engine.search(&chess, limits, &mut previously_seen_hashes);
+
+        if let (TimeControl::Clock { increment, .. }, Some(clocks)) = (time_control, &mut clocks) {
+            let clock = &mut clocks[turn as usize];
+            if search.time > *clock {
+                break (Outcome::Decisive { winner: !turn }, Termination::TimeForfeit);
+            }
+            *clock = *clock - search.time + increment;
+        }
+
+        let mv = search.best_move.clone().expect("a game that isn't over has a legal move");
+        if mv.is_zeroing() {
+            previously_seen_hashes.clear();
+        }
+        chess.play_unchecked(&mv);
+        game_hashes.push(hash(&chess));
+        previously_seen_hashes.push(hash(&chess));
+        moves.push(PlayedMove { mv, search });
+    };
+
+    Game {
+        start: start.clone(),
+        moves,
+        outcome,
+        termination
+    }
+}
+
+/// The result of the game if it is over in `chess`, with `game_hashes` holding every position so far.
+fn adjudicate(chess: &Chess, game_hashes: &[u64]) -> Option<(Outcome, Termination)> {
+    if chess.is_checkmate() {
+        return Some((Outcome::Decisive { winner: !chess.turn() }, Termination::Checkmate));
+    }
+    if chess.is_stalemate() {
+        return Some((Outcome::Draw, Termination::Stalemate));
+    }
+    if chess.is_insufficient_material() {
+        return Some((Outcome::Draw, Termination::InsufficientMaterial));
+    }
+
+    let current = game_hashes.last().copied().unwrap_or_else(|| hash(chess));
+    if game_hashes.iter().filter(|&&hash| hash == current).count() >= REPETITIONS_FOR_DRAW {
+        return Some((Outcome::Draw, Termination::Repetition));
+    }
+    if chess.halfmoves() >= FIFTY_MOVE_RULE_HALFMOVES {
+        return Some((Outcome::Draw, Termination::FiftyMoveRule));
+    }
+
+    None
+}
+
+fn hash(chess: &Chess) -> u64 {
+    let hash: Zobrist64 = chess.zobrist_hash(EnPassantMode::Legal);
+    hash.0
+}
+
+#[cfg(feature = "pgn")]
+impl Game {
+    /// The game as PGN, with the score and depth of each move's search as a comment.
+    pub fn to_pgn_game(&self, white: &str, black: &str, round: u32) -> PgnGame {
+        let mut pgn = PgnGame::new(self.start.clone());
+
+        pgn.set_tag("Event", "Self-play");
+        pgn.set_tag("Site", "?");
+        pgn.set_tag("Date", "????.??.??");
+        pgn.set_tag("Round", round.to_string());
+        pgn.set_tag("White", white);
+        pgn.set_tag("Black", black);
+        pgn.set_tag("Result", self.outcome.to_string());
+        if self.start != Chess::default() {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", Fen::from_position(self.start.clone(), EnPassantMode::Legal).to_string());
+        }
+        pgn.set_tag("Termination", if self.termination == Termination::TimeForfeit {"time forfeit"} else {"normal"});
+
+        pgn.moves = self.moves.iter().map(|played_move| PgnMove {
+            mv: played_move.mv.clone(),
+            comment: Some(search_comment(&played_move.search))
+        }).collect();
+        pgn.final_comment = Some(self.termination.description().to_string());
+        pgn.outcome = Some(self.outcome);
+        pgn
+    }
+
+    /// Writes the game as PGN, see [`Game::to_pgn_game`].
+    pub fn to_pgn(&self, white: &str, black: &str, round: u32) -> String {
+        self.to_pgn_game(white, black, round).to_string()
+    }
+}
+
+//Scores are in pawns from the mover's point of view, such as "+0.35/7" or "-M3/12"
+#[cfg(feature = "pgn")]
+fn search_comment(search: &SearchResult) -> String {
+    let score = match search.mate_in() {
+        Some(mate_in) if mate_in >= 0 => format!("+M{}", mate_in),
+        Some(mate_in) => format!("-M{}", -mate_in),
+        None => format!("{:+.2}", search.score as f64 / 100.0)
+    };
+    format!("{}/{}", score, search.depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, FromSetup};
+
+    fn position(fen: &str) -> Chess {
+        let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+        Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+    }
+
+    #[test]
+    //A mate in one should be played and end the game
+    fn game_ends_in_checkmate() {
+        let start = position("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1");
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, TimeControl::Fixed(limits));
+        assert!(game.outcome == Outcome::Decisive { winner: Color::White });
+        assert!(game.termination == Termination::Checkmate);
+        assert!(game.moves.len() == 1);
+    }
+
+    #[test]
+    #[cfg(feature = "pgn")]
+    //Games should be written with the search of every move and how they ended
+    fn pgn_output() {
+        let start = position("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1");
+        let limits = SearchLimits { max_depth: Some(3), ..SearchLimits::default() };
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, TimeControl::Fixed(limits));
+
+        let pgn = game.to_pgn("A", "B", 1);
+        assert!(pgn.contains("[Result \"1-0\"]"));
+        assert!(pgn.contains("[FEN \"6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1\"]"));
+        assert!(pgn.contains("1. Rd8# {+M1/1} {checkmate} 1-0"));
+        assert!(crate::pgn::parse_pgn(&pgn).is_ok_and(|read_back| read_back.move_list() == vec![game.moves[0].mv.clone()]));
+
+        let start = position("4k3/8/8/8/8/8/4P3/R3K3 b - - 99 80");
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, TimeControl::Fixed(limits));
+        assert!(game.to_pgn("A", "B", 1).contains("80... K"));
+    }
+
+    #[test]
+    //Games should be drawn once a draw can be claimed
+    fn draws_are_adjudicated() {
+        let limits = SearchLimits { max_depth: Some(2), ..SearchLimits::default() };
+        let start = position("4k3/8/8/8/8/8/4P3/R3K3 b - - 99 80");
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, TimeControl::Fixed(limits));
+        assert!(game.outcome == Outcome::Draw);
+        assert!(game.termination == Termination::FiftyMoveRule);
+
+        let start = position("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, TimeControl::Fixed(limits));
+        assert!(game.termination == Termination::InsufficientMaterial && game.moves.is_empty());
+    }
+
+    #[test]
+    //A clock game should stay within its time and finish
+    fn clock_game() {
+        let start = position("6k1/5ppp/8/8/8/8/8/K2R4 w - - 0 1");
+        let time_control = TimeControl::Clock { base: Duration::from_secs(5), increment: Duration::from_millis(100) };
+        let game = play_game(&mut Engine::new(), &mut Engine::new(), &start, time_control);
+        assert!(game.termination == Termination::Checkmate);
+    }
+}
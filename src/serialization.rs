@@ -0,0 +1,146 @@
+//! Serde support for the types shakmaty doesn't serialize itself, used through `#[serde(with = ...)]`.
+//!
+//! Moves are written out in full, with the moving piece and anything it captures, since UCI notation
+//! can't be turned back into a move without the position it was played in. Pieces are written as their
+//! lowercase letters and squares by name.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use shakmaty::{Move, Role, Square};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SerializedMove {
+    Normal { role: char, from: String, capture: Option<char>, to: String, promotion: Option<char> },
+    EnPassant { from: String, to: String },
+    Castle { king: String, rook: String },
+    Put { role: char, to: String }
+}
+
+impl From<&Move> for SerializedMove {
+    fn from(m: &Move) -> SerializedMove {
+        match *m {
+            Move::Normal { role, from, capture, to, promotion } => SerializedMove::Normal {
+                role: role.char(),
+                from: from.to_string(),
+                capture: capture.map(Role::char),
+                to: to.to_string(),
+                promotion: promotion.map(Role::char)
+            },
+            Move::EnPassant { from, to } => SerializedMove::EnPassant { from: from.to_string(), to: to.to_string() },
+            Move::Castle { king, rook } => SerializedMove::Castle { king: king.to_string(), rook: rook.to_string() },
+            Move::Put { role, to } => SerializedMove::Put { role: role.char(), to: to.to_string() }
+        }
+    }
+}
+
+impl SerializedMove {
+    fn into_move<E: Error>(self) -> Result<Move, E> {
+        Ok(match self {
+            SerializedMove::Normal { role, from, capture, to, promotion } => Move::Normal {
+                role: parse_role(role)?,
+                from: parse_square(&from)?,
+                capture: capture.map(parse_role).transpose()?,
+                to: parse_square(&to)?,
+                promotion: promotion.map(parse_role).transpose()?
+            },
+            SerializedMove::EnPassant { from, to } => Move::EnPassant { from: parse_square(&from)?, to: parse_square(&to)? },
+            SerializedMove::Castle { king, rook } => Move::Castle { king: parse_square(&king)?, rook: parse_square(&rook)? },
+            SerializedMove::Put { role, to } => Move::Put { role: parse_role(role)?, to: parse_square(&to)? }
+        })
+    }
+}
+
+fn parse_role<E: Error>(role: char) -> Result<Role, E> {
+    Role::from_char(role).ok_or_else(|| E::custom(format!("invalid piece {}", role)))
+}
+
+fn parse_square<E: Error>(square: &str) -> Result<Square, E> {
+    Square::from_ascii(square.as_bytes()).map_err(|_| E::custom(format!("invalid square {}", square)))
+}
+
+pub mod option_move {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(m: &Option<Move>, serializer: S) -> Result<S::Ok, S::Error> {
+        m.as_ref().map(SerializedMove::from).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Move>, D::Error> {
+        Option::<SerializedMove>::deserialize(deserializer)?.map(SerializedMove::into_move).transpose()
+    }
+}
+
+pub mod moves {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(moves: &[Move], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(moves.iter().map(SerializedMove::from))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Move>, D::Error> {
+        Vec::<SerializedMove>::deserialize(deserializer)?.into_iter().map(SerializedMove::into_move).collect()
+    }
+}
+
+/// Piece square tables, which serde only derives for arrays of up to 32 values.
+pub mod table {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(table: &[i32; 64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(table)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[i32; 64], D::Error> {
+        let values = Vec::<i32>::deserialize(deserializer)?;
+        let length = values.len();
+        values.try_into().map_err(|_| D::Error::invalid_length(length, &"64 values"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tuning::PieceSquareTables;
+    use crate::{SearchLimits, SearchResult, SearchStats};
+    use shakmaty::{Move, Role, Square};
+    use std::time::Duration;
+
+    #[test]
+    //Every kind of move should come back from JSON as it was
+    fn search_result_round_trip() {
+        let result = SearchResult {
+            best_move: Some(Move::Normal { role: Role::Pawn, from: Square::B7, capture: Some(Role::Rook), to: Square::A8, promotion: Some(Role::Queen) }),
+            score: -35,
+            depth: 9,
+            seldepth: 21,
+            pv: vec![
+                Move::Castle { king: Square::E1, rook: Square::H1 },
+                Move::EnPassant { from: Square::D4, to: Square::E3 },
+                Move::Put { role: Role::Knight, to: Square::F7 }
+            ],
+            nodes: 123_456,
+            hashfull: 12,
+            time: Duration::from_millis(1_500),
+            stats: SearchStats { beta_cutoffs: 70, first_move_cutoffs: 60, ..SearchStats::default() }
+        };
+        let json = serde_json::to_string(&result).expect("result should serialize");
+        assert!(json.contains(r#""best_move":{"kind":"normal","role":"p","from":"b7","capture":"r","to":"a8","promotion":"q"}"#));
+        assert!(serde_json::from_str::<SearchResult>(&json).expect("result should deserialize") == result);
+        let invalid_piece = json.replace(r#""role":"n""#, r#""role":"x""#);
+        assert!(serde_json::from_str::<SearchResult>(&invalid_piece).is_err());
+
+        let limits = SearchLimits { max_depth: Some(6), move_time: Some(Duration::from_secs(2)), ..SearchLimits::default() };
+        let json = serde_json::to_string(&limits).expect("limits should serialize");
+        assert!(serde_json::from_str::<SearchLimits>(&json).expect("limits should deserialize") == limits);
+    }
+
+    #[test]
+    //Tables should keep all 64 values, and tables of any other length shouldn't be read
+    fn table_round_trip() {
+        let json = serde_json::to_string(&PieceSquareTables::DEFAULT).expect("tables should serialize");
+        assert!(serde_json::from_str::<PieceSquareTables>(&json).expect("tables should deserialize") == PieceSquareTables::DEFAULT);
+        //The pawn table gets a 65th value
+        let long = json.replacen('[', "[1,", 1);
+        assert!(serde_json::from_str::<PieceSquareTables>(&long).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! SPSA tuning of the [`SearchParams`] through self-play.
+//!
+//! Every iteration nudges all the tuned params at once in a random direction, plays the engine with the params
+//! nudged one way against the engine with them nudged the other way, and moves the params toward whichever
+//! side scored better. The nudges and the steps taken shrink as tuning goes on, the way simultaneous
+//! perturbation stochastic approximation prescribes, so the params settle down instead of wandering.
+
+use shakmaty::Chess;
+
+use crate::engine_match::{run_match, MatchSettings};
+use crate::random::XorShift64;
+use crate::search_params::SearchParams;
+use crate::selfplay::TimeControl;
+use crate::Engine;
+
+//How quickly the steps and nudges shrink, the values usually recommended for SPSA
+const STEP_DECAY: f64 = 0.602;
+const NUDGE_DECAY: f64 = 0.101;
+
+type SearchParamField = fn(&mut SearchParams) -> &mut i32;
+
+/// A search param being tuned, with the range it is kept in and how far it is nudged at the start.
+#[derive(Clone, Copy)]
+pub struct TunedParam {
+    pub name: &'static str,
+    pub field: SearchParamField,
+    pub min: i32,
+    pub max: i32,
+    pub nudge: f64
+}
+
+/// The params tuned by default: the pruning margins, the aspiration window and the time management scales.
+pub const TUNED_PARAMS: [TunedParam; 6] = [
+    TunedParam { name: "aspiration_window", field: |params| &mut params.aspiration_window, min: 10, max: 200, nudge: 10.0 },
+    TunedParam { name: "futility_margin", field: |params| &mut params.futility_margin, min: 30, max: 400, nudge: 20.0 },
+    TunedParam { name: "reverse_futility_margin", field: |params| &mut params.reverse_futility_margin, min: 30, max: 400, nudge: 20.0 },
+    TunedParam { name: "easy_move_margin", field: |params| &mut params.easy_move_margin, min: 50, max: 1000, nudge: 40.0 },
+    TunedParam { name: "best_move_change_scale", field: |params| &mut params.best_move_change_scale, min: 100, max: 300, nudge: 15.0 },
+    TunedParam { name: "stable_scale", field: |params| &mut params.stable_scale, min: 20, max: 100, nudge: 8.0 }
+];
+
+/// How long and how boldly the tuner searches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpsaSettings {
+    pub iterations: u32,
+    /// Games played between the two nudged engines every iteration, rounded up to a whole number of pairs
+    /// so each side plays each opening with both colors.
+    pub games_per_iteration: u32,
+    pub time_control: TimeControl,
+    /// How far a param moves for every game won or lost, in nudges.
+    pub learning_rate: f64,
+    /// Seeds the random directions, so a tuning run can be repeated.
+    pub seed: u64
+}
+
+/// Tunes `params` over `settings.iterations` iterations of self-play from `openings`, or the standard position
+/// if there are none. `on_iteration` is called after every iteration with its number and the params so far.
+pub fn tune(mut params: SearchParams, tuned: &[TunedParam], openings: &[Chess], settings: &SpsaSettings,
+            mut on_iteration: impl FnMut(u32, &SearchParams)) -> SearchParams {
+    let mut random = XorShift64::new(settings.seed);
+    //Params are moved in fractions of a point and only rounded for playing, so small steps still add up
+    let mut values: Vec<f64> = tuned.iter().map(|param| *(param.field)(&mut params) as f64).collect();
+    let match_settings = MatchSettings { time_control: settings.time_control, max_games: settings.games_per_iteration.div_ceil(2) * 2, sprt: None };
+
+    for iteration in 0..settings.iterations {
+        let step = settings.learning_rate / ((iteration + 1) as f64).powf(STEP_DECAY);
+        let nudge_scale = 1.0 / ((iteration + 1) as f64).powf(NUDGE_DECAY);
+        let directions: Vec<f64> = tuned.iter().map(|_| if random.next() & 1 == 0 {1.0} else {-1.0}).collect();
+
+        let nudged = |sign: f64| {
+            let mut nudged = params;
+            for ((param, &value), &direction) in tuned.iter().zip(&values).zip(&directions) {
+                let value = value + sign * direction * param.nudge * nudge_scale;
+                *(param.field)(&mut nudged) = (value.round() as i32).clamp(param.min, param.max);
+            }
+            nudged
+        };
+        let (plus, minus) = (nudged(1.0), nudged(-1.0));
+        let engine = |params: SearchParams| move || {
+            let mut engine = Engine::new();
+            engine.set_search_params(params);
+            engine
+        };
+        let result = run_match(engine(plus), engine(minus), openings, &match_settings, |_, _, _| ());
+
+        //Every game won by the engine nudged in the random direction moves the params further that way
+        let lead = result.score.wins as f64 - result.score.losses as f64;
+        for ((param, value), &direction) in tuned.iter().zip(&mut values).zip(&directions) {
+            *value = (*value + step * lead * direction * param.nudge * nudge_scale).clamp(param.min as f64, param.max as f64);
+            *(param.field)(&mut params) = value.round() as i32;
+        }
+        on_iteration(iteration + 1, &params);
+    }
+
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchLimits;
+
+    #[test]
+    //A short run should keep every param in its range and report each iteration
+    fn tuning_stays_in_range() {
+        let settings = SpsaSettings {
+            iterations: 2,
+            games_per_iteration: 2,
+            time_control: TimeControl::Fixed(SearchLimits { max_depth: Some(2), ..SearchLimits::default() }),
+            learning_rate: 1.0,
+            seed: 7
+        };
+        let mut iterations = Vec::new();
+        let mut params = tune(SearchParams::DEFAULT, &TUNED_PARAMS, &[], &settings, |iteration, _| iterations.push(iteration));
+
+        assert!(iterations == [1, 2]);
+        for param in TUNED_PARAMS {
+            let value = *(param.field)(&mut params);
+            assert!((param.min..=param.max).contains(&value));
+        }
+    }
+}
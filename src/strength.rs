@@ -0,0 +1,68 @@
+//! Playing below full strength.
+//!
+//! A skill level below [`MAX_SKILL_LEVEL`] caps the search depth and searches a few root moves exactly, then
+//! settles for a worse move than the best by up to a random amount that grows as the level drops. An Elo
+//! limit instead caps the nodes searched, starting from a handful at [`MIN_ELO`] and doubling every 100 Elo.
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2200;
+
+//Root moves searched exactly when below full skill, so there are alternatives to the best move to choose from
+pub(crate) const SKILL_MULTI_PV: usize = 4;
+//How much further from the best move each level below the maximum may stray, in centipawns
+const SKILL_MARGIN_PER_LEVEL: i32 = 15;
+const MIN_ELO_NODES: f64 = 50.0;
+const ELO_PER_DOUBLING: f64 = 100.0;
+
+/// The deepest iteration searched at `skill_level`.
+pub(crate) fn skill_depth(skill_level: u8) -> u16 {
+    1 + skill_level as u16 / 2
+}
+
+/// The most nodes searched when playing at `elo`.
+pub(crate) fn elo_nodes(elo: u32) -> u64 {
+    let elo = elo.clamp(MIN_ELO, MAX_ELO);
+    (MIN_ELO_NODES * 2f64.powf((elo - MIN_ELO) as f64 / ELO_PER_DOUBLING)) as u64
+}
+
+/// Picks which of the moves with exact `scores` to play. The loss the engine accepts is drawn at random up to
+/// the level's margin, and the worst move within it is played, so low levels play worse moves more often.
+pub(crate) fn pick_move(scores: &[i32], skill_level: u8, random: u64) -> usize {
+    let Some(&best) = scores.iter().max() else {
+        return 0;
+    };
+    let margin = MAX_SKILL_LEVEL.saturating_sub(skill_level) as i32 * SKILL_MARGIN_PER_LEVEL;
+    let allowed_loss = (random % (margin as u64 + 1)) as i32;
+
+    scores.iter().enumerate()
+        .filter(|&(_, &score)| best.saturating_sub(score) <= allowed_loss)
+        .min_by_key(|&(_, &score)| score)
+        .map_or(0, |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    //Moves should only be picked within the allowed loss, and never at full skill
+    fn picked_moves() {
+        let scores = [50, 40, -100, 45];
+        assert!((0..100).all(|random| pick_move(&scores, MAX_SKILL_LEVEL, random) == 0));
+        assert!((0..100).all(|random| pick_move(&scores, 15, random) != 2));
+        assert!(pick_move(&scores, 15, 10) == 1);
+        assert!(pick_move(&scores, 15, 7) == 3);
+        assert!(pick_move(&scores, 0, 300) == 2);
+        assert!(pick_move(&[], 0, 300) == 0);
+    }
+
+    #[test]
+    //Node caps should grow with Elo and stay within the supported range
+    fn elo_node_caps() {
+        assert!(elo_nodes(MIN_ELO) == 50);
+        assert!(elo_nodes(MIN_ELO + 200) == 200);
+        assert!(elo_nodes(0) == elo_nodes(MIN_ELO));
+        assert!(elo_nodes(u32::MAX) == elo_nodes(MAX_ELO));
+    }
+}
@@ -0,0 +1,304 @@
+//! Decides how long a search may take.
+//!
+//! The soft limit is the time the search aims to use. No new iteration is started past it, or when the
+//! next iteration likely won't finish before it. The hard limit stops the search wherever it is, so an
+//! iteration that takes much longer than predicted can't lose the game on time.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use shakmaty::Color;
+
+use crate::clock::Clock;
+use crate::search_params::SearchParams;
+use crate::SearchLimits;
+
+/// Time assumed to be lost on every move to communication with the GUI or server.
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(100);
+//Sudden death time controls are budgeted as if this many moves were left
+const DEFAULT_MOVES_TO_GO: u32 = 20;
+//Most of each increment can be spent since it comes back after the move
+const INCREMENT_FRACTION: f64 = 0.75;
+//An iteration may run this many times past the soft limit before it is cut off
+const HARD_LIMIT_FACTOR: u32 = 3;
+//The hard limit never uses more than this much of the clock
+const MAX_CLOCK_FRACTION: f64 = 0.5;
+//A new iteration isn't started unless at least this much of it is predicted to fit before the soft limit
+const PARTIAL_ITERATION_FRACTION: f64 = 0.4;
+//How the soft limit is scaled when the score drops, see SearchParams for the other scales
+const SCORE_DROP_SCALE: f64 = 1.3;
+//Score drops smaller than this are just noise between iterations
+const SCORE_DROP_MARGIN: i32 = 30;
+//The best move has to survive this many iterations in a row before the soft limit is shortened
+const STABLE_ITERATIONS: u32 = 3;
+//The soft limit once the best move is shown to be far better than every other move
+const EASY_MOVE_SCALE: f64 = 0.2;
+
+/// The speed deterministic searches pretend to search at, turning their time limits into node limits.
+pub const DETERMINISTIC_NODES_PER_SECOND: u64 = 500_000;
+
+/// How long a search may take.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeBudget {
+    /// No new iterations are started after this.
+    pub soft: Duration,
+    /// The search is stopped once this has passed.
+    pub hard: Duration
+}
+
+impl TimeBudget {
+    /// Works out the budget from the move time and the clock of the side to move, leaving `move_overhead`
+    /// on the clock for every move. Returns `None` if the search isn't limited by time.
+    pub fn from_limits(limits: &SearchLimits, turn: Color, move_overhead: Duration) -> Option<TimeBudget> {
+        if limits.infinite {
+            return None;
+        }
+
+        let (time, increment) = match turn {
+            Color::White => (limits.white_time, limits.white_increment),
+            Color::Black => (limits.black_time, limits.black_increment)
+        };
+
+        let clock_budget = time.map(|time| {
+            let remaining = time.saturating_sub(move_overhead);
+            //Keep a move in hand so the last move before the time control isn't played with an empty clock
+            let moves_left = limits.moves_to_go.map_or(DEFAULT_MOVES_TO_GO, |moves_to_go| (moves_to_go + 1).min(DEFAULT_MOVES_TO_GO));
+            let increment = increment.unwrap_or_default().mul_f64(INCREMENT_FRACTION);
+            let soft = (remaining / moves_left + increment).min(remaining);
+            let hard = (soft * HARD_LIMIT_FACTOR).min(remaining.mul_f64(MAX_CLOCK_FRACTION)).max(soft);
+            TimeBudget { soft, hard }
+        });
+
+        //A fixed move time is used in full
+        let move_time_budget = limits.move_time.map(|move_time| TimeBudget { soft: move_time, hard: move_time });
+
+        match (move_time_budget, clock_budget) {
+            (Some(move_time_budget), Some(clock_budget)) => Some(move_time_budget.min(clock_budget)),
+            (move_time_budget, clock_budget) => move_time_budget.or(clock_budget)
+        }
+    }
+
+    /// The nodes a deterministic search spends on the budget, see [`DETERMINISTIC_NODES_PER_SECOND`].
+    pub fn deterministic_nodes(&self) -> u64 {
+        (self.soft.as_micros() as u64 * DETERMINISTIC_NODES_PER_SECOND / 1_000_000).max(1)
+    }
+
+    /// The tighter of each limit from the two budgets.
+    pub fn min(self, other: TimeBudget) -> TimeBudget {
+        TimeBudget {
+            soft: self.soft.min(other.soft),
+            hard: self.hard.min(other.hard)
+        }
+    }
+}
+
+/// Tracks a search's time against its budget.
+///
+/// The soft limit is stretched while the search is unsure of its best move and shortened once the
+/// best move has been stable for a while, but never past the hard limit.
+pub struct TimeManager {
+    clock: Arc<dyn Clock>,
+    start_time: Duration,
+    budget: Option<TimeBudget>,
+    params: SearchParams,
+    soft_scale: f64,
+    stable_iterations: u32,
+    previous_score: Option<i32>,
+    easy_move: bool
+}
+
+impl TimeManager {
+    /// Starts timing a search that began at `clock`'s current time.
+    pub fn new(clock: Arc<dyn Clock>, budget: Option<TimeBudget>) -> TimeManager {
+        TimeManager::with_params(clock, budget, SearchParams::DEFAULT)
+    }
+
+    /// [`TimeManager::new`] with the soft limit scaled by the scales in `params`.
+    pub fn with_params(clock: Arc<dyn Clock>, budget: Option<TimeBudget>, params: SearchParams) -> TimeManager {
+        TimeManager {
+            start_time: clock.now(),
+            clock,
+            budget,
+            params,
+            soft_scale: 1.0,
+            stable_iterations: 0,
+            previous_score: None,
+            easy_move: false
+        }
+    }
+
+    pub fn budget(&self) -> Option<TimeBudget> {
+        self.budget
+    }
+
+    /// Whether the soft limit can be scaled, which it can't for budgets with equal limits such as a fixed move time.
+    pub fn is_scalable(&self) -> bool {
+        self.budget.is_some_and(|budget| budget.soft != budget.hard)
+    }
+
+    /// The soft limit after scaling for the stability of the best move.
+    /// Budgets with equal limits, such as a fixed move time, are used as they are.
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.budget.map(|budget| {
+            if budget.soft == budget.hard {
+                budget.soft
+            }
+            else {
+                budget.soft.mul_f64(self.soft_scale).min(budget.hard)
+            }
+        })
+    }
+
+    /// Time left until the soft limit, `None` if there is no limit.
+    pub fn soft_remaining(&self) -> Option<Duration> {
+        self.soft_limit().map(|soft_limit| soft_limit.saturating_sub(self.clock.now().saturating_sub(self.start_time)))
+    }
+
+    /// Rescales the soft limit after a completed iteration, given whether it changed the best move
+    /// and the score it ended with.
+    pub fn record_iteration(&mut self, best_move_changed: bool, score: i32) {
+        let score_dropped = self.previous_score.is_some_and(|previous_score| score < previous_score.saturating_sub(SCORE_DROP_MARGIN));
+        self.previous_score = Some(score);
+
+        if best_move_changed {
+            self.stable_iterations = 0;
+        }
+        else {
+            self.stable_iterations += 1;
+        }
+
+        self.soft_scale = if best_move_changed {
+            self.params.best_move_change_scale as f64 / 100.0
+        }
+        else if self.stable_iterations >= STABLE_ITERATIONS {
+            self.params.stable_scale as f64 / 100.0
+        }
+        else {
+            1.0
+        };
+
+        if score_dropped {
+            self.soft_scale *= SCORE_DROP_SCALE;
+        }
+
+        self.easy_move &= !best_move_changed && !score_dropped;
+        if self.easy_move {
+            self.soft_scale = EASY_MOVE_SCALE;
+        }
+    }
+
+    /// Cuts the soft limit short because the best move is far better than every other move, such as
+    /// a recapture or the only move that doesn't lose. The cut lasts until the best move changes or the score drops.
+    pub fn set_easy_move(&mut self) {
+        self.easy_move = true;
+        self.soft_scale = EASY_MOVE_SCALE;
+    }
+
+    /// Whether an iteration predicted to take `predicted_time` is worth starting.
+    pub fn should_start_iteration(&self, predicted_time: Option<Duration>) -> bool {
+        match self.soft_remaining() {
+            None => true,
+            Some(remaining) if remaining.is_zero() => false,
+            //Not even the first few root moves would be searched, so the iteration is wasted time
+            Some(remaining) => predicted_time.is_none_or(|predicted_time| predicted_time.mul_f64(PARTIAL_ITERATION_FRACTION) <= remaining)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    //Budgets should come from the clock of the side to move and respect an explicit move time
+    fn budget_from_clock() {
+        let limits = SearchLimits {
+            white_time: Some(Duration::from_millis(60_100)),
+            black_time: Some(Duration::from_millis(2_100)),
+            black_increment: Some(Duration::from_millis(1_000)),
+            ..SearchLimits::default()
+        };
+        let white = TimeBudget::from_limits(&limits, Color::White, DEFAULT_MOVE_OVERHEAD).unwrap();
+        assert!(white == TimeBudget { soft: Duration::from_millis(3_000), hard: Duration::from_millis(9_000) });
+        let black = TimeBudget::from_limits(&limits, Color::Black, DEFAULT_MOVE_OVERHEAD).unwrap();
+        assert!(black == TimeBudget { soft: Duration::from_millis(850), hard: Duration::from_millis(1_000) });
+
+        //Fewer moves until the time control leaves more time for each of them
+        let moves_to_go = SearchLimits { moves_to_go: Some(3), ..limits };
+        assert!(TimeBudget::from_limits(&moves_to_go, Color::White, DEFAULT_MOVE_OVERHEAD).unwrap().soft == Duration::from_millis(15_000));
+        let last_move = SearchLimits { moves_to_go: Some(0), ..limits };
+        assert!(TimeBudget::from_limits(&last_move, Color::Black, DEFAULT_MOVE_OVERHEAD).unwrap() == TimeBudget { soft: Duration::from_millis(2_000), hard: Duration::from_millis(2_000) });
+
+        //A larger overhead leaves less of the clock to spend
+        assert!(TimeBudget::from_limits(&limits, Color::Black, Duration::from_millis(1_100)).unwrap().soft == Duration::from_millis(800));
+        assert!(TimeBudget::from_limits(&limits, Color::Black, Duration::from_secs(10)).unwrap().hard.is_zero());
+
+        let limits = SearchLimits { move_time: Some(Duration::from_millis(250)), ..limits };
+        assert!(TimeBudget::from_limits(&limits, Color::White, DEFAULT_MOVE_OVERHEAD).unwrap() == TimeBudget { soft: Duration::from_millis(250), hard: Duration::from_millis(250) });
+        assert!(TimeBudget::from_limits(&SearchLimits { infinite: true, ..limits }, Color::White, DEFAULT_MOVE_OVERHEAD).is_none());
+        assert!(TimeBudget::from_limits(&SearchLimits::default(), Color::White, DEFAULT_MOVE_OVERHEAD).is_none());
+    }
+
+    #[test]
+    //Iterations shouldn't be started once the soft limit has passed or when they are predicted to take far too long
+    fn starting_iterations() {
+        let budget = TimeBudget { soft: Duration::from_secs(1), hard: Duration::from_secs(3) };
+        let clock = Arc::new(ManualClock::new());
+        let time_manager = TimeManager::new(clock.clone(), Some(budget));
+        assert!(time_manager.should_start_iteration(None));
+        assert!(time_manager.should_start_iteration(Some(Duration::from_millis(2_000))));
+        assert!(!time_manager.should_start_iteration(Some(Duration::from_secs(10))));
+
+        clock.advance(Duration::from_secs(2));
+        assert!(!time_manager.should_start_iteration(None));
+        assert!(TimeManager::new(clock, None).should_start_iteration(Some(Duration::from_secs(1_000))));
+    }
+
+    #[test]
+    //The soft limit should grow when the search is unsure and shrink when it is settled, never passing the hard limit
+    fn stability_scaling() {
+        let budget = TimeBudget { soft: Duration::from_secs(1), hard: Duration::from_secs(2) };
+        let mut time_manager = TimeManager::new(Arc::new(ManualClock::new()), Some(budget));
+        assert!(time_manager.soft_limit() == Some(Duration::from_secs(1)));
+
+        time_manager.record_iteration(false, 50);
+        time_manager.record_iteration(true, 40);
+        assert!(time_manager.soft_limit() == Some(Duration::from_millis(1_600)));
+
+        //A change along with a big drop would go past the hard limit
+        time_manager.record_iteration(true, -100);
+        assert!(time_manager.soft_limit() == Some(Duration::from_secs(2)));
+
+        for _ in 0..STABLE_ITERATIONS {
+            time_manager.record_iteration(false, -100);
+        }
+        assert!(time_manager.soft_limit() == Some(Duration::from_millis(600)));
+
+        let fixed = TimeBudget { soft: Duration::from_secs(1), hard: Duration::from_secs(1) };
+        let mut time_manager = TimeManager::new(Arc::new(ManualClock::new()), Some(fixed));
+        for _ in 0..STABLE_ITERATIONS {
+            time_manager.record_iteration(false, 0);
+        }
+        assert!(time_manager.soft_limit() == Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    //An easy move should cut the soft limit until the best move changes
+    fn easy_move() {
+        let budget = TimeBudget { soft: Duration::from_secs(1), hard: Duration::from_secs(3) };
+        let mut time_manager = TimeManager::new(Arc::new(ManualClock::new()), Some(budget));
+        assert!(time_manager.is_scalable());
+        time_manager.record_iteration(false, 300);
+        time_manager.set_easy_move();
+        assert!(time_manager.soft_limit() == Some(Duration::from_millis(200)));
+        time_manager.record_iteration(false, 310);
+        assert!(time_manager.soft_limit() == Some(Duration::from_millis(200)));
+        time_manager.record_iteration(true, 310);
+        time_manager.record_iteration(false, 310);
+        assert!(time_manager.soft_limit() == Some(Duration::from_secs(1)));
+
+        let fixed = TimeBudget { soft: Duration::from_secs(1), hard: Duration::from_secs(1) };
+        assert!(!TimeManager::new(Arc::new(ManualClock::new()), Some(fixed)).is_scalable());
+    }
+}
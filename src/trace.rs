@@ -0,0 +1,171 @@
+//! Recording the search tree.
+//!
+//! With [`Engine::set_trace_depth`](crate::Engine::set_trace_depth) set, every node the main search visits
+//! down to the given ply is recorded along with its window, score, and whether the transposition table or a
+//! beta cutoff ended it early. [`SearchTrace::to_json`] exports the tree for visualization. Tracing slows the
+//! search down and uses a lot of memory, so it is only compiled in with the `trace` feature.
+
+use std::fmt::Write;
+
+use shakmaty::{CastlingMode, Move};
+
+/// A node visited by the search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceNode {
+    /// The move that led to this node.
+    pub mv: Option<Move>,
+    pub ply: u16,
+    /// Depth left to search, 0 for nodes handed to quiescence search.
+    pub depth: u16,
+    /// The window the node was searched with, from the point of view of the side to move.
+    pub alpha: i32,
+    pub beta: i32,
+    pub score: i32,
+    /// The transposition table gave a score without searching any moves.
+    pub table_hit: bool,
+    /// A move reached `beta`, so the rest weren't searched. It is the last child.
+    pub cutoff: bool,
+    pub children: Vec<TraceNode>
+}
+
+/// The nodes of one iteration of iterative deepening, starting with the positions after each root move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceIteration {
+    pub depth: u16,
+    pub nodes: Vec<TraceNode>
+}
+
+/// The recorded tree of a search, see [`Engine::take_trace`](crate::Engine::take_trace).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchTrace {
+    pub iterations: Vec<TraceIteration>
+}
+
+impl SearchTrace {
+    /// The trace as JSON, with moves in UCI notation.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"iterations\":[");
+        for (index, iteration) in self.iterations.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            write!(json, "{{\"depth\":{},\"nodes\":", iteration.depth).unwrap();
+            write_nodes(&mut json, &iteration.nodes);
+            json.push('}');
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+fn write_nodes(json: &mut String, nodes: &[TraceNode]) {
+    json.push('[');
+    for (index, node) in nodes.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        match &node.mv {
+            Some(mv) => write!(json, "{{\"move\":\"{}\"", mv.to_uci(CastlingMode::Standard)).unwrap(),
+            None => json.push_str("{\"move\":null")
+        }
+        write!(json, ",\"ply\":{},\"depth\":{},\"alpha\":{},\"beta\":{},\"score\":{},\"table_hit\":{},\"cutoff\":{},\"children\":",
+               node.ply, node.depth, node.alpha, node.beta, node.score, node.table_hit, node.cutoff).unwrap();
+        write_nodes(json, &node.children);
+        json.push('}');
+    }
+    json.push(']');
+}
+
+/// Builds a [`SearchTrace`] as the search enters and leaves nodes.
+pub(crate) struct Tracer {
+    max_ply: u16,
+    //Nodes that have been entered but not left yet, innermost last
+    open: Vec<TraceNode>,
+    trace: SearchTrace
+}
+
+impl Tracer {
+    pub(crate) fn new(max_ply: u16) -> Tracer {
+        Tracer {
+            max_ply,
+            open: Vec::new(),
+            trace: SearchTrace::default()
+        }
+    }
+
+    pub(crate) fn begin_iteration(&mut self, depth: u16) {
+        self.trace.iterations.push(TraceIteration { depth, nodes: Vec::new() });
+    }
+
+    pub(crate) fn enter(&mut self, mv: Option<&Move>, ply: u16, depth: u16, alpha: i32, beta: i32) {
+        if ply <= self.max_ply {
+            self.open.push(TraceNode {
+                mv: mv.cloned(),
+                ply,
+                depth,
+                alpha,
+                beta,
+                score: 0,
+                table_hit: false,
+                cutoff: false,
+                children: Vec::new()
+            });
+        }
+    }
+
+    /// Updates the innermost open node, if the search is at a ply that is traced.
+    pub(crate) fn note(&mut self, ply: u16, note: impl FnOnce(&mut TraceNode)) {
+        if ply <= self.max_ply {
+            if let Some(node) = self.open.last_mut() {
+                note(node);
+            }
+        }
+    }
+
+    pub(crate) fn leave(&mut self, ply: u16, score: i32) {
+        if ply > self.max_ply {
+            return;
+        }
+        let Some(mut node) = self.open.pop() else {
+            return;
+        };
+
+        node.score = score;
+        match (self.open.last_mut(), self.trace.iterations.last_mut()) {
+            (Some(parent), _) => parent.children.push(node),
+            (None, Some(iteration)) => iteration.nodes.push(node),
+            (None, None) => {}
+        }
+    }
+
+    pub(crate) fn finish(self) -> SearchTrace {
+        self.trace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::Square;
+
+    #[test]
+    //Nodes should nest under the node they were entered from, and plies past the maximum should be left out
+    fn building() {
+        let e4 = Move::Normal { role: shakmaty::Role::Pawn, from: Square::E2, capture: None, to: Square::E4, promotion: None };
+        let mut tracer = Tracer::new(1);
+        tracer.begin_iteration(2);
+        tracer.enter(Some(&e4), 1, 1, -10, 10);
+        tracer.enter(None, 2, 0, -10, 10);
+        tracer.note(2, |node| node.table_hit = true);
+        tracer.leave(2, 3);
+        tracer.note(1, |node| node.cutoff = true);
+        tracer.leave(1, 5);
+
+        let trace = tracer.finish();
+        assert!(trace.iterations.len() == 1);
+        let node = &trace.iterations[0].nodes[0];
+        assert!(node.score == 5 && node.cutoff && !node.table_hit && node.children.is_empty());
+        assert!(trace.to_json() == "{\"iterations\":[{\"depth\":2,\"nodes\":[{\"move\":\"e2e4\",\"ply\":1,\"depth\":1,\"alpha\":-10,\"beta\":10,\
+                                    \"score\":5,\"table_hit\":false,\"cutoff\":true,\"children\":[]}]}]}");
+    }
+}
@@ -0,0 +1,429 @@
+//! Texel tuning of the evaluation against positions labelled with game results.
+//!
+//! Evaluations are turned into expected results with [`crate::dataset::expected_result`], and the tuner
+//! looks for the piece square tables and [`EvalParams`] that bring those closest to the actual results. `k` is
+//! fitted once up front, then every value is nudged up and down in turn, keeping any change that lowers the error.
+//! Tuned tables can be written out with [`PieceSquareTables::to_source`] and read back by a running engine
+//! with [`PieceSquareTables::from_source`], see [`crate::Engine::set_piece_square_tables`]. Tuned params go the
+//! same way with [`EvalParams::to_source`] and [`EvalParams::from_source`], see [`crate::Engine::set_eval_params`].
+
+use std::fmt::Write;
+use std::io;
+
+use shakmaty::{Board, Position};
+
+use crate::dataset::{expected_result, LabeledPosition};
+use crate::evaluation::evaluate_board_detailed_with_tables;
+pub use crate::evaluation::EvalParams;
+pub use crate::piece_square_tables::PieceSquareTables;
+
+//Range searched for the best k
+const MIN_K: f64 = 0.05;
+const MAX_K: f64 = 3.0;
+const K_SEARCH_ITERATIONS: u32 = 40;
+//Table values are kept positive, since the trade bonus divides by the total material
+const MIN_TABLE_VALUE: i32 = 1;
+const TABLE_VALUES: usize = 6 * 64;
+//The weights of the tables are left alone, since the tables themselves are tuned
+const UNTUNED_EVAL_PARAMS: [&str; 2] = ["material", "piece_squares"];
+//Scales are the percent of the evaluation kept, so they stay between none and all of it
+const MAX_SCALE: i32 = 100;
+
+type EvalParamValues = fn(&mut EvalParams) -> &mut [i32];
+
+/// Every field of [`EvalParams`] in the order they are declared, as a slice of one for a single value.
+const EVAL_PARAM_FIELDS: [(&str, EvalParamValues); 31] = [
+    ("material", |params| std::slice::from_mut(&mut params.material)),
+    ("piece_squares", |params| std::slice::from_mut(&mut params.piece_squares)),
+    ("trade_bonus", |params| std::slice::from_mut(&mut params.trade_bonus)),
+    ("pawn_structure", |params| std::slice::from_mut(&mut params.pawn_structure)),
+    ("doubled_pawn_penalty", |params| std::slice::from_mut(&mut params.doubled_pawn_penalty)),
+    ("isolated_pawn_penalty", |params| std::slice::from_mut(&mut params.isolated_pawn_penalty)),
+    ("passed_pawn_bonus", |params| &mut params.passed_pawn_bonus[..]),
+    ("bishop_pair_bonus", |params| std::slice::from_mut(&mut params.bishop_pair_bonus)),
+    ("redundant_major_penalty", |params| std::slice::from_mut(&mut params.redundant_major_penalty)),
+    ("minors_for_rook_bonus", |params| std::slice::from_mut(&mut params.minors_for_rook_bonus)),
+    ("pieces_for_queen_bonus", |params| std::slice::from_mut(&mut params.pieces_for_queen_bonus)),
+    ("knight_outpost_bonus", |params| std::slice::from_mut(&mut params.knight_outpost_bonus)),
+    ("bad_bishop_penalty", |params| std::slice::from_mut(&mut params.bad_bishop_penalty)),
+    ("trapped_bishop_penalty", |params| std::slice::from_mut(&mut params.trapped_bishop_penalty)),
+    ("trapped_knight_penalty", |params| std::slice::from_mut(&mut params.trapped_knight_penalty)),
+    ("trapped_rook_penalty", |params| std::slice::from_mut(&mut params.trapped_rook_penalty)),
+    ("development", |params| std::slice::from_mut(&mut params.development)),
+    ("undeveloped_minor_penalty", |params| std::slice::from_mut(&mut params.undeveloped_minor_penalty)),
+    ("early_queen_penalty", |params| std::slice::from_mut(&mut params.early_queen_penalty)),
+    ("castled_king_bonus", |params| std::slice::from_mut(&mut params.castled_king_bonus)),
+    ("king_attack", |params| std::slice::from_mut(&mut params.king_attack)),
+    ("king_tropism", |params| std::slice::from_mut(&mut params.king_tropism)),
+    ("pawn_storm_bonus", |params| &mut params.pawn_storm_bonus[..]),
+    ("unstoppable_passer_bonus", |params| std::slice::from_mut(&mut params.unstoppable_passer_bonus)),
+    ("passer_king_proximity_bonus", |params| std::slice::from_mut(&mut params.passer_king_proximity_bonus)),
+    ("threats", |params| std::slice::from_mut(&mut params.threats)),
+    ("threat_bonus", |params| std::slice::from_mut(&mut params.threat_bonus)),
+    ("hanging_piece_bonus", |params| std::slice::from_mut(&mut params.hanging_piece_bonus)),
+    ("pawnless_scale", |params| std::slice::from_mut(&mut params.pawnless_scale)),
+    ("opposite_bishops_scale", |params| std::slice::from_mut(&mut params.opposite_bishops_scale)),
+    ("rook_ending_scale", |params| std::slice::from_mut(&mut params.rook_ending_scale))
+];
+
+/// How long and how finely the tuner searches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TuningSettings {
+    /// How far each value is moved at a time.
+    pub step: i32,
+    /// Tuning stops after this many passes over every value, or earlier once a pass changes nothing.
+    pub max_passes: u32
+}
+
+impl Default for TuningSettings {
+    fn default() -> TuningSettings {
+        TuningSettings {
+            step: 1,
+            max_passes: 100
+        }
+    }
+}
+
+/// What the tuner adjusts: the piece square tables and the params of the terms evaluated along with them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TunedEvaluation {
+    pub tables: PieceSquareTables,
+    pub params: EvalParams
+}
+
+impl TunedEvaluation {
+    /// The engine's own tables and params.
+    pub const DEFAULT: TunedEvaluation = TunedEvaluation {
+        tables: PieceSquareTables::DEFAULT,
+        params: EvalParams::DEFAULT
+    };
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        evaluate_board_detailed_with_tables(board, &self.params, &self.tables).total()
+    }
+}
+
+impl Default for TunedEvaluation {
+    fn default() -> TunedEvaluation {
+        TunedEvaluation::DEFAULT
+    }
+}
+
+/// The mean squared error between the results expected from `evaluation` and the actual results.
+pub fn mean_squared_error(positions: &[LabeledPosition], k: f64, evaluation: &TunedEvaluation) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+
+    let total_error: f64 = positions.iter().map(|position| {
+        let error = position.result - expected_result(evaluation.evaluate(position.chess.board()), k);
+        error * error
+    }).sum();
+
+    total_error / positions.len() as f64
+}
+
+/// The `k` that gives the lowest error for `evaluation`, found with a ternary search since the error has a single minimum in `k`.
+pub fn find_k(positions: &[LabeledPosition], evaluation: &TunedEvaluation) -> f64 {
+    let (mut low, mut high) = (MIN_K, MAX_K);
+
+    for _ in 0..K_SEARCH_ITERATIONS {
+        let first_third = low + (high - low) / 3.0;
+        let second_third = high - (high - low) / 3.0;
+        if mean_squared_error(positions, first_third, evaluation) < mean_squared_error(positions, second_third, evaluation) {
+            high = second_third;
+        }
+        else {
+            low = first_third;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+/// Tunes the tables and params of `evaluation` against `positions` with a local search. `on_pass` is called
+/// after every pass with the pass number and the error the pass ended with.
+pub fn tune(positions: &[LabeledPosition], k: f64, mut evaluation: TunedEvaluation, settings: TuningSettings,
+            mut on_pass: impl FnMut(u32, f64)) -> TunedEvaluation {
+    let mut best_error = mean_squared_error(positions, k, &evaluation);
+
+    for pass in 1..=settings.max_passes {
+        let mut improved = false;
+
+        for index in tunable_indices() {
+            let original = *value_mut(&mut evaluation, index);
+            let (min, max) = value_range(index);
+
+            for change in [settings.step, -settings.step] {
+                let value = (original + change).clamp(min, max);
+                if value == original {
+                    continue;
+                }
+
+                *value_mut(&mut evaluation, index) = value;
+                let error = mean_squared_error(positions, k, &evaluation);
+                if error < best_error {
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+                *value_mut(&mut evaluation, index) = original;
+            }
+        }
+
+        on_pass(pass, best_error);
+        if !improved {
+            break;
+        }
+    }
+
+    evaluation
+}
+
+/// Every table entry by index, table after table in the order of [`PieceSquareTables`]' fields, followed by every
+/// param value in the order of [`EvalParams`]' fields. Pawns can't stand on the first or last rank, so those
+/// entries are left out, and so are the weights of the tables.
+fn tunable_indices() -> impl Iterator<Item = usize> {
+    let tables = (0..TABLE_VALUES).filter(|&index| index >= 64 || (8..56).contains(&index));
+    let params = (TABLE_VALUES..TABLE_VALUES + eval_param_count())
+        .filter(|&index| !UNTUNED_EVAL_PARAMS.contains(&eval_param(index - TABLE_VALUES).0));
+    tables.chain(params)
+}
+
+fn eval_param_count() -> usize {
+    let mut params = EvalParams::DEFAULT;
+    EVAL_PARAM_FIELDS.iter().map(|(_, values)| values(&mut params).len()).sum()
+}
+
+/// The name of the param holding the `index`th param value, how to reach its values and where the value is among them.
+fn eval_param(mut index: usize) -> (&'static str, EvalParamValues, usize) {
+    let mut params = EvalParams::DEFAULT;
+    for (name, values) in EVAL_PARAM_FIELDS {
+        let length = values(&mut params).len();
+        if index < length {
+            return (name, values, index);
+        }
+        index -= length;
+    }
+    panic!("param value index out of range");
+}
+
+fn value_mut(evaluation: &mut TunedEvaluation, index: usize) -> &mut i32 {
+    if index >= TABLE_VALUES {
+        let (_, values, offset) = eval_param(index - TABLE_VALUES);
+        return &mut values(&mut evaluation.params)[offset];
+    }
+
+    let tables = &mut evaluation.tables;
+    let table = match index / 64 {
+        0 => &mut tables.pawn,
+        1 => &mut tables.knight,
+        2 => &mut tables.bishop,
+        3 => &mut tables.rook,
+        4 => &mut tables.queen,
+        _ => &mut tables.king
+    };
+    &mut table[index % 64]
+}
+
+//Params can't go below zero, which would turn bonuses into penalties and the other way around
+fn value_range(index: usize) -> (i32, i32) {
+    if index < TABLE_VALUES {
+        return (MIN_TABLE_VALUE, i32::MAX);
+    }
+    let (name, ..) = eval_param(index - TABLE_VALUES);
+    (0, if name.ends_with("_scale") {MAX_SCALE} else {i32::MAX})
+}
+
+impl PieceSquareTables {
+    /// The tables written out as constants in the layout of the engine's own tables, ready to be pasted in.
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+
+        for (name, table) in [("PAWN", &self.pawn), ("KNIGHT", &self.knight), ("BISHOP", &self.bishop),
+                              ("ROOK", &self.rook), ("QUEEN", &self.queen), ("KING", &self.king)] {
+            let _ = writeln!(source, "pub const {}: [i32; 64] = [", name);
+            for (row_index, row) in table.chunks(8).enumerate() {
+                let values: Vec<String> = row.iter().map(|value| format!("{:>4}", value)).collect();
+                let separator = if row_index < 7 {","} else {""};
+                let _ = writeln!(source, "{}{}", values.join(","), separator);
+            }
+            let _ = writeln!(source, "];\n");
+        }
+
+        source
+    }
+
+    /// Reads tables written by [`PieceSquareTables::to_source`], or pasted from the engine's own source.
+    pub fn from_source(source: &str) -> io::Result<PieceSquareTables> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut tables = PieceSquareTables::DEFAULT;
+
+        for (name, table) in [("PAWN", &mut tables.pawn), ("KNIGHT", &mut tables.knight), ("BISHOP", &mut tables.bishop),
+                              ("ROOK", &mut tables.rook), ("QUEEN", &mut tables.queen), ("KING", &mut tables.king)] {
+            //The values are between the "= [" after the table's name and the next "]"
+            let start = source.find(&format!("const {}:", name)).ok_or_else(|| invalid(format!("no {} table", name)))?;
+            let values = &source[start..];
+            let values = values.find("= [").map(|open| &values[open + 3..]).ok_or_else(|| invalid(format!("no values for the {} table", name)))?;
+            let values = &values[..values.find(']').ok_or_else(|| invalid(format!("unclosed {} table", name)))?];
+
+            let values = values.split(',').map(str::trim).filter(|value| !value.is_empty())
+                .map(|value| value.parse().map_err(|_| invalid(format!("invalid value {} in the {} table", value, name))))
+                .collect::<io::Result<Vec<i32>>>()?;
+            let length = values.len();
+            *table = values.try_into().map_err(|_| invalid(format!("the {} table has {} values instead of 64", name, length)))?;
+        }
+
+        Ok(tables)
+    }
+
+    /// Reads a file written with [`PieceSquareTables::to_source`].
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<PieceSquareTables> {
+        PieceSquareTables::from_source(&std::fs::read_to_string(path)?)
+    }
+}
+
+impl EvalParams {
+    /// The params written out as a constant in the layout of [`EvalParams::DEFAULT`], ready to be pasted in.
+    pub fn to_source(&self) -> String {
+        let mut params = *self;
+        let mut source = String::from("pub const DEFAULT: EvalParams = EvalParams {\n");
+
+        for (index, (name, values)) in EVAL_PARAM_FIELDS.iter().enumerate() {
+            let values = values(&mut params);
+            let value = match values {
+                [value] => value.to_string(),
+                values => format!("[{}]", values.iter().map(i32::to_string).collect::<Vec<_>>().join(", "))
+            };
+            let separator = if index + 1 < EVAL_PARAM_FIELDS.len() {","} else {""};
+            let _ = writeln!(source, "    {}: {}{}", name, value, separator);
+        }
+        source.push_str("};\n");
+
+        source
+    }
+
+    /// Reads params written by [`EvalParams::to_source`], or pasted from the engine's own source.
+    pub fn from_source(source: &str) -> io::Result<EvalParams> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut params = EvalParams::DEFAULT;
+
+        for (name, values) in EVAL_PARAM_FIELDS {
+            //The name has to start a word, so "threats" isn't found at the end of another param's name
+            let start = source.match_indices(&format!("{}:", name))
+                .find(|&(start, _)| !source[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+                .map(|(start, _)| start + name.len() + 1)
+                .ok_or_else(|| invalid(format!("no {} param", name)))?;
+            let text = source[start..].trim_start();
+            //A list of values runs to the closing bracket, a single value to the end of the field
+            let text = match text.strip_prefix('[') {
+                Some(list) => &list[..list.find(']').ok_or_else(|| invalid(format!("unclosed {} param", name)))?],
+                None => &text[..text.find([',', '\n', '}']).unwrap_or(text.len())]
+            };
+
+            let parsed = text.split(',').map(str::trim).filter(|value| !value.is_empty())
+                .map(|value| value.parse().map_err(|_| invalid(format!("invalid value {} for the {} param", value, name))))
+                .collect::<io::Result<Vec<i32>>>()?;
+            let values = values(&mut params);
+            if parsed.len() != values.len() {
+                return Err(invalid(format!("the {} param has {} values instead of {}", name, parsed.len(), values.len())));
+            }
+            values.copy_from_slice(&parsed);
+        }
+
+        Ok(params)
+    }
+
+    /// Reads a file written with [`EvalParams::to_source`].
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<EvalParams> {
+        EvalParams::from_source(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::read_dataset;
+
+    const DATASET: &str = "
+4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 1-0
+4k3/8/8/8/8/8/3P4/4K3 w - - 0 1 1/2-1/2
+4k3/8/8/8/8/4P3/8/4K3 w - - 0 1 1-0
+4k3/4p3/8/8/8/8/8/4K3 w - - 0 1 0-1
+";
+
+    #[test]
+    //Tuning should lower the error, leave the entries pawns never use alone and keep the params in range
+    fn tuning_lowers_error() {
+        let positions = read_dataset(DATASET.as_bytes()).expect("dataset should be valid");
+        let k = find_k(&positions, &TunedEvaluation::DEFAULT);
+        assert!((MIN_K..=MAX_K).contains(&k));
+        let before = mean_squared_error(&positions, k, &TunedEvaluation::DEFAULT);
+
+        let mut passes = Vec::new();
+        let settings = TuningSettings { step: 5, max_passes: 2 };
+        let tuned = tune(&positions, k, TunedEvaluation::DEFAULT, settings, |pass, error| passes.push((pass, error)));
+        let after = mean_squared_error(&positions, k, &tuned);
+
+        assert!(after < before);
+        assert!(passes.last().is_some_and(|&(_, error)| error == after));
+        assert!(tuned != TunedEvaluation::DEFAULT);
+        assert!(tuned.tables.pawn[..8] == PieceSquareTables::DEFAULT.pawn[..8]);
+        assert!(tuned.params.material == EvalParams::DEFAULT.material);
+        assert!(tuned.params.piece_squares == EvalParams::DEFAULT.piece_squares);
+        assert!((0..=MAX_SCALE).contains(&tuned.params.pawnless_scale));
+    }
+
+    #[test]
+    //Every param value after the tables should be tunable except the weights of the tables
+    fn tunable_params() {
+        let params = tunable_indices().filter(|&index| index >= TABLE_VALUES).count();
+        assert!(params == eval_param_count() - UNTUNED_EVAL_PARAMS.len());
+        assert!(eval_param_count() == EVAL_PARAM_FIELDS.len() + 2 * 7);
+
+        let mut evaluation = TunedEvaluation::DEFAULT;
+        let passed_pawn = TABLE_VALUES + EVAL_PARAM_FIELDS.iter().position(|&(name, _)| name == "passed_pawn_bonus").unwrap() + 6;
+        *value_mut(&mut evaluation, passed_pawn) += 1;
+        assert!(evaluation.params.passed_pawn_bonus[6] == EvalParams::DEFAULT.passed_pawn_bonus[6] + 1);
+        assert!(value_range(TABLE_VALUES + eval_param_count() - 1) == (0, MAX_SCALE));
+    }
+
+    #[test]
+    //The written tables should hold every value
+    fn source_output() {
+        let source = PieceSquareTables::DEFAULT.to_source();
+        assert!(source.starts_with("pub const PAWN: [i32; 64] = [\n 100, 100,"));
+        assert!(source.matches("pub const").count() == 6);
+        assert!(source.matches(',').count() == 6 * 63);
+    }
+
+    #[test]
+    //Written tables should read back the same, and tables with values missing shouldn't read at all
+    fn source_round_trip() {
+        let mut tables = PieceSquareTables::DEFAULT;
+        tables.knight[27] += 15;
+        assert!(PieceSquareTables::from_source(&tables.to_source()).expect("tables should read back") == tables);
+
+        let source = PieceSquareTables::DEFAULT.to_source().replacen(" 100,", "", 1);
+        assert!(PieceSquareTables::from_source(&source).is_err());
+        assert!(PieceSquareTables::from_source("").is_err());
+    }
+
+    #[test]
+    //Written params should read back the same, and params with a field or a value missing shouldn't read at all
+    fn params_round_trip() {
+        let mut params = EvalParams::DEFAULT;
+        params.threats += 10;
+        params.pawn_storm_bonus[3] += 2;
+        let source = params.to_source();
+        assert!(source.starts_with("pub const DEFAULT: EvalParams = EvalParams {\n    material: 100,\n"));
+        assert!(EvalParams::from_source(&source).expect("params should read back") == params);
+
+        assert!(EvalParams::from_source(&source.replace("    threat_bonus: 30,\n", "")).is_err());
+        assert!(EvalParams::from_source(&source.replace("[0, 5, ", "[5, ")).is_err());
+        assert!(EvalParams::from_source("").is_err());
+    }
+}
@@ -0,0 +1,728 @@
+//! The UCI protocol.
+//!
+//! [`run`] reads commands from any reader and writes the engine's replies to any writer, so a frontend
+//! can embed the protocol handler in place of standard input and output, and tests can drive it with
+//! a script of commands. The `hodgey_chess_engine` binary runs it on standard input and output.
+//!
+//! Searches run on their own thread, so `stop`, `ponderhit` and `isready` are answered while they run. Other
+//! commands wait for the search to finish first, stopping it if it would otherwise only end when told to.
+//!
+//! Besides the standard commands it understands `perft <depth>` (or `go perft <depth>`), `bench` and `eval`.
+//! The `Debug Log File` option records every line read and written, with timestamps, to help debugging.
+
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use shakmaty::{CastlingMode, Move, Position, fen::Fen, uci::UciMove};
+use shakmaty::variant::{Variant, VariantPosition};
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+
+use crate::book::Book;
+use crate::evaluator::{evaluate_board_detailed_with_params, EvalParams, Evaluator};
+use crate::mate_search::find_mate;
+use crate::personality::Personality;
+use crate::repertoire::Repertoire;
+use crate::strength::{MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+use crate::tuning::PieceSquareTables;
+use crate::{perft, Engine, SearchEvent, SearchHandle, SearchLimits, SearchResult};
+
+//Variants offered through UCI_Variant, the first is the default
+const UCI_VARIANTS: [Variant; 5] = [Variant::Chess, Variant::Crazyhouse, Variant::Antichess, Variant::KingOfTheHill, Variant::ThreeCheck];
+const MAX_THREADS: usize = 64;
+type EvalParamField = fn(&mut EvalParams) -> &mut i32;
+//Eval params offered as spin options, with the highest value each may be set to
+const EVAL_PARAM_OPTIONS: [(&str, i32, EvalParamField); 13] = [
+    ("Material Weight", 300, |params| &mut params.material),
+    ("Piece Square Weight", 300, |params| &mut params.piece_squares),
+    ("Trade Bonus", 500, |params| &mut params.trade_bonus),
+    ("Pawn Structure Weight", 300, |params| &mut params.pawn_structure),
+    ("Doubled Pawn Penalty", 200, |params| &mut params.doubled_pawn_penalty),
+    ("Isolated Pawn Penalty", 200, |params| &mut params.isolated_pawn_penalty),
+    ("Bishop Pair Bonus", 200, |params| &mut params.bishop_pair_bonus),
+    ("Knight Outpost Bonus", 200, |params| &mut params.knight_outpost_bonus),
+    ("Bad Bishop Penalty", 100, |params| &mut params.bad_bishop_penalty),
+    ("Development Weight", 300, |params| &mut params.development),
+    ("King Attack Weight", 300, |params| &mut params.king_attack),
+    ("Threats Weight", 300, |params| &mut params.threats),
+    ("Opposite Bishops Scale", 100, |params| &mut params.opposite_bishops_scale)
+];
+
+/// Settings for [`run`] that apply before any UCI command is read.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UciOptions {
+    /// The file the session is logged to until the `Debug Log File` option changes it. Nothing is logged by default.
+    pub debug_log_file: Option<PathBuf>
+}
+
+/// Runs the UCI loop, reading commands from `reader` until `quit` or the end of the input and writing the
+/// replies to `writer`. A search still running then is stopped, and its move sent, before returning. Malformed commands are skipped with an `info string` saying what was wrong with them.
+/// Fails if the input can't be read.
+pub fn run<R: BufRead, W: Write + Send + 'static>(mut reader: R, writer: W, options: UciOptions) -> io::Result<()> {
+    let mut game_state = GameState {
+        output: Output::new(Box::new(writer)),
+        ..GameState::default()
+    };
+    game_state.engine.set_report_info(true);
+    set_progress_callback(&mut game_state);
+    if let Some(path) = &options.debug_log_file {
+        open_debug_log(&game_state.output, path);
+    }
+
+    loop {
+        //Lines that aren't valid UTF-8 are kept as far as they can be read rather than ending the loop
+        let mut input_bytes = Vec::new();
+        if reader.read_until(b'\n', &mut input_bytes)? == 0 {
+            break;
+        }
+        let input_buffer = String::from_utf8_lossy(&input_bytes);
+
+        game_state.output.log_input(input_buffer.trim_end());
+
+        let mut input_tokens = input_buffer.split_whitespace();
+
+        let first_token = match input_tokens.next() {
+            Some(token) => token,
+            None => continue
+        };
+
+        //The search has the engine until it finishes, and its position and options shouldn't change under it
+        if matches!(first_token, "debug" | "setoption" | "position" | "go" | "perft" | "bench" | "eval") {
+            wait_for_search(&mut game_state);
+        }
+
+        match first_token {
+            "uci" => {
+                identify_engine(&game_state.output);
+                display_options(&game_state.output);
+                game_state.output.line("uciok");
+            },
+            "isready" => game_state.output.line("readyok"),
+            "debug" => game_state.engine.set_debug(input_tokens.next() == Some("on")),
+            "quit" => break,
+            "stop" => stop_search(&mut game_state, true),
+            //The move that was pondered on was played, so it is searched again as a normal search that starts
+            //from the table the ponder search filled
+            "ponderhit" => if let Some(search) = game_state.search.as_mut() {
+                if let Some((limits, search_moves)) = search.ponder.take() {
+                    stop_search(&mut game_state, false);
+                    game_state.engine.keep_table();
+                    start_search(&mut game_state, GoCommand { limits, mate: None, search_moves, ponder: false });
+                }
+            },
+            "setoption" => set_option(input_tokens.collect(), &mut game_state),
+            "position" => {
+                //The previous position is kept, so a search that follows still has something sensible to search
+                if let Err(error) = update_position(input_tokens.map(|str| str.to_string()).collect(), &mut game_state) {
+                    game_state.output.line(format_args!("info string ignoring position: {}", error));
+                }
+            },
+            //"go perft <depth>" counts moves instead of searching
+            "go" if input_tokens.clone().next() == Some("perft") => print_perft(&game_state, input_tokens.nth(1)),
+            "perft" => print_perft(&game_state, input_tokens.next()),
+            "bench" => game_state.output.text(Engine::bench()),
+            "eval" => print_eval(&game_state),
+            "go" => {
+                let go = parse_go(input_tokens, &game_state.chess);
+                start_search(&mut game_state, go);
+            }
+            _ => continue //Ignore unknown commands
+        }
+    }
+
+    //Once the GUI quits or closes the input nothing is left to wait for the move, so the search isn't left running
+    stop_search(&mut game_state, true);
+    Ok(())
+}
+
+#[derive(Default)]
+struct GameState {
+    engine: Engine<VariantPosition>,
+    chess: VariantPosition,
+    variant: Variant,
+    previously_seen_hashes: Vec<u64>,
+    own_book: bool,
+    book_file: Option<String>,
+    chess960: bool,
+    show_wdl: bool,
+    limit_strength: bool,
+    elo: Option<u32>,
+    output: Output,
+    search: Option<RunningSearch>
+}
+
+/// A search running on its own thread, which has the engine until it finishes.
+struct RunningSearch {
+    thread: JoinHandle<Engine<VariantPosition>>,
+    handle: SearchHandle,
+    //Infinite and pondering searches hold their bestmove until told whether to send it
+    release: Option<Sender<bool>>,
+    //What to search once the pondered move is played
+    ponder: Option<(SearchLimits, Vec<Move>)>
+}
+
+/// The parameters of a `go` command.
+struct GoCommand {
+    limits: SearchLimits,
+    mate: Option<u32>,
+    search_moves: Vec<Move>,
+    ponder: bool
+}
+
+//Values that are missing or can't be read leave their limit unset, and unknown parameters are skipped
+fn parse_go<'a>(mut tokens: impl Iterator<Item = &'a str> + Clone, chess: &VariantPosition) -> GoCommand {
+    let mut go = GoCommand {
+        limits: SearchLimits::default(),
+        mate: None,
+        search_moves: Vec::new(),
+        ponder: false
+    };
+
+    while let Some(value_type) = tokens.next() {
+        let mut next_millis = || tokens.next().and_then(|value| value.parse().ok()).map(Duration::from_millis);
+
+        match value_type {
+            "wtime" => go.limits.white_time = next_millis(),
+            "btime" => go.limits.black_time = next_millis(),
+            "winc" => go.limits.white_increment = next_millis(),
+            "binc" => go.limits.black_increment = next_millis(),
+            "movestogo" => go.limits.moves_to_go = tokens.next().and_then(|value| value.parse().ok()),
+            "movetime" => go.limits.move_time = next_millis(),
+            "depth" => go.limits.max_depth = tokens.next().and_then(|value| value.parse().ok()),
+            "nodes" => go.limits.max_nodes = tokens.next().and_then(|value| value.parse().ok()),
+            "mate" => go.mate = tokens.next().and_then(|value| value.parse().ok()),
+            "infinite" => go.limits.infinite = true,
+            "ponder" => go.ponder = true,
+            //The moves run until the first token that isn't a legal move
+            "searchmoves" => while let Some(m) = tokens.clone().next().and_then(|m| parse_move(m, chess)) {
+                go.search_moves.push(m);
+                tokens.next();
+            },
+            _ => continue
+        }
+    }
+
+    //A bare "go" searches until it is stopped, as in other engines
+    if go.limits == SearchLimits::default() && go.mate.is_none() {
+        go.limits.infinite = true;
+    }
+    go
+}
+
+//Pondering searches without limits until the GUI says whether the pondered move was played
+fn start_search(game_state: &mut GameState, go: GoCommand) {
+    let ponder = go.ponder.then(|| (go.limits, go.search_moves.clone()));
+    let limits = SearchLimits { infinite: go.limits.infinite || go.ponder, ..go.limits };
+    let (release, released) = match limits.infinite {
+        true => {
+            let (release, released) = mpsc::channel();
+            (Some(release), Some(released))
+        },
+        false => (None, None)
+    };
+
+    let mut engine = std::mem::take(&mut game_state.engine);
+    let handle = engine.handle();
+    let chess = game_state.chess.clone();
+    let mut previously_seen_hashes = game_state.previously_seen_hashes.clone();
+    let output = game_state.output.clone();
+    let castling_mode = CastlingMode::from_chess960(game_state.chess960);
+    let show_wdl = game_state.show_wdl;
+    let thread = thread::spawn(move || {
+        //"go mate <moves>" only looks for forced mates, and falls back to a normal search restricted to any
+        //searchmoves when there is none
+        let best_move = match go.mate.and_then(|moves| find_mate(&chess, moves)) {
+            Some(line) => {
+                let pv: Vec<String> = line.iter().map(|m| m.to_uci(castling_mode).to_string()).collect();
+                output.line(format_args!("info depth {} score mate {} pv {}", line.len(), line.len().div_ceil(2), pv.join(" ")));
+                line.into_iter().next()
+            },
+            None => {
+                let result = engine.search_moves(&chess, limits, &mut previously_seen_hashes, &go.search_moves);
+                //Completed iterations are already reported by the progress callback
+                if result.depth == 0 {
+                    print_info(&output, &result, castling_mode, show_wdl);
+                }
+                result.best_move
+            }
+        };
+        if released.is_none_or(|released| released.recv().unwrap_or(true)) {
+            match best_move {
+                //Chess960 castling is sent as the king taking its own rook
+                Some(best_move) => output.line(format_args!("bestmove {}", best_move.to_uci(castling_mode))),
+                //UCI's null move, sent when the game is already over
+                None => output.line("bestmove 0000")
+            }
+        }
+        engine
+    });
+
+    game_state.search = Some(RunningSearch { thread, handle, release, ponder });
+}
+
+//Stops the search, if there is one, and takes the engine back once it has finished
+fn stop_search(game_state: &mut GameState, send_best_move: bool) {
+    let Some(search) = game_state.search.take() else {
+        return;
+    };
+    if let Some(release) = &search.release {
+        let _ = release.send(send_best_move);
+    }
+    //A stop sent before the thread began searching is cleared when the search starts, so it is repeated
+    //until the thread has finished
+    while !search.thread.is_finished() {
+        search.handle.stop();
+        thread::sleep(Duration::from_millis(1));
+    }
+    game_state.engine = search.thread.join().expect("search thread shouldn't panic");
+}
+
+//Searches with limits are left to finish, the others would never finish unless stopped
+fn wait_for_search(game_state: &mut GameState) {
+    match &game_state.search {
+        Some(search) if search.release.is_none() => {
+            let search = game_state.search.take().expect("search should be running");
+            game_state.engine = search.thread.join().expect("search thread shouldn't panic");
+        },
+        Some(_) => stop_search(game_state, true),
+        None => {}
+    }
+}
+
+/// Where replies go, shared with the engine's progress callback, and the debug log if there is one.
+#[derive(Clone)]
+struct Output(Arc<Mutex<Channels>>);
+
+struct Channels {
+    writer: Box<dyn Write + Send>,
+    debug_log: Option<File>
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        Output::new(Box::new(io::stdout()))
+    }
+}
+
+impl Output {
+    fn new(writer: Box<dyn Write + Send>) -> Output {
+        Output(Arc::new(Mutex::new(Channels {writer, debug_log: None})))
+    }
+
+    fn channels(&self) -> std::sync::MutexGuard<'_, Channels> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn line(&self, line: impl Display) {
+        self.text(format_args!("{}\n", line));
+    }
+
+    //The GUI reads replies as they come, so every write is flushed. A GUI that stopped reading has nothing
+    //more to be told, and the debug log is only a help, so write errors are ignored.
+    fn text(&self, text: impl Display) {
+        let text = text.to_string();
+        let mut channels = self.channels();
+        let _ = channels.writer.write_all(text.as_bytes()).and_then(|_| channels.writer.flush());
+        if let Some(debug_log) = &mut channels.debug_log {
+            for line in text.lines() {
+                let _ = writeln!(debug_log, "{} << {}", timestamp(), line);
+            }
+        }
+    }
+
+    //Lines read are marked ">>", as lines written are marked "<<"
+    fn log_input(&self, line: &str) {
+        if let Some(debug_log) = &mut self.channels().debug_log {
+            let _ = writeln!(debug_log, "{} >> {}", timestamp(), line);
+        }
+    }
+
+    fn set_debug_log(&self, debug_log: Option<File>) {
+        self.channels().debug_log = debug_log;
+    }
+}
+
+//Seconds since the Unix epoch, to the millisecond
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:03}", now.as_secs(), now.subsec_millis())
+}
+
+//A log that can't be opened is reported, and nothing is logged until another file is set
+fn open_debug_log(output: &Output, path: &Path) {
+    match OpenOptions::new().append(true).create(true).open(path) {
+        Ok(debug_log) => output.set_debug_log(Some(debug_log)),
+        Err(error) => {
+            output.set_debug_log(None);
+            output.line(format_args!("info string could not open debug log file {}: {}", path.display(), error));
+        }
+    }
+}
+
+//Iterations are printed as they finish, with castling written for the current castling mode
+fn set_progress_callback(game_state: &mut GameState) {
+    let castling_mode = CastlingMode::from_chess960(game_state.chess960);
+    let show_wdl = game_state.show_wdl;
+    let output = game_state.output.clone();
+    game_state.engine.set_progress_callback(Some(Box::new(move |event| {
+        match event {
+            SearchEvent::Iteration(result) => print_info(&output, result, castling_mode, show_wdl),
+            SearchEvent::CurrentMove { depth, mv, number } => {
+                output.line(format_args!("info depth {} currmove {} currmovenumber {}", depth, mv.to_uci(castling_mode), number))
+            },
+            SearchEvent::Info(line) => output.line(line)
+        }
+    })));
+}
+
+fn print_info(output: &Output, result: &SearchResult, castling_mode: CastlingMode, show_wdl: bool) {
+    let mut score = match result.mate_in() {
+        Some(mate_in) => format!("mate {}", mate_in),
+        None => format!("cp {}", result.normalized_score())
+    };
+    if show_wdl {
+        score = format!("{} wdl {}", score, result.wdl());
+    }
+    let pv: Vec<String> = result.pv.iter().map(|m| m.to_uci(castling_mode).to_string()).collect();
+    output.line(format_args!("info depth {} seldepth {} score {} nodes {} time {} hashfull {} pv {}",
+                             result.depth, result.seldepth, score, result.nodes, result.time.as_millis(), result.hashfull, pv.join(" ")));
+}
+
+//Prints the move counts after each legal move and their total, in the format other engines use
+fn print_perft(game_state: &GameState, depth: Option<&str>) {
+    let Some(depth) = depth.and_then(|depth| depth.parse().ok()) else {
+        return;
+    };
+
+    let castling_mode = CastlingMode::from_chess960(game_state.chess960);
+    let divided = perft::divide(&game_state.chess, depth);
+    for (m, nodes) in &divided {
+        game_state.output.line(format_args!("{}: {}", m.to_uci(castling_mode), nodes));
+    }
+    game_state.output.line("");
+    game_state.output.line(format_args!("Nodes searched: {}", divided.iter().map(|&(_, nodes)| nodes).sum::<u64>()));
+}
+
+fn print_eval(game_state: &GameState) {
+    let params = game_state.engine.eval_params();
+    let breakdown = evaluate_board_detailed_with_params(game_state.chess.board(), &params);
+    let evaluation = game_state.engine.evaluator().evaluate_with_params(&game_state.chess, breakdown.total(), &params);
+    game_state.output.text(breakdown);
+    //Variants score more than the board, such as pockets or checks given
+    if evaluation != breakdown.total() {
+        game_state.output.line(format_args!("{:<16}{:>27}", "Variant", format!("{:+.2}", (evaluation - breakdown.total()) as f64 / 100.0)));
+    }
+    game_state.output.line("");
+    game_state.output.line(format_args!("Final evaluation: {:+.2} (white side)", evaluation as f64 / 100.0));
+}
+
+fn set_option(tokens: Vec<&str>, game_state: &mut GameState) {
+    //Format is "setoption name <name> [value <value>]" where the name may contain spaces
+    let value_index = tokens.iter().position(|&token| token == "value").unwrap_or(tokens.len());
+    let name = tokens[..value_index].iter().skip_while(|&&token| token == "name").copied().collect::<Vec<_>>().join(" ");
+    let value = tokens.get(value_index + 1..).unwrap_or_default().join(" ");
+
+    //Unknown options are ignored
+    if name.eq_ignore_ascii_case("maxmovetime") {
+        if let Ok(max_move_time) = value.parse() {
+            let max_move_time = (max_move_time > 0).then(|| Duration::from_millis(max_move_time));
+            game_state.engine.set_max_move_time(max_move_time);
+        }
+    }
+    else if name.eq_ignore_ascii_case("move overhead") {
+        if let Ok(move_overhead) = value.parse() {
+            game_state.engine.set_move_overhead(Duration::from_millis(move_overhead));
+        }
+    }
+    else if name.eq_ignore_ascii_case("threads") {
+        if let Ok(threads) = value.parse() {
+            game_state.engine.set_threads(threads);
+        }
+    }
+    else if name.eq_ignore_ascii_case("deterministic") {
+        game_state.engine.set_deterministic(value.eq_ignore_ascii_case("true"));
+    }
+    else if name.eq_ignore_ascii_case("contempt") {
+        if let Ok(contempt) = value.parse() {
+            game_state.engine.set_contempt(contempt);
+        }
+    }
+    else if name.eq_ignore_ascii_case("personality") {
+        match Personality::from_name(&value) {
+            Some(personality) => game_state.engine.set_personality(personality),
+            None => game_state.output.line(format_args!("info string unknown personality {}", value))
+        }
+    }
+    else if let Some(&(_, max, param)) = EVAL_PARAM_OPTIONS.iter().find(|(option, ..)| option.eq_ignore_ascii_case(&name)) {
+        if let Ok(value) = value.parse::<i32>() {
+            let mut params = game_state.engine.eval_params();
+            *param(&mut params) = value.clamp(0, max);
+            game_state.engine.set_eval_params(params);
+        }
+    }
+    else if name.eq_ignore_ascii_case("skill level") {
+        if let Ok(skill_level) = value.parse() {
+            game_state.engine.set_skill_level(skill_level);
+        }
+    }
+    else if name.eq_ignore_ascii_case("uci_limitstrength") {
+        game_state.limit_strength = value.eq_ignore_ascii_case("true");
+        game_state.engine.set_elo_limit(game_state.limit_strength.then(|| game_state.elo.unwrap_or(MAX_ELO)));
+    }
+    else if name.eq_ignore_ascii_case("uci_elo") {
+        if let Ok(elo) = value.parse() {
+            game_state.elo = Some(elo);
+            game_state.engine.set_elo_limit(game_state.limit_strength.then_some(elo));
+        }
+    }
+    else if name.eq_ignore_ascii_case("ownbook") {
+        game_state.own_book = value.eq_ignore_ascii_case("true");
+        load_book(game_state);
+    }
+    else if name.eq_ignore_ascii_case("bookfile") {
+        game_state.book_file = (!value.is_empty() && value != "<empty>").then_some(value);
+        load_book(game_state);
+    }
+    else if name.eq_ignore_ascii_case("repertoirefile") {
+        let repertoire = match value.as_str() {
+            "" | "<empty>" => None,
+            path => match Repertoire::open(path) {
+                Ok(repertoire) => Some(repertoire),
+                Err(error) => {
+                    game_state.output.line(format_args!("info string could not read repertoire {}: {}", path, error));
+                    None
+                }
+            }
+        };
+        game_state.engine.set_repertoire(repertoire);
+    }
+    else if name.eq_ignore_ascii_case("piecesquaretablefile") {
+        let tables = match value.as_str() {
+            "" | "<empty>" => None,
+            path => match PieceSquareTables::open(path) {
+                Ok(tables) => Some(tables),
+                Err(error) => {
+                    game_state.output.line(format_args!("info string could not read piece square tables {}: {}", path, error));
+                    None
+                }
+            }
+        };
+        game_state.engine.set_piece_square_tables(tables);
+    }
+    else if name.eq_ignore_ascii_case("uci_variant") {
+        match Variant::from_uci(&value) {
+            Ok(variant) if UCI_VARIANTS.contains(&variant) => game_state.variant = variant,
+            _ => game_state.output.line(format_args!("info string unsupported variant {}", value))
+        }
+    }
+    else if name.eq_ignore_ascii_case("debug log file") {
+        match value.as_str() {
+            "" | "<empty>" => game_state.output.set_debug_log(None),
+            path => open_debug_log(&game_state.output, Path::new(path))
+        }
+    }
+    else if name.eq_ignore_ascii_case("uci_chess960") {
+        game_state.chess960 = value.eq_ignore_ascii_case("true");
+        set_progress_callback(game_state);
+    }
+    else if name.eq_ignore_ascii_case("uci_showwdl") {
+        game_state.show_wdl = value.eq_ignore_ascii_case("true");
+        set_progress_callback(game_state);
+    }
+}
+
+//The book is only read once it is both enabled and has a file
+fn load_book(game_state: &mut GameState) {
+    let book = match (game_state.own_book, &game_state.book_file) {
+        (true, Some(book_file)) => match Book::open(book_file) {
+            Ok(book) => Some(book),
+            Err(error) => {
+                game_state.output.line(format_args!("info string could not read book {}: {}", book_file, error));
+                None
+            }
+        },
+        _ => None
+    };
+    game_state.engine.set_book(book);
+}
+
+//Sets up the position, or says what is wrong with it and leaves the game as it was
+fn update_position(position: Vec<String>, game_state: &mut GameState) -> Result<(), String> {
+    let mut tokens = position.iter().map(String::as_str);
+    //Format is "startpos" or "fen <fen>", where the fen is split over several tokens, followed by "moves <moves>"
+    let start: Vec<&str> = tokens.by_ref().take_while(|&token| token != "moves").collect();
+
+    let mut chess = match start.first() {
+        None => return Err("expected startpos or fen".to_string()),
+        Some(&"startpos") => VariantPosition::new(game_state.variant),
+        Some(_) => {
+            let fen = start.iter().skip_while(|&&token| token == "fen").copied().collect::<Vec<_>>().join(" ");
+            let setup = Fen::from_ascii(fen.as_bytes()).map_err(|error| format!("invalid fen {}: {}", fen, error))?.0;
+            VariantPosition::from_setup(game_state.variant, setup, CastlingMode::from_chess960(game_state.chess960))
+                .map_err(|error| format!("invalid position {}: {}", fen, error))?
+        }
+    };
+
+    let starting_pos_hash: Zobrist64 = chess.zobrist_hash(shakmaty::EnPassantMode::Legal);
+    let mut hashes_seen = vec![starting_pos_hash.0];
+
+    for m in tokens {
+        let selected_move = UciMove::from_ascii(m.as_bytes()).map_err(|error| format!("invalid move {}: {}", m, error))?;
+        let legal_move = selected_move.to_move(&chess).map_err(|error| format!("illegal move {}: {}", m, error))?;
+        if legal_move.is_zeroing() {
+            hashes_seen.clear();
+        }
+        chess.play_unchecked(&legal_move);
+        let hash: Zobrist64 = chess.zobrist_hash(shakmaty::EnPassantMode::Legal);
+        hashes_seen.push(hash.0);
+    }
+
+    game_state.chess = chess;
+    game_state.previously_seen_hashes = hashes_seen;
+    Ok(())
+}
+
+fn parse_move(m: &str, chess: &VariantPosition) -> Option<Move> {
+    UciMove::from_ascii(m.as_bytes()).ok()?.to_move(chess).ok()
+}
+
+fn identify_engine(output: &Output) {
+    output.line("id name Hodgeybot");
+    output.line("id author Jixen");
+}
+
+fn display_options(output: &Output) {
+    output.line("option name Move Overhead type spin default 100 min 0 max 5000");
+    output.line("option name MaxMoveTime type spin default 0 min 0 max 3600000");
+    output.line(format_args!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS));
+    output.line("option name Deterministic type check default false");
+    output.line("option name Contempt type spin default 0 min -100 max 100");
+    let personalities: Vec<String> = Personality::ALL.iter().map(|personality| format!("var {}", personality.name())).collect();
+    output.line(format_args!("option name Personality type combo default {} {}", Personality::default().name(), personalities.join(" ")));
+    for (option, max, param) in EVAL_PARAM_OPTIONS {
+        output.line(format_args!("option name {} type spin default {} min 0 max {}", option, param(&mut EvalParams::default()), max));
+    }
+    output.line(format_args!("option name Skill Level type spin default {} min 0 max {}", MAX_SKILL_LEVEL, MAX_SKILL_LEVEL));
+    output.line("option name UCI_LimitStrength type check default false");
+    output.line(format_args!("option name UCI_Elo type spin default {} min {} max {}", MAX_ELO, MIN_ELO, MAX_ELO));
+    output.line("option name OwnBook type check default false");
+    output.line("option name BookFile type string default <empty>");
+    output.line("option name RepertoireFile type string default <empty>");
+    output.line("option name PieceSquareTableFile type string default <empty>");
+    output.line("option name Debug Log File type string default <empty>");
+    output.line("option name UCI_Chess960 type check default false");
+    output.line("option name UCI_ShowWDL type check default false");
+    let variants: Vec<String> = UCI_VARIANTS.iter().map(|variant| format!("var {}", variant.uci())).collect();
+    output.line(format_args!("option name UCI_Variant type combo default {} {}", UCI_VARIANTS[0].uci(), variants.join(" ")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Collects everything written, shared so it can be read after the writer is handed over
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run_commands(commands: &str) -> String {
+        let buffer = SharedBuffer::default();
+        run(commands.as_bytes(), buffer.clone(), UciOptions::default()).expect("commands should be read");
+        let output = buffer.0.lock().unwrap().clone();
+        String::from_utf8(output).expect("output should be text")
+    }
+
+    #[test]
+    //A scripted session should get its replies on the writer and stop at quit
+    fn scripted_session() {
+        //The search is left to finish by waiting for it with another search
+        let output = run_commands("uci\nisready\nposition startpos moves e2e4 e7e5\ngo depth 3\ngo depth 3\nquit\ngo depth 3\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0] == "id name Hodgeybot");
+        assert!(lines.contains(&"uciok") && lines.contains(&"readyok"));
+        assert!(lines.iter().any(|line| line.starts_with("info depth 3 ")));
+        assert!(lines.iter().filter(|line| line.starts_with("bestmove ")).count() == 2);
+    }
+
+    #[test]
+    //Malformed positions should be reported and leave the last good position in place
+    fn malformed_input() {
+        let output = run_commands("position startpos moves e2e4\nposition fen 8/8/8 w - - 0 1\nposition startpos moves e7e5\n\
+                                   position startpos moves e2e4 zz\nposition\ngo wtime soon depth 1\ngo perft 1\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.iter().filter(|line| line.starts_with("info string ignoring position")).count() == 4);
+        //The search with the unreadable time value is still answered, and the last good position is after 1. e4
+        assert!(lines.iter().any(|line| line.starts_with("bestmove ")));
+        assert!(lines.contains(&"e7e5: 1") && output.ends_with("Nodes searched: 20\n"));
+    }
+
+    #[test]
+    //Infinite and pondering searches should only send their move once stopped or once the pondered move is played
+    fn infinite_and_ponder() {
+        let output = run_commands("go infinite\nisready\nstop\ngo\nstop\ngo ponder wtime 1000 btime 1000\nponderhit\n\
+                                   go ponder wtime 1000 btime 1000\nstop\n");
+        let lines: Vec<&str> = output.lines().collect();
+        let ready = lines.iter().position(|&line| line == "readyok").expect("isready should be answered");
+        assert!(lines[..ready].iter().all(|line| !line.starts_with("bestmove ")));
+        assert!(lines.iter().filter(|line| line.starts_with("bestmove ")).count() == 4);
+    }
+
+    #[test]
+    //The debug log should record both sides of the session with timestamps, and only while it is set
+    fn debug_log_file() {
+        let path = std::env::temp_dir().join(format!("hodgey_debug_log_{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let commands = format!("setoption name Debug Log File value {}\nisready\nsetoption name Debug Log File value <empty>\nuci\n",
+                               path.display());
+        let output = run_commands(&commands);
+        assert!(output.contains("readyok") && output.contains("uciok"));
+
+        let log = std::fs::read_to_string(&path).expect("log should be written");
+        std::fs::remove_file(&path).expect("log should be removed");
+        let lines: Vec<&str> = log.lines().collect();
+        assert!(lines.len() == 3);
+        assert!(lines[0].ends_with(" >> isready") && lines[1].ends_with(" << readyok") && lines[2].ends_with(" >> setoption name Debug Log File value <empty>"));
+        assert!(lines.iter().all(|line| line.split(' ').next().is_some_and(|time| time.parse::<f64>().is_ok())));
+    }
+
+    #[test]
+    //The input should be able to end without quit, and perft should count on the given position
+    fn end_of_input() {
+        let output = run_commands("position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1\ngo perft 1");
+        assert!(output.ends_with("Nodes searched: 15\n"));
+    }
+
+    #[test]
+    //A search still running when the input ends should be stopped and send its move rather than running on
+    fn stopped_at_end_of_input() {
+        for commands in ["go depth 100\n", "go movetime 600000\nquit\n", "go ponder wtime 600000 btime 600000\n"] {
+            let output = run_commands(commands);
+            assert!(output.lines().filter(|line| line.starts_with("bestmove ")).count() == 1);
+        }
+    }
+
+    #[test]
+    //Win, draw and loss chances should only be reported once UCI_ShowWDL is set
+    fn show_wdl() {
+        let output = run_commands("go depth 2\nsetoption name UCI_ShowWDL value true\ngo depth 2\n");
+        let (before, after) = output.split_once("bestmove ").expect("the first search should send its move");
+        assert!(before.lines().filter(|line| line.starts_with("info depth")).all(|line| !line.contains(" wdl ")));
+        let wdl_line = after.lines().find(|line| line.contains(" wdl ")).expect("the second search should report wdl");
+        let chances: Vec<u32> = wdl_line.split(" wdl ").nth(1).unwrap().split(' ').take(3).map(|chance| chance.parse().unwrap()).collect();
+        assert!(chances.iter().sum::<u32>() == 1000);
+    }
+}
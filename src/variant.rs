@@ -0,0 +1,223 @@
+//! Positions the engine can search.
+//!
+//! The search works on any shakmaty [`Position`], including the variants from [`shakmaty::variant`].
+//! Each kind of position brings its own evaluation through [`SearchPosition`]. Variants start out with
+//! the standard chess evaluation, which at least knows the value of material. Crazyhouse also counts the
+//! pieces waiting in each pocket, and antichess turns the evaluation around since it is won by losing pieces.
+//! King of the Hill rewards kings close to the center and Three-check rewards the checks already given.
+//! Standard chess looks king and pawn against king endings up in a bitbase, see [`crate::kpk`], and knows
+//! how to mate a lone king, see [`crate::endgame`]. It also counts the castling rights each side has kept.
+
+use shakmaty::variant::{Antichess, Atomic, Crazyhouse, Horde, KingOfTheHill, RacingKings, ThreeCheck, VariantPosition};
+use shakmaty::{Bitboard, Board, ByColor, CastlingSide, Chess, Color, Position, RemainingChecks, Square};
+
+use crate::endgame::evaluate_mating;
+use crate::evaluation::{evaluate_board, game_phase, pocket_value, MAX_GAME_PHASE};
+use crate::kpk;
+
+//Bonus for each king move closer to the center, where reaching it wins in King of the Hill
+const KING_CENTRALIZATION_BONUS: i32 = 50;
+//Bonus for every check given in Three-check, the third wins the game
+const CHECK_BONUS: i32 = 250;
+const THREE_CHECK_CHECKS: u32 = 3;
+//Bonus for each side a king can still castle to, fading out as pieces come off the board
+const CASTLING_RIGHTS_BONUS: i32 = 10;
+
+/// A position the engine knows how to evaluate. Positions are shared between the threads of a search.
+pub trait SearchPosition: Position + Clone + Default + Send + Sync {
+    /// Returns an evaluation of the position from the perspective of white.
+    fn evaluate(&self) -> i32 {
+        self.evaluate_with_board(evaluate_board(self.board()))
+    }
+
+    /// [`SearchPosition::evaluate`] given the material and piece square evaluation of the board, which the
+    /// search keeps up to date as it plays moves rather than adding up every piece. Kinds of positions that
+    /// evaluate differently from standard chess add their own terms here.
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        board_evaluation
+    }
+
+    /// Whether the evaluation is exact from knowledge of the endgame, so quiescence can stop at it
+    /// instead of searching captures.
+    fn is_known_endgame(&self) -> bool {
+        false
+    }
+}
+
+impl SearchPosition for Chess {
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        kpk::evaluate(self.board(), self.turn(), board_evaluation)
+            .or_else(|| evaluate_mating(self.board()))
+            .unwrap_or_else(|| board_evaluation + castling_rights(self))
+    }
+
+    fn is_known_endgame(&self) -> bool {
+        kpk::probe(self.board(), self.turn()).is_some()
+    }
+}
+
+impl SearchPosition for Atomic {}
+impl SearchPosition for RacingKings {}
+impl SearchPosition for Horde {}
+
+impl SearchPosition for Antichess {
+    //Every piece is a liability, so material counts against the side that has it
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        -board_evaluation
+    }
+}
+
+impl SearchPosition for KingOfTheHill {
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        let centralization = king_centralization(self.board(), Color::White) - king_centralization(self.board(), Color::Black);
+        board_evaluation + centralization * KING_CENTRALIZATION_BONUS
+    }
+}
+
+impl SearchPosition for ThreeCheck {
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        let checks = self.remaining_checks().map_or(0, |remaining_checks| checks_given(remaining_checks, Color::White) - checks_given(remaining_checks, Color::Black));
+        board_evaluation + checks * CHECK_BONUS
+    }
+}
+
+impl SearchPosition for Crazyhouse {
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        let pockets = self.pockets().map_or(0, |pockets| pocket_value(&pockets.white) - pocket_value(&pockets.black));
+        board_evaluation + pockets
+    }
+}
+
+/// 3 less the number of king moves `color`'s king needs to reach the center, so 3 once it is there.
+fn king_centralization(board: &Board, color: Color) -> i32 {
+    let Some(king) = board.king_of(color) else {
+        return 0;
+    };
+    let distance = Bitboard::CENTER.into_iter().map(|center: Square| king.distance(center)).min().unwrap_or(0);
+    3 - distance as i32
+}
+
+/// The bonus for castling rights kept, from white's point of view.
+fn castling_rights(chess: &Chess) -> i32 {
+    let rights = |color: Color| CastlingSide::ALL.into_iter().filter(|&side| chess.castles().has(color, side)).count() as i32;
+    (rights(Color::White) - rights(Color::Black)) * CASTLING_RIGHTS_BONUS * game_phase(chess.board()) / MAX_GAME_PHASE
+}
+
+fn checks_given(remaining_checks: &ByColor<RemainingChecks>, color: Color) -> i32 {
+    let remaining: u32 = (*remaining_checks.get(color)).into();
+    THREE_CHECK_CHECKS.saturating_sub(remaining) as i32
+}
+
+impl SearchPosition for VariantPosition {
+    fn evaluate_with_board(&self, board_evaluation: i32) -> i32 {
+        match self {
+            VariantPosition::Chess(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::Atomic(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::Antichess(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::KingOfTheHill(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::ThreeCheck(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::Crazyhouse(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::RacingKings(position) => position.evaluate_with_board(board_evaluation),
+            VariantPosition::Horde(position) => position.evaluate_with_board(board_evaluation)
+        }
+    }
+
+    fn is_known_endgame(&self) -> bool {
+        match self {
+            VariantPosition::Chess(position) => position.is_known_endgame(),
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, FromSetup};
+    use shakmaty::variant::Variant;
+
+    fn crazyhouse(fen: &str) -> Crazyhouse {
+        let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+        Crazyhouse::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+    }
+
+    #[test]
+    //Pieces in a pocket should count for the side that can drop them
+    fn pockets_count_as_material() {
+        let empty = crazyhouse("4k3/8/8/8/8/8/8/4K3[] w - - 0 1");
+        let white_knight = crazyhouse("4k3/8/8/8/8/8/8/4K3[N] w - - 0 1");
+        let black_knight = crazyhouse("4k3/8/8/8/8/8/8/4K3[n] w - - 0 1");
+        assert!(white_knight.evaluate() > empty.evaluate());
+        assert!(black_knight.evaluate() < empty.evaluate());
+        assert!(VariantPosition::Crazyhouse(white_knight.clone()).evaluate() == white_knight.evaluate());
+    }
+
+    #[test]
+    //Antichess should prefer having less material
+    fn antichess_inverts_material() {
+        let setup = Fen::from_ascii("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1".as_bytes()).expect("Fen should be valid").0;
+        let antichess = VariantPosition::from_setup(Variant::Antichess, setup.clone(), CastlingMode::Standard).expect("position should be valid");
+        let chess = Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid");
+        assert!(antichess.evaluate() < 0);
+        assert!(antichess.evaluate() == -chess.evaluate());
+    }
+
+    #[test]
+    //King of the Hill should pull the king towards the center
+    fn hill_centralization() {
+        let position = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            KingOfTheHill::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+        };
+        let corner = position("4k3/8/8/8/8/8/8/K7 w - - 0 1");
+        let closer = position("4k3/8/8/8/8/8/1K6/8 w - - 0 1");
+        let center = position("4k3/8/8/8/8/2K5/8/8 w - - 0 1");
+        assert!(corner.evaluate() < closer.evaluate() && closer.evaluate() < center.evaluate());
+        assert!(king_centralization(center.board(), Color::White) == 2);
+    }
+
+    #[test]
+    //Three-check should count checks given for the side that gave them
+    fn checks_count() {
+        let position = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            ThreeCheck::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+        };
+        let none = position("4k3/8/8/8/8/8/8/4K3 w - - 3+3 0 1");
+        let white_two = position("4k3/8/8/8/8/8/8/4K3 w - - 1+3 0 1");
+        let black_one = position("4k3/8/8/8/8/8/8/4K3 w - - 3+2 0 1");
+        assert!(white_two.evaluate() - none.evaluate() == 2 * CHECK_BONUS);
+        assert!(black_one.evaluate() - none.evaluate() == -CHECK_BONUS);
+    }
+
+    #[test]
+    //Only the side that has given up its castling rights should lose the bonus for them
+    fn castling_rights_count() {
+        let position = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+        };
+        let both = position("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1");
+        let black_kingside = position("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQq - 0 1");
+        assert!(castling_rights(&both) == 0);
+        assert!(castling_rights(&black_kingside) > 0 && both.evaluate() < black_kingside.evaluate());
+    }
+
+    #[test]
+    //King and pawn against king should be scored from the bitbase in standard chess only
+    fn kpk_endings() {
+        let position = |fen: &str| {
+            let setup = Fen::from_ascii(fen.as_bytes()).expect("Fen should be valid").0;
+            Chess::from_setup(setup, CastlingMode::Standard).expect("position should be valid")
+        };
+        let drawn = position("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1");
+        let won = position("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1");
+        assert!(drawn.evaluate() == 0 && drawn.is_known_endgame());
+        assert!(won.evaluate() > evaluate_board(won.board()));
+        assert!(VariantPosition::Chess(drawn.clone()).evaluate() == 0);
+
+        let atomic = VariantPosition::from_setup(Variant::Atomic, Fen::from_ascii("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1".as_bytes()).expect("Fen should be valid").0, CastlingMode::Standard).expect("position should be valid");
+        assert!(!atomic.is_known_endgame() && atomic.evaluate() == evaluate_board(atomic.board()));
+    }
+}
@@ -0,0 +1,104 @@
+//! Win, draw and loss chances estimated from a score, for the `wdl` that GUIs show next to the score, and the
+//! scaling of scores to the centipawns that are reported.
+//!
+//! The chance of winning is a logistic curve of the score, and the chance of losing is the same curve for the
+//! other side, so whatever is left over is a draw. Level positions come out mostly drawn, the way games between
+//! engines usually go. Reported scores are scaled so that 100 centipawns is where a win becomes as likely as not,
+//! which is what a score of one pawn means for most other engines too.
+
+use std::fmt;
+
+use crate::mate_in;
+
+/// The search score at which the side to move wins as often as not, reported as 100 centipawns.
+/// Search scores run higher than that for an extra pawn since winning material also earns the trade bonus.
+pub const NORMALIZED_PAWN: i32 = 160;
+//How gradually the chance of winning rises around the normalized pawn
+const WIN_SPREAD: f64 = 65.0;
+
+/// Scales a search score to reported centipawns, leaving mates as they are.
+pub fn normalize_score(score: i32) -> i32 {
+    match mate_in(score) {
+        Some(_) => score,
+        None => score * 100 / NORMALIZED_PAWN
+    }
+}
+
+/// Chances of winning, drawing and losing in permille, from the point of view of the side to move.
+/// They always add up to 1000.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Wdl {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32
+}
+
+impl Wdl {
+    /// The chances for a search score, where mates are certain wins or losses.
+    pub fn from_score(score: i32) -> Wdl {
+        match mate_in(score) {
+            Some(mate_in) if mate_in > 0 => Wdl { win: 1000, draw: 0, loss: 0 },
+            Some(_) => Wdl { win: 0, draw: 0, loss: 1000 },
+            None => {
+                let win = win_permille(score);
+                let loss = win_permille(-score);
+                Wdl { win, draw: 1000 - win - loss, loss }
+            }
+        }
+    }
+}
+
+//Rounded down so the win and loss chances never add up to more than 1000
+fn win_permille(score: i32) -> u32 {
+    let chance = 1.0 / (1.0 + ((NORMALIZED_PAWN as f64 - score as f64) / WIN_SPREAD).exp());
+    (chance * 1000.0) as u32
+}
+
+/// Written the way UCI reports it, as "win draw loss".
+impl fmt::Display for Wdl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.win, self.draw, self.loss)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CHECKMATE_SCORE;
+
+    #[test]
+    //Chances should add up, mirror for the other side and favor whoever is ahead
+    fn chances_from_score() {
+        let level = Wdl::from_score(0);
+        assert!(level.win == level.loss && level.draw > level.win);
+
+        let mut previous_win = 0;
+        for score in (-1000..=1000).step_by(50) {
+            let wdl = Wdl::from_score(score);
+            let mirrored = Wdl::from_score(-score);
+            assert!(wdl.win + wdl.draw + wdl.loss == 1000);
+            assert!(wdl.win == mirrored.loss && wdl.loss == mirrored.win);
+            assert!(wdl.win >= previous_win);
+            previous_win = wdl.win;
+        }
+        assert!(Wdl::from_score(1000).win > 990);
+    }
+
+    #[test]
+    //A normalized pawn should be reported as 100 centipawns and be won half the time, and mates shouldn't be scaled
+    fn normalized_scores() {
+        assert!(normalize_score(NORMALIZED_PAWN) == 100 && normalize_score(-NORMALIZED_PAWN) == -100);
+        assert!(normalize_score(0) == 0);
+        assert!(Wdl::from_score(NORMALIZED_PAWN).win == 500);
+        assert!(normalize_score(CHECKMATE_SCORE - 3) == CHECKMATE_SCORE - 3);
+    }
+
+    #[test]
+    //Mates should be certain either way
+    fn mate_chances() {
+        assert!(Wdl::from_score(CHECKMATE_SCORE - 3) == Wdl { win: 1000, draw: 0, loss: 0 });
+        assert!(Wdl::from_score(-(CHECKMATE_SCORE - 4)) == Wdl { win: 0, draw: 0, loss: 1000 });
+        assert!(Wdl::from_score(0).to_string().split(' ').count() == 3);
+    }
+}